@@ -0,0 +1,184 @@
+use std::borrow::Cow;
+
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::{Hint, Hinter};
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper, Result};
+
+use termcalc::engine::{self, Engine};
+
+//the commands of the terminal, the others are in engine::COMMANDS
+const COMMANDS: &[&str] = &[":q", ":quit", ":hist", ":vars", ":fns"];
+
+//the commands whose argument is a path
+const PATH_COMMANDS: &[&str] = &[":load", ":save", ":restore", ":autosave"];
+
+//completes names, commands and paths with tab, and shows the arguments after 'name('
+pub struct Completion {
+    files: FilenameCompleter,
+    //stdlib and user functions with their arguments, e.g. ('pmt', 'rate, nper, pv, fv')
+    functions: Vec<(String, String)>,
+    vars: Vec<String>
+}
+
+impl Completion {
+    pub fn new() -> Completion {
+        Completion { files: FilenameCompleter::new(), functions: Vec::new(), vars: Vec::new() }
+    }
+
+    //takes the names of the engine, before every line
+    pub fn update(&mut self, engine: &Engine) {
+        self.functions = engine::builtin_functions().iter()
+            .filter(|x| !engine.functions().contains_key(x.0))
+            .map(|x| (x.0.to_owned(), x.1.to_owned()))
+            .collect();
+        self.functions.extend(engine.functions().iter().map(|(name, func)| (name.clone(), func.args().join(", "))));
+        self.functions.sort();
+        self.vars = engine.vars().keys().cloned().collect();
+        self.vars.sort();
+    }
+
+    fn arguments(&self, name: &str) -> Option<&String> {
+        self.functions.iter().find(|x| x.0 == name).map(|x| &x.1)
+    }
+}
+
+impl Completer for Completion {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context) -> Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        if PATH_COMMANDS.iter().any(|x| before.starts_with(&format!("{} ", x))) {
+            return self.files.complete_path(line, pos)
+        }
+        if before.starts_with(':') && !before.contains(' ') {
+            let commands = engine::COMMANDS.iter().chain(COMMANDS.iter())
+                .filter(|x| x.starts_with(before))
+                .map(|x| Pair { display: x.to_string(), replacement: format!("{} ", x) })
+                .collect();
+            return Ok((0, commands))
+        }
+        let start = word_start(before);
+        let word = &before[start..];
+        if word.is_empty() {
+            return Ok((pos, Vec::new()))
+        }
+        let mut names = self.functions.iter()
+            .filter(|x| x.0.starts_with(word))
+            .map(|x| Pair { display: format!("{}({})", x.0, x.1), replacement: format!("{}(", x.0) })
+            .collect::<Vec<Pair>>();
+        names.extend(self.vars.iter()
+            .filter(|x| x.starts_with(word))
+            .map(|x| Pair { display: x.clone(), replacement: x.clone() }));
+        Ok((start, names))
+    }
+}
+
+//the arguments shown after 'name(', they are not inserted by the right arrow key
+pub struct Arguments(String);
+
+impl Hint for Arguments {
+    fn display(&self) -> &str {
+        &self.0
+    }
+
+    fn completion(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl Hinter for Completion {
+    type Hint = Arguments;
+
+    //'pmt(' shows 'rate, nper, pv, fv)' after the cursor
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context) -> Option<Arguments> {
+        if pos < line.len() || !line.ends_with('(') {
+            return None
+        }
+        let before = &line[..pos - 1];
+        self.arguments(&before[word_start(before)..]).map(|x| Arguments(format!("{})", x)))
+    }
+}
+
+impl Highlighter for Completion {
+    //hints are dimmed
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("\x1b[2m{}\x1b[0m", hint))
+    }
+}
+
+impl Validator for Completion {}
+
+impl Helper for Completion {}
+
+//the index of the name that ends at the end of text
+fn word_start(text: &str) -> usize {
+    text.char_indices().rev()
+        .take_while(|x| x.1.is_alphanumeric() || x.1 == '_' || x.1 == '$' || x.1 == '.' || x.1 == '\'')
+        .last()
+        .map_or(text.len(), |x| x.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::history::History;
+
+    //the replacements for the line with the cursor at its end, and where they start
+    fn complete(completion: &Completion, line: &str) -> (usize, Vec<String>) {
+        let history = History::new();
+        let (start, pairs) = completion.complete(line, line.len(), &Context::new(&history)).unwrap();
+        (start, pairs.into_iter().map(|x| x.replacement).collect())
+    }
+
+    fn hint(completion: &Completion, line: &str) -> Option<String> {
+        let history = History::new();
+        completion.hint(line, line.len(), &Context::new(&history)).map(|x| x.0)
+    }
+
+    fn completion() -> Completion {
+        let mut engine = Engine::new();
+        engine.eval_line(format!(":var Rate 0.05"));
+        engine.eval_line(format!(":fn rect W H = W*H"));
+        engine.eval_line(format!(":fn pmt R = R"));
+        let mut completion = Completion::new();
+        Completion::update(&mut completion, &engine);
+        completion
+    }
+
+    #[test]
+    fn names() {
+        let completion = completion();
+        assert_eq!(complete(&completion, "2 * rec"), (4, vec![format!("rect(")]));
+        assert_eq!(complete(&completion, "1 + Ra"), (4, vec![format!("Rate")]));
+        assert_eq!(complete(&completion, "2 + "), (4, Vec::new()));
+        //the user function replaces the stdlib one of the same name
+        assert_eq!(complete(&completion, "pmt"), (0, vec![format!("pmt(")]));
+        assert_eq!(completion.arguments("pmt").unwrap(), "R");
+    }
+
+    #[test]
+    fn commands() {
+        let completion = completion();
+        assert_eq!(complete(&completion, ":re"), (0, vec![format!(":redo "), format!(":restore ")]));
+        assert_eq!(complete(&completion, ":q"), (0, vec![format!(":q "), format!(":quit ")]));
+        assert_eq!(complete(&completion, ":x"), (0, Vec::new()));
+    }
+
+    #[test]
+    fn hints() {
+        let completion = completion();
+        assert_eq!(hint(&completion, "rect("), Some(format!("W, H)")));
+        assert_eq!(hint(&completion, "1 + npv("), Some(format!("rate, cashflows)")));
+        assert_eq!(hint(&completion, "unknown("), None);
+        assert_eq!(hint(&completion, "rect"), None);
+    }
+
+    #[test]
+    fn word_starts() {
+        assert_eq!(word_start("1 + geo.area"), 4);
+        assert_eq!(word_start("f($1"), 2);
+        assert_eq!(word_start("2 + "), 4);
+    }
+}
@@ -0,0 +1,146 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+//older entries are dropped when termcalc starts
+const MAX_ENTRIES: usize = 1000;
+
+//the lines typed in with their results, kept in a file between runs
+pub struct History {
+    path: Option<PathBuf>,
+    //input and result, e.g. ('2+3', '$0: 5 [int]')
+    entries: Vec<(String, String)>
+}
+
+impl History {
+    //the history of the last runs, or an empty one if there is no data directory
+    pub fn load() -> History {
+        History::from_file(data_dir().map(|x| x.join("history")))
+    }
+
+    //the entries in path, without a path they are not kept
+    pub fn from_file(path: Option<PathBuf>) -> History {
+        let mut text = String::new();
+        if let Some(ref path) = path {
+            if let Ok(mut file) = File::open(path) {
+                let _ = file.read_to_string(&mut text);
+            }
+        }
+        let mut entries = text.lines().map(|line| match line.find('\t') {
+            Some(index) => (line[..index].to_owned(), line[index + 1..].to_owned()),
+            None => (line.to_owned(), String::new())
+        }).collect::<Vec<(String, String)>>();
+        if entries.len() > MAX_ENTRIES {
+            entries = entries.split_off(entries.len() - MAX_ENTRIES);
+            if let Some(ref path) = path {
+                let lines = entries.iter().map(|x| format!("{}\t{}\n", x.0, x.1)).collect::<String>();
+                let _ = fs::write(path, lines);
+            }
+        }
+        History { path: path, entries: entries }
+    }
+
+    pub fn entries(&self) -> &Vec<(String, String)> {
+        &self.entries
+    }
+
+    //the input of entry n, counted from 1
+    pub fn input(&self, n: usize) -> Option<&String> {
+        if n == 0 {
+            return None
+        }
+        self.entries.get(n - 1).map(|x| &x.0)
+    }
+
+    //adds the entry to the file right away, so it is kept even if termcalc does not exit normally
+    pub fn add(&mut self, input: &str, result: &str) {
+        let entry = (input.replace('\t', " "), result.lines().next().unwrap_or("").replace('\t', " "));
+        if let Some(ref path) = self.path {
+            if let Some(dir) = path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                let _ = writeln!(file, "{}\t{}", entry.0, entry.1);
+            }
+        }
+        self.entries.push(entry);
+    }
+
+    //the numbers and entries whose input or result contain text, the last count of them
+    pub fn search(&self, text: &str, count: usize) -> Vec<(usize, &(String, String))> {
+        let found = self.entries.iter().enumerate()
+            .filter(|x| (x.1).0.contains(text) || (x.1).1.contains(text))
+            .map(|(index, entry)| (index + 1, entry))
+            .collect::<Vec<(usize, &(String, String))>>();
+        let skip = found.len().saturating_sub(count);
+        found.into_iter().skip(skip).collect()
+    }
+}
+
+//$XDG_DATA_HOME/termcalc, ~/.local/share/termcalc or %APPDATA%\termcalc
+fn data_dir() -> Option<PathBuf> {
+    if let Ok(dir) = env::var("XDG_DATA_HOME") {
+        if !dir.is_empty() {
+            return Some(PathBuf::from(dir).join("termcalc"))
+        }
+    }
+    if let Ok(dir) = env::var("HOME") {
+        return Some(PathBuf::from(dir).join(".local").join("share").join("termcalc"))
+    }
+    env::var("APPDATA").ok().map(|x| PathBuf::from(x).join("termcalc"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("termcalc-test-{}-{}", process::id(), name))
+    }
+
+    #[test]
+    fn kept_in_file() {
+        let path = temp_path("history");
+        let mut history = History::from_file(Some(path.clone()));
+        assert!(history.entries().is_empty());
+        history.add("2+3", "$0: 5 [int]");
+        history.add("str(\"a\tb\")", "$1: \"a b\" [str]\nsecond line");
+        history.add(":var X 1", "");
+        let history = History::from_file(Some(path.clone()));
+        assert_eq!(history.entries(), &vec![
+            (format!("2+3"), format!("$0: 5 [int]")),
+            (format!("str(\"a b\")"), format!("$1: \"a b\" [str]")),
+            (format!(":var X 1"), format!(""))
+        ]);
+        assert_eq!(history.input(1).unwrap(), "2+3");
+        assert_eq!(history.input(0), None);
+        assert_eq!(history.input(4), None);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn oldest_entries_dropped() {
+        let path = temp_path("long-history");
+        let lines = (0..MAX_ENTRIES + 5).map(|x| format!("{}\t$0: {} [int]\n", x, x)).collect::<String>();
+        fs::write(&path, lines).unwrap();
+        let history = History::from_file(Some(path.clone()));
+        assert_eq!(history.entries().len(), MAX_ENTRIES);
+        assert_eq!(history.input(1).unwrap(), "5");
+        assert_eq!(History::from_file(Some(path.clone())).entries().len(), MAX_ENTRIES);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn search() {
+        let mut history = History::from_file(None);
+        for n in 0..5 {
+            history.add(&format!("{}*2", n), &format!("${}: {} [int]", n, n * 2));
+        }
+        //in the input or the result, numbered from 1
+        assert_eq!(history.search("4", 10).iter().map(|x| x.0).collect::<Vec<usize>>(), vec![3, 5]);
+        assert_eq!(history.search("*2", 2).iter().map(|x| x.0).collect::<Vec<usize>>(), vec![4, 5]);
+        assert!(history.search("sqrt", 10).is_empty());
+    }
+}
@@ -0,0 +1,763 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter, self};
+
+//significant digits until ':precision' is used
+pub const DEFAULT_PRECISION: u32 = 50;
+
+//extra digits of intermediate results of sqrt, exp, ...
+const GUARD_DIGITS: u32 = 10;
+
+//decimal digits per limb of a natural number
+const LIMB_DIGITS: usize = 9;
+const LIMB: u64 = 1_000_000_000;
+
+//more zeros than this are written with an exponent
+const MAX_ZEROS: i64 = 20;
+
+//unsigned integer in base 10^9, least significant limb first, without leading zero limbs
+#[derive(Debug, Clone, PartialEq)]
+struct Natural {
+    limbs: Vec<u32>
+}
+
+impl Natural {
+    fn zero() -> Natural {
+        Natural { limbs: Vec::new() }
+    }
+
+    fn from_u64(mut v: u64) -> Natural {
+        let mut limbs = Vec::new();
+        while v > 0 {
+            limbs.push((v % LIMB) as u32);
+            v /= LIMB;
+        }
+        Natural { limbs: limbs }
+    }
+
+    //only digits
+    fn parse(digits: &str) -> Natural {
+        let mut limbs = Vec::new();
+        let mut end = digits.len();
+        while end > 0 {
+            let start = end.saturating_sub(LIMB_DIGITS);
+            limbs.push(digits[start..end].parse::<u32>().unwrap());
+            end = start;
+        }
+        Natural { limbs: limbs }.trimmed()
+    }
+
+    fn trimmed(mut self) -> Natural {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        self
+    }
+
+    fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    //number of decimal digits, 0 for zero
+    fn digits(&self) -> usize {
+        match self.limbs.last() {
+            Some(top) => (self.limbs.len() - 1) * LIMB_DIGITS + top.to_string().len(),
+            None => 0
+        }
+    }
+
+    //the decimal digit at index, counted from the least significant one
+    fn digit(&self, index: usize) -> u32 {
+        match self.limbs.get(index / LIMB_DIGITS) {
+            Some(limb) => limb / 10u32.pow((index % LIMB_DIGITS) as u32) % 10,
+            None => 0
+        }
+    }
+
+    fn trailing_zeros(&self) -> usize {
+        let mut count = 0;
+        while count < self.digits() && self.digit(count) == 0 {
+            count += 1;
+        }
+        count
+    }
+
+    fn compare(&self, other: &Natural) -> Ordering {
+        if self.limbs.len() != other.limbs.len() {
+            return self.limbs.len().cmp(&other.limbs.len())
+        }
+        for (a, b) in self.limbs.iter().rev().zip(other.limbs.iter().rev()) {
+            if a != b {
+                return a.cmp(b)
+            }
+        }
+        Ordering::Equal
+    }
+
+    fn add(&self, other: &Natural) -> Natural {
+        let length = self.limbs.len().max(other.limbs.len());
+        let mut limbs = Vec::with_capacity(length + 1);
+        let mut carry = 0u64;
+        for i in 0..length {
+            let sum = carry + *self.limbs.get(i).unwrap_or(&0) as u64 + *other.limbs.get(i).unwrap_or(&0) as u64;
+            limbs.push((sum % LIMB) as u32);
+            carry = sum / LIMB;
+        }
+        if carry > 0 {
+            limbs.push(carry as u32);
+        }
+        Natural { limbs: limbs }
+    }
+
+    //self has to be at least other
+    fn sub(&self, other: &Natural) -> Natural {
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        let mut borrow = 0i64;
+        for i in 0..self.limbs.len() {
+            let mut diff = self.limbs[i] as i64 - borrow - *other.limbs.get(i).unwrap_or(&0) as i64;
+            borrow = 0;
+            if diff < 0 {
+                diff += LIMB as i64;
+                borrow = 1;
+            }
+            limbs.push(diff as u32);
+        }
+        Natural { limbs: limbs }.trimmed()
+    }
+
+    fn mul(&self, other: &Natural) -> Natural {
+        if self.is_zero() || other.is_zero() {
+            return Natural::zero()
+        }
+        let mut limbs = vec![0u64; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let current = limbs[i + j] + a as u64 * b as u64 + carry;
+                limbs[i + j] = current % LIMB;
+                carry = current / LIMB;
+            }
+            limbs[i + other.limbs.len()] = carry;
+        }
+        Natural { limbs: limbs.into_iter().map(|x| x as u32).collect() }.trimmed()
+    }
+
+    fn mul_small(&self, factor: u32) -> Natural {
+        self.mul(&Natural::from_u64(factor as u64))
+    }
+
+    fn add_small(&self, summand: u32) -> Natural {
+        self.add(&Natural::from_u64(summand as u64))
+    }
+
+    //quotient and remainder
+    fn div_small(&self, divisor: u32) -> (Natural, u32) {
+        let mut limbs = vec![0u32; self.limbs.len()];
+        let mut rem = 0u64;
+        for i in (0..self.limbs.len()).rev() {
+            let current = rem * LIMB + self.limbs[i] as u64;
+            limbs[i] = (current / divisor as u64) as u32;
+            rem = current % divisor as u64;
+        }
+        (Natural { limbs: limbs }.trimmed(), rem as u32)
+    }
+
+    //floor(self / other), long division digit by digit
+    fn div(&self, other: &Natural) -> Natural {
+        let mut quot = String::new();
+        let mut rem = Natural::zero();
+        for ch in self.to_string().chars() {
+            rem = rem.mul_small(10).add_small(ch.to_digit(10).unwrap());
+            let mut count = 0;
+            while rem.compare(other) != Ordering::Less {
+                rem = rem.sub(other);
+                count += 1;
+            }
+            quot.push(::std::char::from_digit(count, 10).unwrap());
+        }
+        Natural::parse(&quot)
+    }
+
+    //self * 10^count
+    fn shift_up(&self, count: usize) -> Natural {
+        if self.is_zero() {
+            return Natural::zero()
+        }
+        let mut limbs = vec![0u32; count / LIMB_DIGITS];
+        limbs.extend(self.limbs.iter().cloned());
+        Natural { limbs: limbs }.mul_small(10u32.pow((count % LIMB_DIGITS) as u32))
+    }
+
+    //self / 10^count, rounded half up or truncated
+    fn shift_down(&self, count: usize, round: bool) -> Natural {
+        let round_up = round && count > 0 && self.digit(count - 1) >= 5;
+        let limbs = match self.limbs.get(count / LIMB_DIGITS..) {
+            Some(v) => v.to_vec(),
+            None => Vec::new()
+        };
+        let (result, _) = Natural { limbs: limbs }.div_small(10u32.pow((count % LIMB_DIGITS) as u32));
+        if round_up {
+            result.add_small(1)
+        }
+        else {
+            result
+        }
+    }
+}
+
+impl Display for Natural {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match self.limbs.last() {
+            Some(top) => write!(fmt, "{}", top)?,
+            None => return write!(fmt, "0")
+        }
+        for limb in self.limbs.iter().rev().skip(1) {
+            write!(fmt, "{:09}", limb)?;
+        }
+        Ok(())
+    }
+}
+
+//mantissa * 10^exponent, without trailing zeros in the mantissa
+//every operation rounds to the given number of significant digits
+#[derive(Debug, Clone, PartialEq)]
+pub struct BigFloat {
+    negative: bool,
+    mantissa: Natural,
+    exponent: i64
+}
+
+impl BigFloat {
+    fn new(negative: bool, mantissa: Natural, exponent: i64) -> BigFloat {
+        if mantissa.is_zero() {
+            return BigFloat::zero()
+        }
+        let zeros = mantissa.trailing_zeros();
+        BigFloat {
+            negative: negative,
+            mantissa: mantissa.shift_down(zeros, false),
+            exponent: exponent + zeros as i64
+        }
+    }
+
+    pub fn zero() -> BigFloat {
+        BigFloat { negative: false, mantissa: Natural::zero(), exponent: 0 }
+    }
+
+    pub fn from_int(v: i64) -> BigFloat {
+        BigFloat::new(v < 0, Natural::from_u64((v as i128).abs() as u64), 0)
+    }
+
+    //'12.50', '.5', '3e-2', '-1.5e100'
+    pub fn parse(src: &str) -> Option<BigFloat> {
+        let (negative, src) = if src.starts_with('-') { (true, &src[1..]) } else { (false, src) };
+        let (number, exponent) = match src.find(|x| x == 'e' || x == 'E') {
+            Some(index) => (&src[..index], src[index + 1..].parse::<i64>().ok()?),
+            None => (src, 0)
+        };
+        let (int_part, frac_part) = match number.find('.') {
+            Some(index) => (&number[..index], &number[index + 1..]),
+            None => (number, "")
+        };
+        if int_part.len() + frac_part.len() == 0 ||
+                !int_part.chars().chain(frac_part.chars()).all(|x| x.is_digit(10)) {
+            return None
+        }
+        let mantissa = Natural::parse(&format!("{}{}", int_part, frac_part));
+        Some(BigFloat::new(negative, mantissa, exponent.checked_sub(frac_part.len() as i64)?))
+    }
+
+    //uses the shortest representation of the float, 0.1 stays 0.1
+    pub fn from_f64(v: f64) -> Option<BigFloat> {
+        if !v.is_finite() {
+            return None
+        }
+        BigFloat::parse(&format!("{:?}", v))
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let sign = if self.negative { "-" } else { "" };
+        format!("{}{}e{}", sign, self.mantissa, self.exponent).parse::<f64>().unwrap()
+    }
+
+    pub fn to_i64(&self) -> i64 {
+        self.to_f64() as i64
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn neg(&self) -> BigFloat {
+        let mut result = self.clone();
+        result.negative = !self.negative && !self.is_zero();
+        result
+    }
+
+    pub fn abs(&self) -> BigFloat {
+        let mut result = self.clone();
+        result.negative = false;
+        result
+    }
+
+    //the power of ten of the leading digit
+    fn magnitude(&self) -> i64 {
+        self.exponent + self.mantissa.digits() as i64 - 1
+    }
+
+    //to digits significant digits
+    pub fn round(&self, digits: u32) -> BigFloat {
+        let count = self.mantissa.digits();
+        if count <= digits as usize {
+            return self.clone()
+        }
+        let excess = count - digits as usize;
+        BigFloat::new(self.negative, self.mantissa.shift_down(excess, true), self.exponent + excess as i64)
+    }
+
+    //to places digits after the point
+    pub fn round_places(&self, places: u32) -> BigFloat {
+        let excess = -(places as i64) - self.exponent;
+        if excess <= 0 {
+            return self.clone()
+        }
+        if excess > self.mantissa.digits() as i64 {
+            return BigFloat::zero()
+        }
+        BigFloat::new(self.negative, self.mantissa.shift_down(excess as usize, true), -(places as i64))
+    }
+
+    pub fn floor(&self) -> BigFloat {
+        if self.exponent >= 0 {
+            return self.clone()
+        }
+        let count = (-self.exponent) as usize;
+        let (int, exact) = if count > self.mantissa.digits() {
+            (Natural::zero(), false)
+        }
+        else {
+            let int = self.mantissa.shift_down(count, false);
+            let exact = int.shift_up(count) == self.mantissa;
+            (int, exact)
+        };
+        if self.negative && !exact {
+            BigFloat::new(true, int.add_small(1), 0)
+        }
+        else {
+            BigFloat::new(self.negative, int, 0)
+        }
+    }
+
+    pub fn ceil(&self) -> BigFloat {
+        self.neg().floor().neg()
+    }
+
+    pub fn compare(&self, other: &BigFloat) -> Ordering {
+        if self.negative != other.negative {
+            return if self.negative { Ordering::Less } else { Ordering::Greater }
+        }
+        let ordering = match (self.is_zero(), other.is_zero()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (false, false) if self.magnitude() != other.magnitude() => self.magnitude().cmp(&other.magnitude()),
+            (false, false) => {
+                let exponent = self.exponent.min(other.exponent);
+                let a = self.mantissa.shift_up((self.exponent - exponent) as usize);
+                let b = other.mantissa.shift_up((other.exponent - exponent) as usize);
+                a.compare(&b)
+            }
+        };
+        if self.negative { ordering.reverse() } else { ordering }
+    }
+
+    pub fn add(&self, other: &BigFloat, digits: u32) -> BigFloat {
+        if self.is_zero() {
+            return other.round(digits)
+        }
+        if other.is_zero() {
+            return self.round(digits)
+        }
+        //a much smaller summand does not reach the digits of the result
+        let gap = digits as i64 + 2;
+        if self.magnitude() - other.magnitude() > gap {
+            return self.round(digits)
+        }
+        if other.magnitude() - self.magnitude() > gap {
+            return other.round(digits)
+        }
+        let exponent = self.exponent.min(other.exponent);
+        let a = self.mantissa.shift_up((self.exponent - exponent) as usize);
+        let b = other.mantissa.shift_up((other.exponent - exponent) as usize);
+        let result = if self.negative == other.negative {
+            BigFloat::new(self.negative, a.add(&b), exponent)
+        }
+        else if a.compare(&b) == Ordering::Less {
+            BigFloat::new(other.negative, b.sub(&a), exponent)
+        }
+        else {
+            BigFloat::new(self.negative, a.sub(&b), exponent)
+        };
+        result.round(digits)
+    }
+
+    pub fn sub(&self, other: &BigFloat, digits: u32) -> BigFloat {
+        self.add(&other.neg(), digits)
+    }
+
+    pub fn mul(&self, other: &BigFloat, digits: u32) -> BigFloat {
+        let product = self.mantissa.mul(&other.mantissa);
+        BigFloat::new(self.negative != other.negative, product, self.exponent + other.exponent).round(digits)
+    }
+
+    //None when dividing by zero
+    pub fn div(&self, other: &BigFloat, digits: u32) -> Option<BigFloat> {
+        if other.is_zero() {
+            return None
+        }
+        //at least digits + 2 digits in the quotient
+        let shift = digits as i64 + 2 + other.mantissa.digits() as i64 - self.mantissa.digits() as i64;
+        let shift = shift.max(0) as usize;
+        let quot = self.mantissa.shift_up(shift).div(&other.mantissa);
+        let exponent = self.exponent - other.exponent - shift as i64;
+        Some(BigFloat::new(self.negative != other.negative, quot, exponent).round(digits))
+    }
+
+    fn div_small(&self, divisor: u32, digits: u32) -> BigFloat {
+        let shift = (digits as i64 + 12 - self.mantissa.digits() as i64).max(0) as usize;
+        let (quot, _) = self.mantissa.shift_up(shift).div_small(divisor);
+        BigFloat::new(self.negative, quot, self.exponent - shift as i64).round(digits)
+    }
+
+    //None for 0 to a negative power
+    pub fn powi(&self, exponent: i64, digits: u32) -> Option<BigFloat> {
+        //the rounding error grows with every squaring
+        let work = digits + GUARD_DIGITS + exponent.abs().to_string().len() as u32;
+        let mut result = BigFloat::from_int(1);
+        let mut base = self.clone();
+        let mut rest = (exponent as i128).abs();
+        while rest > 0 {
+            if rest % 2 == 1 {
+                result = result.mul(&base, work);
+            }
+            rest /= 2;
+            if rest > 0 {
+                base = base.mul(&base, work);
+            }
+        }
+        if exponent < 0 {
+            BigFloat::from_int(1).div(&result, digits)
+        }
+        else {
+            Some(result.round(digits))
+        }
+    }
+
+    //x^y = exp(y * ln(x)), None for negative x
+    pub fn powf(&self, exponent: &BigFloat, digits: u32) -> Option<BigFloat> {
+        if self.is_zero() && !exponent.negative {
+            return Some(BigFloat::zero())
+        }
+        let work = digits + GUARD_DIGITS;
+        let log = self.ln(work)?;
+        log.mul(exponent, work).exp(digits)
+    }
+
+    //None for negative numbers
+    pub fn sqrt(&self, digits: u32) -> Option<BigFloat> {
+        if self.negative {
+            return None
+        }
+        if self.is_zero() {
+            return Some(BigFloat::zero())
+        }
+        //self = scaled * 100^half, with scaled between 1 and 100
+        let half = self.magnitude().div_euclid(2);
+        let mut scaled = self.clone();
+        scaled.exponent -= 2 * half;
+        let work = digits + GUARD_DIGITS;
+        let one_half = BigFloat::new(false, Natural::from_u64(5), -1);
+        //newton, the correct digits double with every step
+        let mut x = BigFloat::from_f64(scaled.to_f64().sqrt())?;
+        let mut correct = 15;
+        loop {
+            x = x.add(&scaled.div(&x, work)?, work).mul(&one_half, work);
+            if correct > work {
+                break
+            }
+            correct *= 2;
+        }
+        x.exponent += half;
+        Some(x.round(digits))
+    }
+
+    //None if the result is too large
+    pub fn exp(&self, digits: u32) -> Option<BigFloat> {
+        let size = self.to_f64().abs();
+        if size > 1e15 {
+            return None
+        }
+        //exp(x) = exp(x / 2^halvings)^(2^halvings), the series converges fast for small x
+        let halvings = if size < 0.25 { 0 } else { size.log2().ceil() as u32 + 2 };
+        let work = digits + GUARD_DIGITS + halvings / 3;
+        let one_half = BigFloat::new(false, Natural::from_u64(5), -1);
+        let mut x = self.clone();
+        for _ in 0..halvings {
+            x = x.mul(&one_half, work);
+        }
+        let mut sum = BigFloat::from_int(1);
+        let mut term = BigFloat::from_int(1);
+        let mut n = 1;
+        loop {
+            term = term.mul(&x, work).div_small(n, work);
+            if negligible(&term, &sum, work) {
+                break
+            }
+            sum = sum.add(&term, work);
+            n += 1;
+        }
+        for _ in 0..halvings {
+            sum = sum.mul(&sum, work);
+        }
+        Some(sum.round(digits))
+    }
+
+    //None for numbers that are not positive
+    pub fn ln(&self, digits: u32) -> Option<BigFloat> {
+        if self.negative || self.is_zero() {
+            return None
+        }
+        let work = digits + GUARD_DIGITS;
+        //self = 2^k * z with z close to 1
+        let mut leading = self.clone();
+        leading.exponent -= self.magnitude();
+        let log10 = self.magnitude() as f64 + leading.to_f64().log10();
+        let k = (log10 * ::std::f64::consts::LOG2_10).round() as i64;
+        let two = BigFloat::from_int(2);
+        let z = if k >= 0 {
+            self.div(&two.powi(k, work)?, work)?
+        }
+        else {
+            self.mul(&two.powi(-k, work)?, work)
+        };
+        //ln(z) = 2 * atanh((z - 1) / (z + 1))
+        let one = BigFloat::from_int(1);
+        let t = z.sub(&one, work).div(&z.add(&one, work), work)?;
+        let ln_z = atanh(&t, work).mul(&two, work);
+        let ln_two = atanh(&BigFloat::from_int(1).div_small(3, work), work).mul(&two, work);
+        Some(ln_two.mul(&BigFloat::from_int(k), work).add(&ln_z, work).round(digits))
+    }
+
+    pub fn pi(digits: u32) -> BigFloat {
+        let work = digits + GUARD_DIGITS;
+        //machin: pi = 16 * atan(1/5) - 4 * atan(1/239)
+        let a = atan_inverse(5, work).mul(&BigFloat::from_int(16), work);
+        let b = atan_inverse(239, work).mul(&BigFloat::from_int(4), work);
+        a.sub(&b, work).round(digits)
+    }
+
+    pub fn sin(&self, digits: u32) -> Option<BigFloat> {
+        let (x, work) = self.reduce_angle(digits)?;
+        Some(series(&x, x.clone(), 2, true, work).round(digits))
+    }
+
+    pub fn cos(&self, digits: u32) -> Option<BigFloat> {
+        let (x, work) = self.reduce_angle(digits)?;
+        Some(series(&x, BigFloat::from_int(1), 1, true, work).round(digits))
+    }
+
+    pub fn tan(&self, digits: u32) -> Option<BigFloat> {
+        let work = digits + GUARD_DIGITS;
+        self.sin(work)?.div(&self.cos(work)?, digits)
+    }
+
+    pub fn sinh(&self, digits: u32) -> Option<BigFloat> {
+        let work = digits + GUARD_DIGITS;
+        if self.to_f64().abs() < 1.0 {
+            //no cancellation in exp(x) - exp(-x)
+            return Some(series(self, self.clone(), 2, false, work).round(digits))
+        }
+        let (plus, minus) = self.exp_pair(work)?;
+        Some(plus.sub(&minus, work).div_small(2, digits))
+    }
+
+    pub fn cosh(&self, digits: u32) -> Option<BigFloat> {
+        let work = digits + GUARD_DIGITS;
+        let (plus, minus) = self.exp_pair(work)?;
+        Some(plus.add(&minus, work).div_small(2, digits))
+    }
+
+    pub fn tanh(&self, digits: u32) -> Option<BigFloat> {
+        let work = digits + GUARD_DIGITS;
+        self.sinh(work)?.div(&self.cosh(work)?, digits)
+    }
+
+    //exp(x) and exp(-x)
+    fn exp_pair(&self, digits: u32) -> Option<(BigFloat, BigFloat)> {
+        let plus = self.exp(digits)?;
+        let minus = BigFloat::from_int(1).div(&plus, digits)?;
+        Some((plus, minus))
+    }
+
+    //self - n * 2pi between -pi and pi, with the digits to use for the series
+    fn reduce_angle(&self, digits: u32) -> Option<(BigFloat, u32)> {
+        if self.is_zero() {
+            return Some((BigFloat::zero(), digits))
+        }
+        if self.magnitude() > 1000 {
+            return None
+        }
+        //the digits of pi have to cover the integer part of self / 2pi as well
+        let work = digits + GUARD_DIGITS + self.magnitude().max(0) as u32;
+        let two_pi = BigFloat::pi(work).mul(&BigFloat::from_int(2), work);
+        let turns = self.div(&two_pi, work)?.round_places(0);
+        Some((self.sub(&turns.mul(&two_pi, work), work), work))
+    }
+}
+
+impl Display for BigFloat {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        if self.is_zero() {
+            return write!(fmt, "0")
+        }
+        if self.negative {
+            write!(fmt, "-")?;
+        }
+        let digits = self.mantissa.to_string();
+        let magnitude = self.magnitude();
+        if self.exponent > MAX_ZEROS || magnitude < -MAX_ZEROS {
+            let (first, rest) = digits.split_at(1);
+            if rest.is_empty() {
+                write!(fmt, "{}e{}", first, magnitude)
+            }
+            else {
+                write!(fmt, "{}.{}e{}", first, rest, magnitude)
+            }
+        }
+        else if self.exponent >= 0 {
+            write!(fmt, "{}{}", digits, "0".repeat(self.exponent as usize))
+        }
+        else if magnitude >= 0 {
+            let (int_part, frac_part) = digits.split_at(magnitude as usize + 1);
+            write!(fmt, "{}.{}", int_part, frac_part)
+        }
+        else {
+            write!(fmt, "0.{}{}", "0".repeat((-magnitude - 1) as usize), digits)
+        }
+    }
+}
+
+//true if term does not change sum at the given digits
+fn negligible(term: &BigFloat, sum: &BigFloat, digits: u32) -> bool {
+    term.is_zero() || (!sum.is_zero() && term.magnitude() < sum.magnitude() - digits as i64 - 2)
+}
+
+//first + first * (+-x^2) / (n (n+1)) + ..., the series of sin, cos, sinh and cosh
+fn series(x: &BigFloat, first: BigFloat, n: u32, alternate: bool, digits: u32) -> BigFloat {
+    let square = x.mul(x, digits);
+    let mut term = first.clone();
+    let mut sum = first;
+    let mut n = n;
+    loop {
+        term = term.mul(&square, digits).div_small(n * (n + 1), digits);
+        if alternate {
+            term = term.neg();
+        }
+        if negligible(&term, &sum, digits) {
+            break
+        }
+        sum = sum.add(&term, digits);
+        n += 2;
+    }
+    sum
+}
+
+//t + t^3/3 + t^5/5 + ..., for small t
+fn atanh(t: &BigFloat, digits: u32) -> BigFloat {
+    if t.is_zero() {
+        return BigFloat::zero()
+    }
+    let square = t.mul(t, digits);
+    let mut power = t.clone();
+    let mut sum = t.clone();
+    let mut n = 3;
+    loop {
+        power = power.mul(&square, digits);
+        let term = power.div_small(n, digits);
+        if negligible(&term, &sum, digits) {
+            break
+        }
+        sum = sum.add(&term, digits);
+        n += 2;
+    }
+    sum
+}
+
+//atan(1/n) = 1/n - 1/(3 n^3) + 1/(5 n^5) - ...
+fn atan_inverse(n: u32, digits: u32) -> BigFloat {
+    let mut power = BigFloat::from_int(1).div_small(n, digits);
+    let mut sum = power.clone();
+    let mut k = 1;
+    loop {
+        power = power.div_small(n * n, digits);
+        let term = power.div_small(2 * k + 1, digits);
+        if negligible(&term, &sum, digits) {
+            break
+        }
+        sum = if k % 2 == 1 { sum.sub(&term, digits) } else { sum.add(&term, digits) };
+        k += 1;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(src: &str) -> BigFloat {
+        BigFloat::parse(src).unwrap()
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(format!("{}", BigFloat::pi(50)), "3.1415926535897932384626433832795028841971693993751");
+        assert_eq!(format!("{}", BigFloat::from_int(1).exp(50).unwrap()), "2.7182818284590452353602874713526624977572470937");
+        assert_eq!(format!("{}", BigFloat::from_int(2).ln(50).unwrap()), "0.69314718055994530941723212145817656807550013436026");
+    }
+
+    #[test]
+    fn identities() {
+        let two = BigFloat::from_int(2);
+        let root = two.sqrt(50).unwrap();
+        assert_eq!(format!("{}", root), "1.4142135623730950488016887242096980785696718753769");
+        assert_eq!(format!("{}", root.mul(&root, 50).round(45)), "2");
+        let x = big("0.7");
+        let sin = x.sin(50).unwrap();
+        let cos = x.cos(50).unwrap();
+        assert_eq!(format!("{}", sin.mul(&sin, 50).add(&cos.mul(&cos, 50), 50).round(45)), "1");
+        assert_eq!(format!("{}", x.exp(50).unwrap().ln(50).unwrap().round(45)), "0.7");
+        assert_eq!(format!("{}", BigFloat::pi(50).div(&BigFloat::from_int(6), 50).unwrap().sin(50).unwrap().round(45)), "0.5");
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(format!("{}", big("1.25").round(2)), "1.3");
+        assert_eq!(format!("{}", big("-1.25").round(2)), "-1.3");
+        assert_eq!(format!("{}", big("1.249").round(2)), "1.2");
+        assert_eq!(format!("{}", big("9.99").round(2)), "10");
+        assert_eq!(format!("{}", big("0.125").round_places(2)), "0.13");
+        assert_eq!(format!("{}", big("0.004").round_places(2)), "0");
+        assert_eq!(format!("{}", BigFloat::from_int(2).div(&BigFloat::from_int(3), 10).unwrap()), "0.6666666667");
+        assert_eq!(format!("{}", BigFloat::from_int(1).div(&BigFloat::from_int(3), 10).unwrap()), "0.3333333333");
+    }
+
+    #[test]
+    fn errors() {
+        assert!(BigFloat::from_int(1).div(&BigFloat::zero(), 50).is_none());
+        assert!(BigFloat::from_int(-2).sqrt(50).is_none());
+        assert!(BigFloat::zero().ln(50).is_none());
+    }
+}
@@ -0,0 +1,147 @@
+//function bodies over several lines, e.g. in a script:
+//
+//  :fn sign X =
+//      if X < 0
+//          S = -1
+//      else
+//          S = 1
+//      end
+//      S*abs(X)
+//  end
+//
+//they are rewritten as a single expression with 'let' and 'if()'
+
+enum Stmt {
+    //Name = expr
+    Assign(String, String),
+    //if cond, the lines up to 'else' and the lines up to 'end'
+    If(String, Vec<Stmt>, Vec<Stmt>),
+    //the value of the body, only as the last line
+    Value(String)
+}
+
+//the lines of a body as a single expression
+pub fn compile(body: &str) -> Result<String, String> {
+    let lines = body.lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty() && !x.starts_with("--"))
+        .collect::<Vec<&str>>();
+    let mut index = 0;
+    let stmts = parse(&lines, &mut index)?;
+    if index < lines.len() {
+        return Err(format!("'{}' without 'if'", lines[index]))
+    }
+    value_of(&stmts)
+}
+
+//the statements up to a line 'else' or 'end' (which is not consumed)
+fn parse(lines: &Vec<&str>, index: &mut usize) -> Result<Vec<Stmt>, String> {
+    let mut result = Vec::new();
+    while *index < lines.len() {
+        let line = lines[*index];
+        if line == "else" || line == "end" {
+            break;
+        }
+        *index += 1;
+        if line.starts_with("if ") {
+            let then = parse(lines, index)?;
+            let mut otherwise = Vec::new();
+            if lines.get(*index) == Some(&"else") {
+                *index += 1;
+                otherwise = parse(lines, index)?;
+            }
+            if lines.get(*index) != Some(&"end") {
+                return Err(format!("'{}' without 'end'", line))
+            }
+            *index += 1;
+            result.push(Stmt::If(line[3..].trim().to_owned(), then, otherwise));
+        }
+        else if let Some((name, value)) = assignment(line) {
+            result.push(Stmt::Assign(name, value));
+        }
+        else {
+            result.push(Stmt::Value(line.to_owned()));
+        }
+    }
+    Ok(result)
+}
+
+//'Y = X^2' -> ('Y', 'X^2')
+fn assignment(line: &str) -> Option<(String, String)> {
+    let index = line.find('=')?;
+    let name = line[..index].trim();
+    let valid = name.starts_with(char::is_uppercase) && name.chars().all(|x| x.is_alphanumeric() || x == '_');
+    if valid && !line[index + 1..].trim().is_empty() {
+        Some((name.to_owned(), line[index + 1..].trim().to_owned()))
+    }
+    else {
+        None
+    }
+}
+
+//the expression for the value of the last statement
+fn value_of(stmts: &[Stmt]) -> Result<String, String> {
+    let (last, rest) = match stmts.split_last() {
+        Some(v) => v,
+        None => return Err(format!("the body has no value"))
+    };
+    let value = match *last {
+        Stmt::Value(ref expr) => format!("({})", expr),
+        Stmt::If(ref cond, ref then, ref otherwise) => {
+            if otherwise.is_empty() {
+                return Err(format!("'if {}' in the last line needs an 'else'", cond))
+            }
+            format!("if({}, {}, {})", cond, value_of(then)?, value_of(otherwise)?)
+        },
+        //'X = 0' in the last line is a comparison
+        Stmt::Assign(ref name, ref expr) => format!("({} = {})", name, expr)
+    };
+    with_assignments(rest, value)
+}
+
+//'let' for the assignments of stmts around value
+fn with_assignments(stmts: &[Stmt], value: String) -> Result<String, String> {
+    let mut result = value;
+    for stmt in stmts.iter().rev() {
+        result = match *stmt {
+            Stmt::Assign(ref name, ref expr) => format!("let {} = ({}) in {}", name, expr, result),
+            //every variable assigned in a branch is the value of the branch with that variable as the last line,
+            //computed with the old values of all variables first
+            Stmt::If(ref cond, ref then, ref otherwise) => {
+                let mut names = Vec::new();
+                assigned(then, &mut names);
+                assigned(otherwise, &mut names);
+                let mut bindings = Vec::new();
+                for name in &names {
+                    let then_value = with_assignments(then, name.clone())?;
+                    let otherwise_value = with_assignments(otherwise, name.clone())?;
+                    bindings.push(format!("$branch_{} = if({}, {}, {})", name, cond, then_value, otherwise_value));
+                }
+                for name in &names {
+                    bindings.push(format!("{} = $branch_{}", name, name));
+                }
+                if bindings.is_empty() {
+                    result
+                }
+                else {
+                    format!("let {} in {}", bindings.join(", "), result)
+                }
+            },
+            Stmt::Value(ref expr) => return Err(format!("'{}' is not the last line, its value is not used", expr))
+        };
+    }
+    Ok(result)
+}
+
+fn assigned(stmts: &[Stmt], names: &mut Vec<String>) {
+    for stmt in stmts {
+        match *stmt {
+            Stmt::Assign(ref name, _) if !names.contains(name) => names.push(name.clone()),
+            Stmt::If(_, ref then, ref otherwise) => {
+                assigned(then, names);
+                assigned(otherwise, names);
+            },
+            _ => {}
+        }
+    }
+}
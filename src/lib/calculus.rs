@@ -0,0 +1,226 @@
+use value::Value;
+use engine::Engine;
+use interpreter;
+use stdlib;
+use lists;
+
+//integration stops when the estimated error is below this (absolute or relative to the result)
+const INTEGRATE_TOLERANCE: f64 = 1e-10;
+const INTEGRATE_MAX_INTERVALS: usize = 1000;
+
+//Gauss-Kronrod 7-15 nodes and weights, from QUADPACK (qk15)
+const KRONROD_NODES: [f64; 8] = [
+    0.991455371120812639206854697526329,
+    0.949107912342758524526189684047851,
+    0.864864423359769072789712788640926,
+    0.741531185599394439863864773280788,
+    0.586087235467691130294144845693013,
+    0.405845151377397166906606412076961,
+    0.207784955007898467600689403773245,
+    0.000000000000000000000000000000000
+];
+const KRONROD_WEIGHTS: [f64; 8] = [
+    0.022935322010529224963732008058970,
+    0.063092092629978553290700663189204,
+    0.104790010322250183839876322541518,
+    0.140653259715525918745189590510238,
+    0.169004726639267902826583426598550,
+    0.190350578064785409913256402421014,
+    0.204432940075298892414161999234649,
+    0.209482141084727828012999174891714
+];
+//weights of the gauss nodes (every second kronrod node)
+const GAUSS_WEIGHTS: [f64; 4] = [
+    0.129484966168869693270611432679082,
+    0.279705391489276667901467771423780,
+    0.381830050505118944950369775488975,
+    0.417959183673469387755102040816327
+];
+
+//deriv(f, x): five point central difference
+pub fn deriv(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    stdlib::check_n(2, "deriv", valstack)?;
+    let x = valstack.pop().unwrap();
+    let func = valstack.pop().unwrap();
+    let x = expect_number("deriv", &x)?;
+    let f = |x: f64| eval_at(&func, x, engine);
+    Ok(Value::Float(derivative(&f, x)?))
+}
+
+//integrate(f, a, b)
+pub fn integrate(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let (result, error) = integrate_args("integrate", valstack, engine)?;
+    if error > tolerance(result) {
+        return Err(format!("integral did not converge (estimated error: {})", error))
+    }
+    Ok(Value::Float(result))
+}
+
+//integrate_err(f, a, b): the estimated absolute error of integrate(f, a, b)
+pub fn integrate_err(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let (_, error) = integrate_args("integrate_err", valstack, engine)?;
+    Ok(Value::Float(error))
+}
+
+//sum(f, a, b): f(a) + f(a+1) + ... + f(b)
+pub fn sum(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    fold_range("sum", "add", Value::Integer(0), valstack, engine)
+}
+
+//product(f, a, b): f(a) * f(a+1) * ... * f(b)
+pub fn product(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    fold_range("product", "mul", Value::Integer(1), valstack, engine)
+}
+
+//UTILS
+
+pub fn derivative<F>(f: &F, x: f64) -> Result<f64, String> where F: Fn(f64) -> Result<f64, String> {
+    let h = 1e-3 * x.abs().max(1.0);
+    Ok((f(x - 2.0 * h)? - 8.0 * f(x - h)? + 8.0 * f(x + h)? - f(x + 2.0 * h)?) / (12.0 * h))
+}
+
+fn fold_range(fnname: &str, op: &str, init: Value, valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    stdlib::check_n(3, fnname, valstack)?;
+    let to = valstack.pop().unwrap();
+    let from = valstack.pop().unwrap();
+    let func = valstack.pop().unwrap();
+    if !from.is_int() || !to.is_int() {
+        return Err(format!("invalid types in function '{}': bounds have to be integers", fnname))
+    }
+    let (from, to) = (from.to_int(), to.to_int());
+    if to as i128 - from as i128 >= lists::MAX_ITEMS as i128 {
+        return Err(format!("{}: more than {} terms", fnname, lists::MAX_ITEMS))
+    }
+    let mut acc = init;
+    for i in from..=to {
+        let val = interpreter::apply(&func, vec![Value::Integer(i)], engine)?;
+        let mut stack = vec![acc, val];
+        acc = stdlib::match_fn(op.to_owned(), &mut stack, engine)?;
+    }
+    Ok(acc)
+}
+
+fn integrate_args(fnname: &str, valstack: &mut Vec<Value>, engine: &Engine) -> Result<(f64, f64), String> {
+    stdlib::check_n(3, fnname, valstack)?;
+    let b = valstack.pop().unwrap();
+    let a = valstack.pop().unwrap();
+    let func = valstack.pop().unwrap();
+    let a = expect_number(fnname, &a)?;
+    let b = expect_number(fnname, &b)?;
+    let f = |x: f64| eval_at(&func, x, engine);
+    adaptive_kronrod(&f, a, b)
+}
+
+//bisects the interval with the largest error estimate until the total error is small enough
+fn adaptive_kronrod<F>(f: &F, a: f64, b: f64) -> Result<(f64, f64), String> where F: Fn(f64) -> Result<f64, String> {
+    let (result, error) = kronrod(f, a, b)?;
+    let mut intervals = vec![(a, b, result, error)];
+    loop {
+        let result = intervals.iter().map(|x| x.2).sum::<f64>();
+        let error = intervals.iter().map(|x| x.3).sum::<f64>();
+        if error <= tolerance(result) || intervals.len() >= INTEGRATE_MAX_INTERVALS {
+            return Ok((result, error))
+        }
+        let mut worst = 0;
+        for i in 1..intervals.len() {
+            if intervals[i].3 > intervals[worst].3 {
+                worst = i;
+            }
+        }
+        let (a, b, _, _) = intervals.swap_remove(worst);
+        let mid = 0.5 * (a + b);
+        let (left, left_err) = kronrod(f, a, mid)?;
+        let (right, right_err) = kronrod(f, mid, b)?;
+        intervals.push((a, mid, left, left_err));
+        intervals.push((mid, b, right, right_err));
+    }
+}
+
+//returns the 15 point kronrod result and its difference to the 7 point gauss result
+fn kronrod<F>(f: &F, a: f64, b: f64) -> Result<(f64, f64), String> where F: Fn(f64) -> Result<f64, String> {
+    let center = 0.5 * (a + b);
+    let half = 0.5 * (b - a);
+    let fc = f(center)?;
+    let mut kronrod = fc * KRONROD_WEIGHTS[7];
+    let mut gauss = fc * GAUSS_WEIGHTS[3];
+    for i in 0..7 {
+        let dx = half * KRONROD_NODES[i];
+        let pair = f(center - dx)? + f(center + dx)?;
+        kronrod += KRONROD_WEIGHTS[i] * pair;
+        if i % 2 == 1 {
+            gauss += GAUSS_WEIGHTS[i / 2] * pair;
+        }
+    }
+    Ok((kronrod * half, ((kronrod - gauss) * half).abs()))
+}
+
+fn tolerance(result: f64) -> f64 {
+    INTEGRATE_TOLERANCE.max(INTEGRATE_TOLERANCE * result.abs())
+}
+
+pub fn eval_at(func: &Value, x: f64, engine: &Engine) -> Result<f64, String> {
+    let val = interpreter::apply(func, vec![Value::Float(x)], engine)?;
+    if !val.is_number() {
+        return Err(format!("function returned {}, expected a number", val.type_str()))
+    }
+    Ok(val.to_float())
+}
+
+pub fn expect_number(fnname: &str, val: &Value) -> Result<f64, String> {
+    if val.is_number() {
+        Ok(val.to_float())
+    }
+    else {
+        Err(format!("invalid types in function '{}': {}", fnname, val.type_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{PI, E};
+    use testing::run;
+
+    fn integral<F>(f: F, a: f64, b: f64) -> f64 where F: Fn(f64) -> f64 {
+        adaptive_kronrod(&|x| Ok(f(x)), a, b).unwrap().0
+    }
+
+    #[test]
+    fn known_integrals() {
+        assert!((integral(|x| x * x, 0.0, 1.0) - 1.0 / 3.0).abs() < 1e-12);
+        assert!((integral(f64::sin, 0.0, PI) - 2.0).abs() < 1e-12);
+        assert!((integral(f64::exp, 0.0, 1.0) - (E - 1.0)).abs() < 1e-12);
+        assert!((integral(|x| 1.0 / (1.0 + x * x), -1.0, 1.0) - PI / 2.0).abs() < 1e-12);
+        //a peak that needs many intervals
+        assert!((integral(|x| 1.0 / (1e-4 + x * x), -1.0, 1.0) - 2.0 * 100.0 * (100.0f64).atan()).abs() < 1e-8);
+        assert_eq!(integral(|x| x, 2.0, 2.0), 0.0);
+        assert!((integral(|x| x, 1.0, 0.0) + 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn error_estimate() {
+        let (result, error) = adaptive_kronrod(&|x: f64| Ok(x.sqrt()), 0.0, 1.0).unwrap();
+        assert!((result - 2.0 / 3.0).abs() < 1e-9);
+        assert!(error <= tolerance(result));
+        assert!(adaptive_kronrod(&|_| Err(format!("failed")), 0.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn derivatives() {
+        assert!((derivative(&|x| Ok(x * x * x), 2.0).unwrap() - 12.0).abs() < 1e-9);
+        assert!((derivative(&|x: f64| Ok(x.sin()), 0.0).unwrap() - 1.0).abs() < 1e-9);
+        assert!((derivative(&|x: f64| Ok(x.exp()), 10.0).unwrap() - 10f64.exp()).abs() < 1e-6 * 10f64.exp());
+    }
+
+    #[test]
+    fn sums_and_products() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "sum(X -> X, 1, 100)"), "5050 [int]");
+        assert_eq!(run(&mut engine, "product(X -> X, 1, 20)"), "2432902008176640000 [int]");
+        assert_eq!(run(&mut engine, "sum(X -> X, 5, 1)"), "0 [int]");
+        assert_eq!(run(&mut engine, "product(X -> X, 1, 30)"), "error: integer overflow in function 'mul'");
+        assert_eq!(run(&mut engine, "sum(X -> X, 9223372036854775806, 9223372036854775807)"), "error: integer overflow in function 'add'");
+        assert_eq!(run(&mut engine, "sum(X -> 1, 9223372036854775806, 9223372036854775807)"), "2 [int]");
+        assert_eq!(run(&mut engine, "sum(X -> X, 1, 100000000000)"), "error: sum: more than 1000000 terms");
+    }
+}
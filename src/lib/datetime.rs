@@ -0,0 +1,490 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter, self};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use value::Value;
+use engine::Engine;
+use stdlib;
+
+const DAY: i64 = 86400;
+
+//all times are UTC, without leap seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateTime {
+    //days since 1970-01-01
+    Date(i64),
+    //seconds since midnight
+    Time(i64),
+    //seconds since 1970-01-01 00:00
+    Both(i64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Duration {
+    seconds: i64
+}
+
+impl DateTime {
+    //'2026-10-18', '14:30', '14:30:05' or '2026-10-18T14:30'
+    pub fn parse(src: &str) -> Option<DateTime> {
+        if let Some(index) = src.find('T') {
+            let days = parse_date(&src[..index])?;
+            let seconds = parse_time(&src[index + 1..])?;
+            return Some(DateTime::Both(days * DAY + seconds))
+        }
+        if let Some(days) = parse_date(src) {
+            return Some(DateTime::Date(days))
+        }
+        parse_time(src).map(DateTime::Time)
+    }
+
+    //'2026-02-30' or '25:00', written like a date or a time but none
+    pub fn is_invalid(src: &str) -> bool {
+        let shape = src.starts_with(|c: char| c.is_digit(10)) &&
+            src.chars().all(|c| c.is_digit(10) || c == '-' || c == ':' || c == 'T') &&
+            (src.contains(':') || src.matches('-').count() == 2);
+        shape && DateTime::parse(src).is_none()
+    }
+
+    pub fn type_str(&self) -> &'static str {
+        match *self {
+            DateTime::Date(_) => "date",
+            DateTime::Time(_) => "time",
+            DateTime::Both(_) => "datetime"
+        }
+    }
+
+    //seconds since 1970-01-01 00:00, times are taken as today
+    pub fn seconds(&self, today: i64) -> i64 {
+        match *self {
+            DateTime::Date(days) => days * DAY,
+            DateTime::Time(seconds) => today * DAY + seconds,
+            DateTime::Both(seconds) => seconds
+        }
+    }
+
+    fn days(&self) -> Option<i64> {
+        match *self {
+            DateTime::Date(days) => Some(days),
+            DateTime::Both(seconds) => Some(seconds.div_euclid(DAY)),
+            DateTime::Time(_) => None
+        }
+    }
+
+    pub fn add(&self, duration: Duration) -> Result<DateTime, String> {
+        let result = match *self {
+            DateTime::Date(days) if duration.seconds % DAY == 0 => DateTime::Date(days + duration.seconds / DAY),
+            DateTime::Date(days) => DateTime::Both(days * DAY).add(duration)?,
+            //the time on a clock, 23:00 + 2h is 01:00
+            DateTime::Time(seconds) => DateTime::Time((seconds + duration.seconds.rem_euclid(DAY)).rem_euclid(DAY)),
+            DateTime::Both(seconds) => DateTime::Both(seconds.checked_add(duration.seconds).ok_or_else(out_of_range)?)
+        };
+        match result.days() {
+            Some(days) if days < min_days() || days > max_days() => Err(out_of_range()),
+            _ => Ok(result)
+        }
+    }
+
+    pub fn sub(&self, other: &DateTime) -> Result<Duration, String> {
+        match (*self, *other) {
+            (DateTime::Time(a), DateTime::Time(b)) => Ok(Duration { seconds: a - b }),
+            (DateTime::Time(_), _) | (_, DateTime::Time(_)) => {
+                Err(format!("cannot subtract a time and a {}", if self.type_str() == "time" { other.type_str() } else { self.type_str() }))
+            },
+            (a, b) => Ok(Duration { seconds: a.seconds(0) - b.seconds(0) })
+        }
+    }
+
+    pub fn compare(&self, other: &DateTime) -> Result<Ordering, String> {
+        match (*self, *other) {
+            (DateTime::Time(a), DateTime::Time(b)) => Ok(a.cmp(&b)),
+            _ => self.sub(other).map(|x| x.seconds.cmp(&0))
+        }
+    }
+}
+
+impl Display for DateTime {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            DateTime::Date(days) => write_date(fmt, days),
+            DateTime::Time(seconds) => write_time(fmt, seconds),
+            DateTime::Both(seconds) => {
+                write_date(fmt, seconds.div_euclid(DAY))?;
+                write!(fmt, "T")?;
+                write_time(fmt, seconds.rem_euclid(DAY))
+            }
+        }
+    }
+}
+
+impl Duration {
+    pub fn from_seconds(seconds: i64) -> Duration {
+        Duration { seconds: seconds }
+    }
+
+    //seconds of a unit: 's', 'min', 'h', 'days', 'weeks', ...
+    pub fn unit(name: &str) -> Option<i64> {
+        match name {
+            "s" | "sec" | "secs" | "second" | "seconds" => Some(1),
+            "min" | "mins" | "minute" | "minutes" => Some(60),
+            "h" | "hour" | "hours" => Some(3600),
+            "day" | "days" => Some(DAY),
+            "week" | "weeks" => Some(7 * DAY),
+            _ => None
+        }
+    }
+
+    //count units, rounded to seconds
+    pub fn of(count: f64, unit: i64) -> Result<Duration, String> {
+        let seconds = (count * unit as f64).round();
+        //i64::MAX as f64 is rounded up to 2^63
+        if !seconds.is_finite() || seconds.abs() >= i64::MAX as f64 {
+            return Err(format!("duration out of range"))
+        }
+        Ok(Duration { seconds: seconds as i64 })
+    }
+
+    //'3h', '20min', '1.5days'
+    pub fn parse(src: &str) -> Option<Duration> {
+        let index = src.find(|x: char| x.is_alphabetic())?;
+        let (number, unit) = src.split_at(index);
+        if number.is_empty() || !number.chars().all(|x| x.is_digit(10) || x == '.') {
+            return None
+        }
+        Duration::of(number.parse::<f64>().ok()?, Duration::unit(unit)?).ok()
+    }
+
+    pub fn seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    pub fn add(&self, other: Duration) -> Result<Duration, String> {
+        match self.seconds.checked_add(other.seconds) {
+            //without i64::MIN, every duration can be negated
+            Some(v) if v != i64::MIN => Ok(Duration { seconds: v }),
+            _ => Err(format!("duration out of range"))
+        }
+    }
+
+    pub fn neg(&self) -> Duration {
+        Duration { seconds: -self.seconds }
+    }
+}
+
+//'90 days 3h 20min 5s', can be read back by the tokenizer
+impl Display for Duration {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        if self.seconds < 0 {
+            write!(fmt, "-")?;
+        }
+        let seconds = self.seconds.abs();
+        let mut parts = Vec::new();
+        match seconds / DAY {
+            0 => {},
+            1 => parts.push(format!("1 day")),
+            days => parts.push(format!("{} days", days))
+        }
+        let units = [(seconds % DAY / 3600, "h"), (seconds % 3600 / 60, "min"), (seconds % 60, "s")];
+        for &(count, name) in units.iter() {
+            if count != 0 {
+                parts.push(format!("{}{}", count, name));
+            }
+        }
+        if parts.is_empty() {
+            parts.push(format!("0s"));
+        }
+        write!(fmt, "{}", parts.join(" "))
+    }
+}
+
+//FUNCTIONS
+
+//now(): the current date and time (UTC) of the engine's clock
+pub fn now(engine: &Engine) -> Result<Value, String> {
+    Ok(Value::DateTime(DateTime::Both(engine.now())))
+}
+
+//today(): the current date
+pub fn today(engine: &Engine) -> Result<Value, String> {
+    Ok(Value::DateTime(DateTime::Date(engine.now().div_euclid(DAY))))
+}
+
+//days_between(a, b): whole days from a to b
+pub fn days_between(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    stdlib::check_n(2, "days_between", valstack)?;
+    let b = expect_days("days_between", &valstack.pop().unwrap())?;
+    let a = expect_days("days_between", &valstack.pop().unwrap())?;
+    Ok(Value::Integer(b - a))
+}
+
+//weekday(date): 1 for monday to 7 for sunday
+pub fn weekday(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    stdlib::check_n(1, "weekday", valstack)?;
+    let days = expect_days("weekday", &valstack.pop().unwrap())?;
+    Ok(Value::Integer(weekday_of(days)))
+}
+
+//add_business_days(date, n): n days later, not counting saturdays and sundays
+pub fn add_business_days(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    stdlib::check_n(2, "add_business_days", valstack)?;
+    let count = valstack.pop().unwrap();
+    let date = valstack.pop().unwrap();
+    let days = expect_days("add_business_days", &date)?;
+    if !count.is_int() {
+        return Err(format!("invalid types in function 'add_business_days': {} {}", date.type_str(), count.type_str()))
+    }
+    let count = count.to_int();
+    if count.abs() > max_days() - min_days() {
+        return Err(out_of_range())
+    }
+    //1 for monday to 7 for sunday
+    let weekday = weekday_of(days);
+    let result = if count > 0 {
+        //counted from the monday of the week, a weekend counts like its friday
+        let total = weekday.min(5) - 1 + count;
+        days - (weekday - 1) + total / 5 * 7 + total % 5
+    }
+    else if count < 0 {
+        //counted back from the friday of the week, a weekend counts like the next monday
+        let friday = if weekday > 5 { days + 12 - weekday } else { days + 5 - weekday };
+        let total = (if weekday > 5 { 4 } else { 5 - weekday }) - count;
+        friday - total / 5 * 7 - total % 5
+    }
+    else {
+        days
+    };
+    if result < min_days() || result > max_days() {
+        return Err(out_of_range())
+    }
+    Ok(Value::DateTime(DateTime::Date(result)))
+}
+
+//ARITHMETIC
+
+//None if none of the operands is a date, time or duration
+pub fn arith(fnname: &str, p1: &Value, p2: &Value) -> Option<Result<Value, String>> {
+    if !is_time_value(p1) && !is_time_value(p2) {
+        return None
+    }
+    Some(match (fnname, p1, p2) {
+        ("add", &Value::DateTime(a), &Value::Duration(b)) | ("add", &Value::Duration(b), &Value::DateTime(a)) => {
+            a.add(b).map(Value::DateTime)
+        },
+        ("sub", &Value::DateTime(a), &Value::Duration(b)) => a.add(b.neg()).map(Value::DateTime),
+        ("sub", &Value::DateTime(a), &Value::DateTime(b)) => a.sub(&b).map(Value::Duration),
+        //2026-10-18 + 14:30
+        ("add", &Value::DateTime(DateTime::Date(days)), &Value::DateTime(DateTime::Time(seconds))) |
+        ("add", &Value::DateTime(DateTime::Time(seconds)), &Value::DateTime(DateTime::Date(days))) => {
+            Ok(Value::DateTime(DateTime::Both(days * DAY + seconds)))
+        },
+        ("add", &Value::Duration(a), &Value::Duration(b)) => a.add(b).map(Value::Duration),
+        ("sub", &Value::Duration(a), &Value::Duration(b)) => a.add(b.neg()).map(Value::Duration),
+        ("mul", &Value::Duration(a), b) | ("mul", b, &Value::Duration(a)) if b.is_number() => {
+            Duration::of(b.to_float(), a.seconds).map(Value::Duration)
+        },
+        ("div", &Value::Duration(a), b) if b.is_number() => {
+            if b.to_float() == 0.0 {
+                Err(format!("divide by zero"))
+            }
+            else {
+                Duration::of(1.0 / b.to_float(), a.seconds).map(Value::Duration)
+            }
+        },
+        //how often b fits into a, e.g. 90min / 1h
+        ("div", &Value::Duration(a), &Value::Duration(b)) => {
+            if b.seconds == 0 {
+                Err(format!("divide by zero"))
+            }
+            else {
+                Ok(Value::Float(a.seconds as f64 / b.seconds as f64))
+            }
+        },
+        _ => Err(format!("invalid types in function '{}': {} {}", fnname, p1.type_str(), p2.type_str()))
+    })
+}
+
+pub fn negate(p1: &Value) -> Option<Value> {
+    match *p1 {
+        Value::Duration(a) => Some(Value::Duration(a.neg())),
+        _ => None
+    }
+}
+
+//None if none of the operands is a date, time or duration
+pub fn compare(fnname: &str, p1: &Value, p2: &Value) -> Option<Result<Ordering, String>> {
+    if !is_time_value(p1) && !is_time_value(p2) {
+        return None
+    }
+    Some(match (p1, p2) {
+        (&Value::DateTime(a), &Value::DateTime(b)) => a.compare(&b),
+        (&Value::Duration(a), &Value::Duration(b)) => Ok(a.seconds.cmp(&b.seconds)),
+        _ => Err(format!("invalid types in function '{}': {} {}", fnname, p1.type_str(), p2.type_str()))
+    })
+}
+
+//UTILS
+
+//a date, time or duration
+fn is_time_value(val: &Value) -> bool {
+    match *val {
+        Value::DateTime(_) | Value::Duration(_) => true,
+        _ => false
+    }
+}
+
+//seconds since 1970-01-01 00:00 of the system clock
+pub fn system_time() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(v) => v.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64)
+    }
+}
+
+fn expect_days(fnname: &str, val: &Value) -> Result<i64, String> {
+    match *val {
+        Value::DateTime(v) if v.days().is_some() => Ok(v.days().unwrap()),
+        _ => Err(format!("invalid types in function '{}': {}", fnname, val.type_str()))
+    }
+}
+
+//dates have four digit years, so they can be written and read back
+fn min_days() -> i64 {
+    days_from_civil(0, 1, 1)
+}
+
+fn max_days() -> i64 {
+    days_from_civil(9999, 12, 31)
+}
+
+fn out_of_range() -> String {
+    format!("date out of range")
+}
+
+fn weekday_of(days: i64) -> i64 {
+    //1970-01-01 was a thursday
+    (days + 3).rem_euclid(7) + 1
+}
+
+//'2026-10-18' as days since 1970-01-01
+fn parse_date(src: &str) -> Option<i64> {
+    let parts = src.split('-').collect::<Vec<&str>>();
+    if parts.len() != 3 || parts[0].len() != 4 || parts[1].len() != 2 || parts[2].len() != 2 ||
+            !parts.iter().all(|x| x.chars().all(|c| c.is_digit(10))) {
+        return None
+    }
+    let (year, month, day) = (parts[0].parse::<i64>().ok()?, parts[1].parse::<i64>().ok()?, parts[2].parse::<i64>().ok()?);
+    if month < 1 || month > 12 || day < 1 || day > days_in_month(year, month) {
+        return None
+    }
+    Some(days_from_civil(year, month, day))
+}
+
+//'14:30' or '14:30:05' as seconds since midnight
+fn parse_time(src: &str) -> Option<i64> {
+    let parts = src.split(':').collect::<Vec<&str>>();
+    if parts.len() < 2 || parts.len() > 3 || !parts.iter().all(|x| x.len() == 2 && x.chars().all(|c| c.is_digit(10))) {
+        return None
+    }
+    let numbers = parts.iter().map(|x| x.parse::<i64>().unwrap()).collect::<Vec<i64>>();
+    let seconds = if numbers.len() == 3 { numbers[2] } else { 0 };
+    if numbers[0] > 23 || numbers[1] > 59 || seconds > 59 {
+        return None
+    }
+    Some(numbers[0] * 3600 + numbers[1] * 60 + seconds)
+}
+
+fn write_date(fmt: &mut Formatter, days: i64) -> Result<(), fmt::Error> {
+    let (year, month, day) = civil_from_days(days);
+    write!(fmt, "{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn write_time(fmt: &mut Formatter, seconds: i64) -> Result<(), fmt::Error> {
+    write!(fmt, "{:02}:{:02}", seconds / 3600, seconds % 3600 / 60)?;
+    if seconds % 60 != 0 {
+        write!(fmt, ":{:02}", seconds % 60)?;
+    }
+    Ok(())
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+//gregorian calendar, see howard hinnant's 'chrono-compatible low-level date algorithms'
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::run;
+
+    #[test]
+    fn invalid_dates() {
+        let mut engine = Engine::new();
+        for date in &["2026-02-30", "2026-13-45", "25:00", "0:00", "2026-10-18T25:00"] {
+            assert_eq!(run(&mut engine, date), format!("error: invalid date/time: {}", date));
+        }
+        assert_eq!(run(&mut engine, "2024-02-29"), "2024-02-29 [date]");
+    }
+
+    #[test]
+    fn business_days() {
+        //day by day, skipping saturdays and sundays
+        fn stepwise(mut days: i64, count: i64) -> i64 {
+            for _ in 0..count.abs() {
+                days += count.signum();
+                while weekday_of(days) > 5 {
+                    days += count.signum();
+                }
+            }
+            days
+        }
+        let start = days_from_civil(2026, 10, 12);
+        for days in start..start + 14 {
+            for count in -12..13 {
+                let mut stack = vec![Value::DateTime(DateTime::Date(days)), Value::Integer(count)];
+                assert_eq!(add_business_days(&mut stack), Ok(Value::DateTime(DateTime::Date(stepwise(days, count)))));
+            }
+        }
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "add_business_days(2026-10-16, 100000000000)"), "error: date out of range");
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "1e308 days + 1e308 days"), "error: duration out of range");
+        assert_eq!(run(&mut engine, "100000000000000 days + 100000000000000 days"), "error: duration out of range");
+        assert_eq!(run(&mut engine, "2026-01-01 + 1e17 days"), "error: duration out of range");
+        assert_eq!(run(&mut engine, "2026-01-01 + 1e10 days"), "error: date out of range");
+        assert_eq!(run(&mut engine, "9999-12-31T23:00 + 1h"), "error: date out of range");
+        assert_eq!(run(&mut engine, "0000-01-01 - 1 day"), "error: date out of range");
+        assert_eq!(run(&mut engine, "1 day * 1e300"), "error: duration out of range");
+        assert_eq!(run(&mut engine, "23:00 + 1e12 days + 2h"), "01:00 [time]");
+    }
+}
@@ -0,0 +1,339 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter, self};
+
+//decimal places of results that are not exact, e.g. 1/3
+pub const DEFAULT_PLACES: u32 = 16;
+
+const GUARD_PLACES: u32 = 6;
+
+//mantissa * 10^-scale, all operations return None on overflow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32
+}
+
+impl Decimal {
+    pub fn from_int(v: i64) -> Decimal {
+        Decimal {
+            mantissa: v as i128,
+            scale: 0
+        }
+    }
+
+    //'12.50', '.5', '3e-2'
+    pub fn parse(src: &str) -> Option<Decimal> {
+        let (number, exponent) = match src.find(|x| x == 'e' || x == 'E') {
+            Some(index) => (&src[..index], src[index + 1..].parse::<i32>().ok()?),
+            None => (src, 0)
+        };
+        let (int_part, frac_part) = match number.find('.') {
+            Some(index) => (&number[..index], &number[index + 1..]),
+            None => (number, "")
+        };
+        if int_part.len() + frac_part.len() == 0 ||
+                !int_part.chars().chain(frac_part.chars()).all(|x| x.is_digit(10)) {
+            return None
+        }
+        let mut mantissa: i128 = 0;
+        for ch in int_part.chars().chain(frac_part.chars()) {
+            mantissa = mantissa.checked_mul(10)?.checked_add(ch.to_digit(10).unwrap() as i128)?;
+        }
+        let scale = frac_part.len() as i32 - exponent;
+        if scale < 0 {
+            mantissa = mantissa.checked_mul(pow10(-scale as u32)?)?;
+            return Some(Decimal { mantissa: mantissa, scale: 0 })
+        }
+        Some(Decimal { mantissa: mantissa, scale: scale as u32 })
+    }
+
+    //for results of float functions (sin, exp, ...), uses the shortest representation of the float
+    pub fn from_f64(v: f64, places: u32) -> Option<Decimal> {
+        if !v.is_finite() {
+            return None
+        }
+        let text = format!("{:?}", v.abs());
+        let result = Decimal::parse(&text)?.round(places)?;
+        if v < 0.0 { Some(result.neg()) } else { Some(result) }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        //through the text, so that 0.1d becomes the float closest to 0.1
+        format!("{}", self).parse::<f64>().unwrap()
+    }
+
+    pub fn to_i64(&self) -> i64 {
+        (self.mantissa / pow10(self.scale).unwrap()) as i64
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa == 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.mantissa < 0
+    }
+
+    pub fn neg(self) -> Decimal {
+        Decimal { mantissa: -self.mantissa, scale: self.scale }
+    }
+
+    pub fn abs(self) -> Decimal {
+        Decimal { mantissa: self.mantissa.abs(), scale: self.scale }
+    }
+
+    pub fn add(self, other: Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale);
+        let mantissa = self.rescale(scale)?.checked_add(other.rescale(scale)?)?;
+        Some(Decimal { mantissa: mantissa, scale: scale })
+    }
+
+    pub fn sub(self, other: Decimal) -> Option<Decimal> {
+        self.add(other.neg())
+    }
+
+    //keeps the scale of the operands (1.50d * 2 is 3.00d) and rounds to places beyond that
+    pub fn mul(self, other: Decimal, places: u32) -> Option<Decimal> {
+        let scale = self.scale + other.scale;
+        let product = if scale > places {
+            let mantissa = mul_round(self.mantissa, other.mantissa, pow10(scale - places)?)?;
+            Decimal { mantissa: mantissa, scale: places }
+        }
+        else {
+            Decimal { mantissa: self.mantissa.checked_mul(other.mantissa)?, scale: scale }
+        };
+        Some(product.normalize_to(self.scale.max(other.scale).min(places)))
+    }
+
+    //rounded to places, without trailing zeros, None when dividing by zero
+    pub fn div(self, other: Decimal, places: u32) -> Option<Decimal> {
+        if other.is_zero() {
+            return None
+        }
+        let shift = places as i64 + other.scale as i64 - self.scale as i64;
+        let (num, den) = if shift >= 0 {
+            (self.mantissa.checked_mul(pow10(shift as u32)?)?, other.mantissa)
+        }
+        else {
+            (self.mantissa, other.mantissa.checked_mul(pow10(-shift as u32)?)?)
+        };
+        let result = Decimal { mantissa: round_div(num, den)?, scale: places };
+        Some(result.normalize())
+    }
+
+    pub fn powi(self, exponent: i64, places: u32) -> Option<Decimal> {
+        //square and multiply, with guard digits against the rounding of each step
+        let guarded = places + GUARD_PLACES;
+        let mut result = Decimal::from_int(1);
+        let mut base = self;
+        let mut rest = exponent.checked_abs()?;
+        while rest > 0 {
+            if rest % 2 == 1 {
+                result = result.mul(base, guarded)?;
+            }
+            rest /= 2;
+            if rest > 0 {
+                base = base.mul(base, guarded)?;
+            }
+        }
+        if exponent < 0 {
+            Decimal::from_int(1).div(result, places)
+        }
+        else {
+            Some(result.round(places)?.normalize_to(self.scale.min(places)))
+        }
+    }
+
+    //half away from zero
+    pub fn round(self, places: u32) -> Option<Decimal> {
+        if self.scale <= places {
+            return Some(self)
+        }
+        let mantissa = round_div(self.mantissa, pow10(self.scale - places)?)?;
+        Some(Decimal { mantissa: mantissa, scale: places })
+    }
+
+    pub fn floor(self) -> Decimal {
+        let unit = pow10(self.scale).unwrap();
+        let mut mantissa = self.mantissa / unit;
+        if self.mantissa % unit != 0 && self.mantissa < 0 {
+            mantissa -= 1;
+        }
+        Decimal { mantissa: mantissa, scale: 0 }
+    }
+
+    pub fn ceil(self) -> Decimal {
+        self.neg().floor().neg()
+    }
+
+    pub fn compare(&self, other: &Decimal) -> Option<Ordering> {
+        let scale = self.scale.max(other.scale);
+        Some(self.rescale(scale)?.cmp(&other.rescale(scale)?))
+    }
+
+    fn rescale(&self, scale: u32) -> Option<i128> {
+        self.mantissa.checked_mul(pow10(scale - self.scale)?)
+    }
+
+    //without trailing zeros
+    pub fn normalize(self) -> Decimal {
+        self.normalize_to(0)
+    }
+
+    //strips trailing zeros, but keeps at least min_scale places
+    fn normalize_to(mut self, min_scale: u32) -> Decimal {
+        while self.scale > min_scale && self.mantissa % 10 == 0 {
+            self.mantissa /= 10;
+            self.scale -= 1;
+        }
+        self
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        let digits = format!("{:0>width$}", self.mantissa.abs(), width = self.scale as usize + 1);
+        let (int_part, frac_part) = digits.split_at(digits.len() - self.scale as usize);
+        if self.mantissa < 0 {
+            write!(fmt, "-")?;
+        }
+        if frac_part.is_empty() {
+            write!(fmt, "{}", int_part)
+        }
+        else {
+            write!(fmt, "{}.{}", int_part, frac_part)
+        }
+    }
+}
+
+fn pow10(exponent: u32) -> Option<i128> {
+    10i128.checked_pow(exponent)
+}
+
+//num / den, rounded half away from zero
+fn round_div(num: i128, den: i128) -> Option<i128> {
+    let quot = num / den;
+    let rem = num % den;
+    if rem.abs().checked_mul(2)? >= den.abs() {
+        if (num < 0) == (den < 0) { quot.checked_add(1) } else { quot.checked_sub(1) }
+    }
+    else {
+        Some(quot)
+    }
+}
+
+//a * b / den, rounded half away from zero, through a 256 bit product
+fn mul_round(a: i128, b: i128, den: i128) -> Option<i128> {
+    if let Some(product) = a.checked_mul(b) {
+        return round_div(product, den)
+    }
+    let negative = (a < 0) != (b < 0);
+    let (high, low) = wide_mul(a.checked_abs()? as u128, b.checked_abs()? as u128);
+    //long division, den < 2^127 so the remainder never overflows when shifted
+    let den = den as u128;
+    let mut quot: u128 = 0;
+    let mut rem: u128 = 0;
+    for bit in (0..256).rev() {
+        let next = if bit >= 128 { (high >> (bit - 128)) & 1 } else { (low >> bit) & 1 };
+        rem = (rem << 1) | next;
+        if rem >= den {
+            rem -= den;
+            if bit >= 128 {
+                return None
+            }
+            quot |= 1 << bit;
+        }
+    }
+    if rem * 2 >= den {
+        quot = quot.checked_add(1)?;
+    }
+    if quot > i128::max_value() as u128 {
+        return None
+    }
+    if negative { Some(-(quot as i128)) } else { Some(quot as i128) }
+}
+
+//(high, low) halves of a * b
+fn wide_mul(a: u128, b: u128) -> (u128, u128) {
+    let mask = (1u128 << 64) - 1;
+    let (a_high, a_low) = (a >> 64, a & mask);
+    let (b_high, b_low) = (b >> 64, b & mask);
+    let low_low = a_low * b_low;
+    let high_low = a_high * b_low;
+    let low_high = a_low * b_high;
+    let high_high = a_high * b_high;
+    let middle = (low_low >> 64) + (high_low & mask) + (low_high & mask);
+    let low = (low_low & mask) | (middle << 64);
+    let high = high_high + (high_low >> 64) + (low_high >> 64) + (middle >> 64);
+    (high, low)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::Engine;
+    use testing::run;
+
+    fn dec(src: &str) -> Decimal {
+        Decimal::parse(src).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(format!("{}", dec("12.50")), "12.50");
+        assert_eq!(format!("{}", dec(".5")), "0.5");
+        assert_eq!(format!("{}", dec("3e-2")), "0.03");
+        assert_eq!(format!("{}", dec("3e2")), "300");
+        assert!(Decimal::parse("1.2.3").is_none());
+        assert!(Decimal::parse("").is_none());
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(format!("{}", dec("0.1").add(dec("0.2")).unwrap()), "0.3");
+        assert_eq!(format!("{}", dec("1.5").mul(dec("1.5"), 16).unwrap()), "2.25");
+        assert_eq!(format!("{}", Decimal::from_int(1).div(Decimal::from_int(3), 16).unwrap()), "0.3333333333333333");
+        assert_eq!(format!("{}", Decimal::from_int(2).div(Decimal::from_int(3), 16).unwrap()), "0.6666666666666667");
+        assert_eq!(format!("{}", dec("1.1").powi(2, 16).unwrap()), "1.21");
+        assert_eq!(format!("{}", Decimal::from_int(2).powi(-2, 16).unwrap()), "0.25");
+        assert!(Decimal::from_int(1).div(Decimal::from_int(0), 16).is_none());
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(format!("{}", dec("2.5").round(0).unwrap()), "3");
+        assert_eq!(format!("{}", dec("2.5").neg().round(0).unwrap()), "-3");
+        assert_eq!(format!("{}", dec("0.125").round(2).unwrap()), "0.13");
+        assert_eq!(format!("{}", dec("0.1249").round(2).unwrap()), "0.12");
+        assert_eq!(format!("{}", dec("1.5").round(3).unwrap()), "1.5");
+        assert_eq!(format!("{}", Decimal::from_f64(0.1 + 0.2, 16).unwrap()), "0.3000000000000000");
+    }
+
+    #[test]
+    fn overflow() {
+        let big = dec("99999999999999999999999999999999999999");
+        assert!(big.add(big).is_none());
+        assert!(big.mul(big, 16).is_none());
+        assert!(Decimal::parse("999999999999999999999999999999999999999").is_none());
+    }
+
+    #[test]
+    fn numeric_mode() {
+        let mut engine = Engine::new();
+        assert!(run(&mut engine, ":numeric  ").starts_with("error: numeric usage"));
+        assert_eq!(run(&mut engine, "sqrt(2d)"), "1.414213562373095 [decimal]");
+        assert_eq!(run(&mut engine, "2d^0.5d"), "1.414213562373095 [decimal]");
+        run(&mut engine, ":numeric decimal 28");
+        assert_eq!(run(&mut engine, "sqrt(2)"), "1.4142135623730950488016887242 [decimal]");
+        assert_eq!(run(&mut engine, "2^0.5"), "1.4142135623730950488016887242 [decimal]");
+        assert_eq!(run(&mut engine, "(-8)^0.5"), "error: invalid arguments in function 'pow'");
+    }
+
+    #[test]
+    fn round_floats() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "round(1.2345, 2)"), "1.23 [float]");
+        assert_eq!(run(&mut engine, "round(1e300, 28)"), run(&mut engine, "1e300"));
+        assert_eq!(run(&mut engine, "round(2.5d, 0)"), "3 [decimal]");
+    }
+}
@@ -1,17 +1,121 @@
-use std::collections::HashMap;
-use std::io::{self, Read};
-use std::fs::File;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read, Write};
+use std::fs::{self, File};
+use std::path::Path;
+use std::env;
+use std::mem;
 
 use interpreter;
-use value::Value;
-use function::Function;
+use value::{Value, NumericMode};
+use decimal::{self, Decimal};
+use bigfloat::{self, BigFloat};
+use datetime::{self, DateTime};
+use function::{self, Function};
 use tokenize;
+use solver;
+use expr::Expr;
+use strings;
+use block;
+use module;
+use session;
+use stdlib;
+
+//leaves room for about 10 digits before the point in the 38 digits of a decimal
+const MAX_DECIMAL_PLACES: u32 = 28;
+
+//the long division of bigfloats gets slow above that
+const MAX_PRECISION: u32 = 1000;
+
+//of ':assert_close' without a tolerance
+const DEFAULT_TOLERANCE: f64 = 1e-9;
+
+//steps that ':undo' can take back
+const MAX_UNDO: usize = 100;
+
+//':while' and ':for' stop there instead of running forever
+const MAX_ITERATIONS: u32 = 1000000;
+
+//the commands of eval_line, for completion in the terminal
+pub const COMMANDS: &[&str] = &[
+    ":var", ":fn", ":let", ":deps", ":rvar", ":rfn", ":load", ":import", ":undo", ":redo", ":history",
+    ":save", ":restore", ":autosave", ":path", ":for", ":while", ":if", ":else", ":end", ":assert",
+    ":assert_close", ":print", ":numeric", ":precision", ":clock", ":table", ":simplify", ":diff", ":solve"
+];
+
+//the stdlib functions with the names of their arguments, e.g. ("pmt", "rate, nper, pv, fv")
+pub fn builtin_functions() -> &'static [(&'static str, &'static str)] {
+    stdlib::SIGNATURES
+}
 
 pub struct Engine {
     //so that no empty hashmap has to be generated on every interpreted line (-> local vars)
     static_empty_map: HashMap<String, Value>,
     vars: HashMap<String, Value>,
-    functions: HashMap<String, Function>
+    functions: HashMap<String, Function>,
+    numeric: NumericMode,
+    decimal_places: u32,
+    precision: u32,
+    //a fixed time for now() and today(), the system clock if not set
+    clock: Option<i64>,
+    //lines printed by commands, e.g. ':simplify'
+    output: Vec<String>,
+    //counts the assertions of scripts instead of stopping at the first one that fails
+    tests: Option<TestReport>,
+    //the scripts being loaded (the last one loaded by the one before) and their current line
+    loading: Vec<(String, usize)>,
+    //the directories ':import name' looks in for 'name.tc' after the current directory
+    search_path: Vec<String>,
+    //the full paths and namespaces of the imported scripts, each is only imported once
+    imported: Vec<(String, String)>,
+    //the number of results so far, the next result is $counter
+    counter: usize,
+    //':save' to this path when termcalc exits
+    autosave: Option<String>,
+    //the changes of the lines, the last one is undone first
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    //the changes of the line eval_line is running, the commands add the names before they change them
+    step: Option<Step>,
+    //the variables of ':let', computed again after every line
+    formulas: HashMap<String, Formula>,
+    //the calls of user functions that are running, see interpreter::call_fn()
+    call_depth: Cell<usize>
+}
+
+//':let Area = W*H'
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    pub text: String,
+    //the variables used in text
+    pub deps: Vec<String>,
+    //the numeric mode the numbers of text are read in, the one of ':let'
+    pub mode: NumericMode
+}
+
+//what a line changed, with the old and the new value (None if there was none)
+struct Step {
+    line: String,
+    vars: Changes<Value>,
+    functions: Changes<Function>,
+    formulas: Changes<Formula>,
+    counter: (usize, usize)
+}
+
+type Changes<T> = BTreeMap<String, (Option<T>, Option<T>)>;
+
+//an ':import' that is ready to run
+struct Import {
+    path: String,
+    key: String,
+    namespace: String,
+    script: String
+}
+
+pub struct TestReport {
+    pub passed: usize,
+    //the messages, with the location
+    pub failed: Vec<String>
 }
 
 impl Engine {
@@ -19,7 +123,23 @@ impl Engine {
         Engine {
             static_empty_map: HashMap::new(),
             vars: HashMap::new(),
-            functions: HashMap::new()
+            functions: HashMap::new(),
+            numeric: NumericMode::Float,
+            decimal_places: decimal::DEFAULT_PLACES,
+            precision: bigfloat::DEFAULT_PRECISION,
+            clock: None,
+            output: Vec::new(),
+            tests: None,
+            loading: Vec::new(),
+            search_path: Vec::new(),
+            imported: Vec::new(),
+            counter: 0,
+            autosave: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            step: None,
+            formulas: HashMap::new(),
+            call_depth: Cell::new(0)
         }
     }
 
@@ -31,9 +151,141 @@ impl Engine {
         &self.functions
     }
 
+    pub fn call_depth(&self) -> &Cell<usize> {
+        &self.call_depth
+    }
+
+    pub fn numeric(&self) -> NumericMode {
+        self.numeric
+    }
+
+    //decimal places of decimal results that are not exact
+    pub fn decimal_places(&self) -> u32 {
+        self.decimal_places
+    }
+
+    //significant digits of bigfloat results
+    pub fn precision(&self) -> u32 {
+        self.precision
+    }
+
+    //seconds since 1970-01-01 00:00 (UTC)
+    pub fn now(&self) -> i64 {
+        self.clock.unwrap_or_else(datetime::system_time)
+    }
+
+    //the fixed time of ':clock', None for the system clock
+    pub fn clock(&self) -> Option<i64> {
+        self.clock
+    }
+
+    //None for the system clock
+    pub fn set_clock(&mut self, fixed: Option<i64>) {
+        self.clock = fixed;
+    }
+
+    //PI and E, as precise as the numeric mode allows
+    pub fn constant(&self, name: &str) -> Option<Value> {
+        let digits = match self.numeric {
+            NumericMode::Float => 0,
+            NumericMode::Decimal => self.decimal_places + 2,
+            NumericMode::BigFloat => self.precision
+        };
+        let value = match name {
+            "PI" if self.numeric == NumericMode::Float => return Some(Value::Float(::std::f64::consts::PI)),
+            "E" if self.numeric == NumericMode::Float => return Some(Value::Float(::std::f64::consts::E)),
+            "PI" => BigFloat::pi(digits),
+            "E" => BigFloat::from_int(1).exp(digits).unwrap(),
+            _ => return None
+        };
+        if self.numeric == NumericMode::Decimal {
+            let decimal = Decimal::parse(&format!("{}", value)).unwrap();
+            return Some(Value::Decimal(decimal.round(self.decimal_places).unwrap()))
+        }
+        Some(Value::BigFloat(value))
+    }
+
+    //':assert' in scripts counts from now on, see take_tests()
+    pub fn start_tests(&mut self) {
+        self.tests = Some(TestReport { passed: 0, failed: Vec::new() });
+    }
+
+    //the assertions since start_tests()
+    pub fn take_tests(&mut self) -> Option<TestReport> {
+        self.tests.take()
+    }
+
+    //stores the result of a line as $0, $1, ... and returns the name
+    pub fn add_result(&mut self, val: Value) -> String {
+        let name = format!("${}", self.counter);
+        let old = self.vars.insert(name.clone(), val.clone());
+        self.counter += 1;
+        let mut vars = BTreeMap::new();
+        vars.insert(name.clone(), (old, Some(val.clone())));
+        self.record(Step {
+            line: format!("{} = {}", name, val.literal()),
+            vars: vars,
+            functions: BTreeMap::new(),
+            formulas: BTreeMap::new(),
+            counter: (self.counter - 1, self.counter)
+        });
+        name
+    }
+
+    pub fn result_count(&self) -> usize {
+        self.counter
+    }
+
+    pub fn autosave(&self) -> Option<&String> {
+        self.autosave.as_ref()
+    }
+
+    //':save' to the autosave path, if there is one
+    pub fn save_on_exit(&mut self) -> LineResult {
+        match self.autosave.clone() {
+            Some(path) => self.save_cmd(path),
+            None => LineResult::Success
+        }
+    }
+
+    pub fn search_path(&self) -> &Vec<String> {
+        &self.search_path
+    }
+
+    //a directory for ':import name'
+    pub fn add_search_dir(&mut self, dir: String) {
+        if !self.search_path.contains(&dir) {
+            self.search_path.push(dir);
+        }
+    }
+
+    //the lines printed since the last call
+    pub fn take_output(&mut self) -> Vec<String> {
+        mem::replace(&mut self.output, Vec::new())
+    }
+
+    //the body of a user function as a simplified formula, the function itself runs the code as it was typed in,
+    //since 'S + S' -> '2*S' is only the same for numbers
+    pub fn fn_body(&self, name: &String) -> Result<String, String> {
+        let function = match self.functions.get(name) {
+            Some(v) => v,
+            None => return Err(format!("function {} not found", name))
+        };
+        //bodies that cannot be simplified, like '(Y -> Y*X)(2)', are shown as typed
+        match Expr::from_code(&function.code(), self) {
+            Ok(body) => Ok(format!("{}", body.simplify(self))),
+            Err(_) => Ok(function.text().clone())
+        }
+    }
+
     pub fn eval_stmt(&mut self, line: String) -> StatementResult{
-        let token = tokenize::tokenize(line);
-        let code = match interpreter::rearrange(token) {
+        let mode = self.numeric;
+        self.eval_text(line, mode)
+    }
+
+    //like eval_stmt, with the numbers of line read in mode
+    fn eval_text(&mut self, line: String, mode: NumericMode) -> StatementResult {
+        let code = match tokenize::tokenize(line, mode, &self.functions).and_then(interpreter::rearrange) {
             Ok(v) => v,
             Err(e) => return StatementResult::Error(e)
         };
@@ -48,15 +300,146 @@ impl Engine {
     }
 
     pub fn set_val(&mut self, name: String, val: Value) {
+        self.touch_var(&name);
         self.vars.insert(name, val);
     }
 
     //true if already exists
     pub fn add_func(&mut self, name: String, func: Function) {
+        self.touch_fn(&name);
         let _ = self.functions.insert(name, func);
     }
 
+    //keeps the old value of name for ':undo', before the line changes it
+    fn touch_var(&mut self, name: &str) {
+        if let Some(ref mut step) = self.step {
+            touch(&mut step.vars, &self.vars, name);
+        }
+    }
+
+    fn touch_fn(&mut self, name: &str) {
+        if let Some(ref mut step) = self.step {
+            touch(&mut step.functions, &self.functions, name);
+        }
+    }
+
+    fn touch_formula(&mut self, name: &str) {
+        if let Some(ref mut step) = self.step {
+            touch(&mut step.formulas, &self.formulas, name);
+        }
+    }
+
+    //a line typed in, the changes of the variables and functions can be undone
     pub fn eval_line(&mut self, line: String) -> LineResult {
+        match line.trim() {
+            ":undo" => return self.undo_cmd(),
+            ":redo" => return self.redo_cmd(),
+            ":history" => return self.history_cmd(),
+            _ => {}
+        }
+        self.step = Some(Step {
+            line: line.clone(),
+            vars: BTreeMap::new(),
+            functions: BTreeMap::new(),
+            formulas: BTreeMap::new(),
+            counter: (self.counter, self.counter)
+        });
+        let result = self.exec_line(line);
+        let mut step = self.step.take().unwrap();
+        finish(&mut step.vars, &self.vars);
+        finish(&mut step.functions, &self.functions);
+        finish(&mut step.formulas, &self.formulas);
+        step.counter.1 = self.counter;
+        if !step.vars.is_empty() || !step.functions.is_empty() || !step.formulas.is_empty() {
+            self.record(step);
+        }
+        result
+    }
+
+    fn record(&mut self, step: Step) {
+        self.undo.push(step);
+        if self.undo.len() > MAX_UNDO {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    fn undo_cmd(&mut self) -> LineResult {
+        match self.undo.pop() {
+            Some(step) => {
+                self.apply_step(&step, false);
+                self.output.push(format!("undone: {}", step.line));
+                self.redo.push(step);
+                LineResult::Success
+            },
+            None => LineResult::Error(format!("nothing to undo"))
+        }
+    }
+
+    fn redo_cmd(&mut self) -> LineResult {
+        match self.redo.pop() {
+            Some(step) => {
+                self.apply_step(&step, true);
+                self.output.push(format!("redone: {}", step.line));
+                self.undo.push(step);
+                LineResult::Success
+            },
+            None => LineResult::Error(format!("nothing to redo"))
+        }
+    }
+
+    //the new values of the step, or the old ones to undo it
+    fn apply_step(&mut self, step: &Step, new: bool) {
+        for (name, &(ref old, ref new_val)) in &step.vars {
+            match if new { new_val } else { old } {
+                &Some(ref v) => self.vars.insert(name.clone(), v.clone()),
+                &None => self.vars.remove(name)
+            };
+        }
+        for (name, &(ref old, ref new_func)) in &step.functions {
+            match if new { new_func } else { old } {
+                &Some(ref f) => self.functions.insert(name.clone(), f.clone()),
+                &None => self.functions.remove(name)
+            };
+        }
+        for (name, &(ref old, ref new_formula)) in &step.formulas {
+            match if new { new_formula } else { old } {
+                &Some(ref f) => self.formulas.insert(name.clone(), f.clone()),
+                &None => self.formulas.remove(name)
+            };
+        }
+        self.counter = if new { step.counter.1 } else { step.counter.0 };
+    }
+
+    //the steps that can be undone and redone, with what they changed
+    fn history_cmd(&mut self) -> LineResult {
+        let mut lines = Vec::new();
+        for (index, step) in self.undo.iter().enumerate() {
+            lines.push(format!("{}: {}", index + 1, step.line));
+            lines.extend(step_changes(step));
+        }
+        if !self.redo.is_empty() {
+            lines.push(format!("undone:"));
+        }
+        for step in self.redo.iter().rev() {
+            lines.push(format!("{}", step.line));
+            lines.extend(step_changes(step));
+        }
+        if lines.is_empty() {
+            lines.push(format!("no changes"));
+        }
+        self.output.extend(lines);
+        LineResult::Success
+    }
+
+    //a line of the REPL or of a script, then the variables of ':let' are computed again
+    fn exec_line(&mut self, line: String) -> LineResult {
+        let result = self.run_line(line);
+        self.update_formulas();
+        result
+    }
+
+    fn run_line(&mut self, line: String) -> LineResult {
         if line.starts_with(":") {
             match line.find(char::is_whitespace) {
                 Some(index) => {
@@ -68,32 +451,88 @@ impl Engine {
                         ":fn" => {
                             self.fn_cmd(param.trim().to_owned())
                         },
+                        ":diff" => {
+                            self.diff_cmd(param.trim().to_owned())
+                        },
+                        ":numeric" => {
+                            self.numeric_cmd(param.trim().to_owned())
+                        },
+                        ":precision" => {
+                            self.precision_cmd(param.trim().to_owned())
+                        },
+                        ":clock" => {
+                            self.clock_cmd(param.trim().to_owned())
+                        },
+                        ":print" => {
+                            self.print_cmd(param.trim().to_owned())
+                        },
+                        ":assert" => {
+                            self.assert_cmd(param.trim().to_owned())
+                        },
+                        ":assert_close" => {
+                            self.assert_close_cmd(param.trim().to_owned())
+                        },
+                        ":table" => {
+                            self.table_cmd(param.trim().to_owned())
+                        },
+                        ":simplify" => {
+                            self.simplify_cmd(param.trim().to_owned())
+                        },
+                        ":solve" => {
+                            self.solve_cmd(param.trim().to_owned())
+                        },
                         ":load" => {
-                            let script = match read_file(param.trim().to_owned()) {
-                                Ok(v) => v,
-                                Err(e) => return LineResult::Error(format!("file read error: {}", e))
-                            };
-                            for line in script.split('\n') {
-                                if line.trim().len() == 0 || line.trim().starts_with("--") {
-                                    continue;
-                                }
-                                match self.eval_line(line.to_owned()) {
-                                    LineResult::Value(_) => {},
-                                    LineResult::Error(e) => {
-                                        return LineResult::Error(format!("error in script: {}", e))
-                                    },
-                                    LineResult::Success => {}
-                                };
+                            match self.read_script(param.trim()) {
+                                Ok((path, script)) => self.run_script(&path, &script),
+                                Err(e) => LineResult::Error(e)
+                            }
+                        },
+                        ":import" => {
+                            match self.prepare_import(param.trim()) {
+                                Ok(Some(import)) => match self.run_import(import) {
+                                    Ok(()) => LineResult::Success,
+                                    Err(e) => LineResult::Error(e)
+                                },
+                                Ok(None) => LineResult::Success,
+                                Err(e) => LineResult::Error(e)
+                            }
+                        },
+                        ":save" => {
+                            self.save_cmd(param.trim().to_owned())
+                        },
+                        ":restore" => {
+                            self.restore_cmd(param.trim().to_owned())
+                        },
+                        ":autosave" => {
+                            match param.trim() {
+                                "off" => self.autosave = None,
+                                path => self.autosave = Some(expand_home(path))
                             }
                             LineResult::Success
                         },
+                        ":path" => {
+                            self.path_cmd(param.trim().to_owned())
+                        },
+                        ":for" | ":while" | ":if" => {
+                            LineResult::Error(format!("'{}' can only be used in scripts", cmd))
+                        },
+                        ":let" => {
+                            self.let_cmd(param.trim().to_owned())
+                        },
+                        ":deps" => {
+                            self.deps_cmd(param.trim().to_owned())
+                        },
                         ":rvar" => {
+                            self.touch_var(param.trim());
+                            self.touch_formula(param.trim());
+                            self.formulas.remove(param.trim());
                             match self.vars.remove(param.trim()) {
                                 Some(_) => LineResult::Success,
                                 None => LineResult::Error(format!("var {} not found", param))
                             }
                         },
                         ":rfn" => {
+                            self.touch_fn(param.trim());
                             match self.functions.remove(param.trim()) {
                                 Some(_) => LineResult::Success,
                                 None => LineResult::Error(format!("function {} not found", param))
@@ -114,13 +553,327 @@ impl Engine {
         }
     }
 
+    //the lines of a script, a ':fn name X =' without a body is followed by the lines of the body,
+    //name is the file name in error messages
+    pub fn run_script(&mut self, name: &str, script: &str) -> LineResult {
+        match self.run_file(name, script) {
+            Ok(()) => LineResult::Success,
+            Err(e) => LineResult::Error(e)
+        }
+    }
+
+    fn run_file(&mut self, name: &str, script: &str) -> Result<(), String> {
+        self.loading.push((name.to_owned(), 0));
+        let result = self.run_lines(&continued_lines(script));
+        self.loading.pop();
+        result
+    }
+
+    //lines with their line numbers, the errors of all lines run by this have their location
+    fn run_lines(&mut self, lines: &[(usize, String)]) -> Result<(), String> {
+        let mut index = 0;
+        while index < lines.len() {
+            let (number, ref line) = lines[index];
+            index += 1;
+            if let Some(frame) = self.loading.last_mut() {
+                frame.1 = number;
+            }
+            let mut trimmed = line.trim().to_owned();
+            if trimmed.len() == 0 || trimmed.starts_with("--") {
+                continue;
+            }
+            if trimmed == ":else" || trimmed == ":end" {
+                return Err(self.locate(format!("'{}' without ':for', ':while' or ':if'", trimmed), line))
+            }
+            if let Some(keyword) = block_keyword(&trimmed) {
+                let (body, otherwise) = match script_block(lines, &mut index) {
+                    Some(v) => v,
+                    None => return Err(self.locate(format!("'{}' without ':end'", trimmed), line))
+                };
+                let param = trimmed[keyword.len()..].trim();
+                match (keyword, otherwise) {
+                    (":if", otherwise) => {
+                        if self.located(number, line, |x| x.condition(param))? {
+                            self.run_lines(&body)?;
+                        }
+                        else if let Some(otherwise) = otherwise {
+                            self.run_lines(&otherwise)?;
+                        }
+                    },
+                    (_, Some(_)) => return Err(self.locate(format!("':else' in '{}'", trimmed), line)),
+                    (":for", None) => {
+                        let (name, items) = self.located(number, line, |x| x.for_items(param))?;
+                        for item in items {
+                            self.set_val(name.clone(), item);
+                            self.update_formulas();
+                            self.run_lines(&body)?;
+                        }
+                    },
+                    _ => {
+                        let mut iterations = 0;
+                        while self.located(number, line, |x| x.condition(param))? {
+                            if iterations == MAX_ITERATIONS {
+                                return Err(self.locate(format!("':while {}' stopped after {} iterations", param, MAX_ITERATIONS), line))
+                            }
+                            self.run_lines(&body)?;
+                            iterations += 1;
+                        }
+                    }
+                }
+                continue;
+            }
+            if trimmed.starts_with(":load ") {
+                let param = trimmed[":load".len()..].trim().to_owned();
+                let (path, script) = self.located(number, line, |x| x.read_script(&param))?;
+                self.run_file(&path, &script)?;
+                continue;
+            }
+            if trimmed.starts_with(":import ") {
+                let param = trimmed[":import".len()..].trim().to_owned();
+                if let Some(import) = self.located(number, line, |x| x.prepare_import(&param))? {
+                    self.run_import(import)?;
+                }
+                continue;
+            }
+            if trimmed.starts_with(":fn ") && trimmed.ends_with('=') {
+                match block_body(lines, &mut index, indentation(line)) {
+                    Some(body) => trimmed = format!("{}\n{}", trimmed, body),
+                    None => return Err(self.locate(format!("'{}' without 'end'", trimmed), line))
+                }
+            }
+            let assertion = trimmed.starts_with(":assert");
+            match self.exec_line(trimmed) {
+                LineResult::Error(e) => {
+                    let error = self.locate(e, line);
+                    match self.tests {
+                        Some(ref mut report) if assertion => report.failed.push(error),
+                        _ => return Err(error)
+                    }
+                },
+                _ => if let Some(ref mut report) = self.tests {
+                    if assertion {
+                        report.passed += 1;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    //'geometry [as geo]' or '"path.tc" [as geo]', None if it is imported already
+    fn prepare_import(&mut self, param: &str) -> Result<Option<Import>, String> {
+        let (source, rest) = if param.starts_with('"') {
+            match param[1..].find('"') {
+                Some(index) => (&param[1..index + 1], &param[index + 2..]),
+                None => return Err(format!("unclosed '\"' in ':import {}'", param))
+            }
+        }
+        else {
+            let index = param.find(char::is_whitespace).unwrap_or(param.len());
+            (&param[..index], &param[index..])
+        };
+        let words = rest.split_whitespace().collect::<Vec<&str>>();
+        let stem = Path::new(source).file_stem().and_then(|x| x.to_str()).unwrap_or("").to_owned();
+        let namespace = match words.len() {
+            0 => stem,
+            2 if words[0] == "as" => words[1].to_owned(),
+            _ => return Err(format!("expected ':import name [as namespace]' or ':import \"path\" [as namespace]'"))
+        };
+        let valid = namespace.starts_with(char::is_lowercase) && namespace.chars().all(|x| x.is_alphanumeric() || x == '_');
+        if !valid {
+            return Err(format!("invalid namespace '{}', it has to start with a lowercase character", namespace))
+        }
+        let path = if param.starts_with('"') {
+            self.resolve_path(source)
+        }
+        else {
+            self.find_module(source)?
+        };
+        let key = match fs::canonicalize(&path) {
+            Ok(v) => v.to_string_lossy().into_owned(),
+            Err(e) => return Err(format!("file read error: {}: {}", path, e))
+        };
+        self.check_cycle(&path, &key)?;
+        match self.imported.iter().find(|x| x.1 == namespace) {
+            Some(x) if x.0 == key => return Ok(None),
+            Some(x) => return Err(format!("the namespace '{}' is used by {} already", namespace, x.0)),
+            None => {}
+        }
+        let script = match read_file(path.clone()) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("file read error: {}: {}", path, e))
+        };
+        Ok(Some(Import { path: path, key: key, namespace: namespace, script: script }))
+    }
+
+    //':load path', with the path as in error messages
+    fn read_script(&self, param: &str) -> Result<(String, String), String> {
+        let path = self.resolve_path(param);
+        let key = match fs::canonicalize(&path) {
+            Ok(v) => v.to_string_lossy().into_owned(),
+            Err(e) => return Err(format!("file read error: {}: {}", path, e))
+        };
+        self.check_cycle(&path, &key)?;
+        match read_file(path.clone()) {
+            Ok(v) => Ok((path, v)),
+            Err(e) => Err(format!("file read error: {}: {}", path, e))
+        }
+    }
+
+    //a script that is being loaded must not be loaded again by itself or the scripts it loads
+    fn check_cycle(&self, path: &str, key: &str) -> Result<(), String> {
+        if self.loading.iter().any(|x| same_file(&x.0, key)) {
+            let chain = self.loading.iter().map(|x| x.0.clone()).collect::<Vec<String>>();
+            return Err(format!("include cycle: {} -> {}", chain.join(" -> "), path))
+        }
+        Ok(())
+    }
+
+    //'~/a.tc' in the home directory, and a relative path in a script is relative to the script
+    fn resolve_path(&self, path: &str) -> String {
+        let path = expand_home(path);
+        match self.loading.last() {
+            Some(&(ref script, _)) if Path::new(&path).is_relative() => {
+                let dir = Path::new(script).parent().unwrap_or(Path::new(""));
+                dir.join(&path).to_string_lossy().into_owned()
+            },
+            _ => path
+        }
+    }
+
+    //'name.tc' in the directory of the script (or the current directory) or the search path
+    fn find_module(&self, name: &str) -> Result<String, String> {
+        let file = format!("{}.tc", name);
+        let mut candidates = vec![self.resolve_path(&file)];
+        candidates.extend(self.search_path.iter().map(|dir| Path::new(&expand_home(dir)).join(&file).to_string_lossy().into_owned()));
+        match candidates.into_iter().find(|x| Path::new(x).is_file()) {
+            Some(v) => Ok(v),
+            None => Err(format!("module '{}' not found, the search path is [{}]", name, self.search_path.join(", ")))
+        }
+    }
+
+    //runs the script with its own variables and functions and adds them with the namespace
+    fn run_import(&mut self, import: Import) -> Result<(), String> {
+        //the names of the script are not the ones of the step
+        let step = self.step.take();
+        let vars = mem::replace(&mut self.vars, HashMap::new());
+        let functions = mem::replace(&mut self.functions, HashMap::new());
+        let formulas = mem::replace(&mut self.formulas, HashMap::new());
+        let imported = mem::replace(&mut self.imported, Vec::new());
+        let result = self.run_file(&import.path, &import.script);
+        let module_vars = mem::replace(&mut self.vars, vars);
+        let module_functions = mem::replace(&mut self.functions, functions);
+        let module_formulas = mem::replace(&mut self.formulas, formulas);
+        self.imported = imported;
+        self.step = step;
+        result?;
+        let names = module_vars.keys().chain(module_functions.keys()).chain(module_formulas.keys())
+            .map(|x| (x.clone(), module::qualified(&import.namespace, x)))
+            .collect::<HashMap<String, String>>();
+        for (name, val) in module_vars {
+            self.set_val(names[&name].clone(), module::rename_value(&val, &names, &[]));
+        }
+        for (name, func) in module_functions {
+            let code = module::rename_code(&func.code(), &names, func.args());
+            let text = module::rename_text(func.text(), &names, func.args());
+            self.add_func(names[&name].clone(), Function::from_code(func.args().clone(), code, text, func.mode()));
+        }
+        for (name, formula) in module_formulas {
+            self.touch_formula(&names[&name]);
+            self.formulas.insert(names[&name].clone(), Formula {
+                text: module::rename_text(&formula.text, &names, &[]),
+                deps: formula.deps.iter().map(|x| names.get(x).unwrap_or(x).clone()).collect(),
+                mode: formula.mode
+            });
+        }
+        self.imported.push((import.key, import.namespace));
+        Ok(())
+    }
+
+    //f(self) with the location of the line in the error
+    fn located<T, F>(&mut self, number: usize, line: &str, f: F) -> Result<T, String> where F: FnOnce(&mut Engine) -> Result<T, String> {
+        if let Some(frame) = self.loading.last_mut() {
+            frame.1 = number;
+        }
+        f(self).map_err(|e| self.locate(e, line))
+    }
+
+    //'error in a.tc:3:5: ...' with the line and the ':load's that led to it
+    fn locate(&self, error: String, line: &str) -> String {
+        let (file, number) = match self.loading.last() {
+            Some(frame) => frame.clone(),
+            None => return error
+        };
+        let mut lines = error.lines();
+        let mut result = format!("error in {}:{}:{}: {}", file, number, error_column(&error, line), lines.next().unwrap_or(""));
+        result.push_str(&format!("\n    {} | {}", number, line.trim()));
+        for rest in lines {
+            result.push_str(&format!("\n{}", rest));
+        }
+        for frame in self.loading.iter().rev().skip(1) {
+            result.push_str(&format!("\n    loaded at {}:{}", frame.0, frame.1));
+        }
+        result
+    }
+
+    //'I in 1..10' -> ('I', [1, 2, ..., 10]), or 'X in [list]'
+    fn for_items(&mut self, param: &str) -> Result<(String, Vec<Value>), String> {
+        let params = param.splitn(3, char::is_whitespace).collect::<Vec<&str>>();
+        if params.len() != 3 || params[1] != "in" || !params[0].starts_with(char::is_uppercase) {
+            return Err(format!("expected ':for Name in from..to' or ':for Name in list', found ':for {}'", param))
+        }
+        let items = match params[2].find("..") {
+            Some(index) => {
+                let from = self.eval_value(&params[2][..index])?;
+                let to = self.eval_value(&params[2][index + 2..])?;
+                match (from, to) {
+                    (Value::Integer(a), Value::Integer(b)) => {
+                        if b as i128 - a as i128 >= MAX_ITERATIONS as i128 {
+                            return Err(format!("':for {}' would run more than {} iterations", param, MAX_ITERATIONS))
+                        }
+                        (a..=b).map(Value::Integer).collect()
+                    },
+                    (a, b) => return Err(format!("':for' needs integers around '..', found {} and {}", a.type_str(), b.type_str()))
+                }
+            },
+            None => match self.eval_value(params[2])? {
+                Value::List(items) => items,
+                v => return Err(format!("':for' needs a range or a list, found {}", v.type_str()))
+            }
+        };
+        Ok((params[0].to_owned(), items))
+    }
+
+    fn eval_value(&mut self, expr: &str) -> Result<Value, String> {
+        match self.eval_stmt(expr.to_owned()) {
+            StatementResult::Value(v) => Ok(v),
+            StatementResult::Error(e) => Err(e)
+        }
+    }
+
+    //a number that is not 0
+    fn condition(&mut self, expr: &str) -> Result<bool, String> {
+        match self.eval_value(expr)? {
+            ref v if v.is_number() => Ok(v.to_float() != 0.0),
+            v => Err(format!("the condition '{}' is {}, expected a number", expr, v.type_str()))
+        }
+    }
+
     fn fn_cmd(&mut self, param: String) -> LineResult {
         let split_index = match param.find("=") {
             Some(v) => v,
             None => return LineResult::Error(format!("no '=' found"))
         };
         let (args, line) = param.split_at(split_index);
-        let formatted_line = line.replace("=", " ");
+        let formatted_line = if line.contains('\n') {
+            match block::compile(&line[1..]) {
+                Ok(v) => v,
+                Err(e) => return LineResult::Error(format!("function parse error: {}", e))
+            }
+        }
+        else {
+            line[1..].to_owned()
+        };
         let mut arg_vec = args.split_whitespace().map(|x| x.to_owned()).collect::<Vec<String>>();
         if arg_vec.len() < 2 {
             return LineResult::Error(format!("no arguments or name in function definition"))
@@ -133,7 +886,7 @@ impl Engine {
             return LineResult::Error(format!("functions cannot end with '!'"));
         }
         arg_vec.remove(0);
-        let func = match Function::new(arg_vec, formatted_line){
+        let func = match Function::new(arg_vec, formatted_line, self.numeric, &self.functions){
             Ok(v) => v,
             Err(e) => return LineResult::Error(format!("function parse error: {}", e))
         };
@@ -141,13 +894,307 @@ impl Engine {
         LineResult::Success
     }
 
+    //':numeric float' or ':numeric decimal [places]'
+    fn numeric_cmd(&mut self, param: String) -> LineResult {
+        let params = param.split_whitespace().collect::<Vec<&str>>();
+        match (params.get(0).cloned().unwrap_or(""), params.get(1)) {
+            ("float", None) => {
+                self.numeric = NumericMode::Float;
+            },
+            ("bigfloat", None) => {
+                self.numeric = NumericMode::BigFloat;
+            },
+            ("decimal", places) => {
+                if let Some(places) = places {
+                    match places.parse::<u32>() {
+                        Ok(v) if v <= MAX_DECIMAL_PLACES => self.decimal_places = v,
+                        _ => return LineResult::Error(format!("decimal places have to be between 0 and {}", MAX_DECIMAL_PLACES))
+                    }
+                }
+                self.numeric = NumericMode::Decimal;
+            },
+            _ => return LineResult::Error(format!("numeric usage: ':numeric float', ':numeric decimal [places]' or ':numeric bigfloat'"))
+        }
+        LineResult::Success
+    }
+
+    //':precision digits', switches to bigfloats
+    fn precision_cmd(&mut self, param: String) -> LineResult {
+        match param.parse::<u32>() {
+            Ok(v) if v > 0 && v <= MAX_PRECISION => self.precision = v,
+            _ => return LineResult::Error(format!("precision has to be between 1 and {} digits", MAX_PRECISION))
+        }
+        self.numeric = NumericMode::BigFloat;
+        LineResult::Success
+    }
+
+    //':clock 2026-10-18T09:00' stops the clock, ':clock system' uses the system clock again
+    fn clock_cmd(&mut self, param: String) -> LineResult {
+        match DateTime::parse(&param) {
+            _ if param == "system" => self.set_clock(None),
+            Some(DateTime::Time(_)) | None => return LineResult::Error(format!("clock usage: ':clock <date or datetime>' or ':clock system'")),
+            Some(v) => self.set_clock(Some(v.seconds(0)))
+        }
+        LineResult::Success
+    }
+
+    //':print "Total: " + str(X)' prints the text of the value, e.g. in a script
+    fn print_cmd(&mut self, param: String) -> LineResult {
+        match self.eval_stmt(param) {
+            StatementResult::Value(v) => {
+                for line in strings::text(&v).split('\n') {
+                    self.output.push(line.to_owned());
+                }
+                LineResult::Success
+            },
+            StatementResult::Error(e) => LineResult::Error(e)
+        }
+    }
+
+    //an error if the term is 0
+    fn assert_cmd(&mut self, param: String) -> LineResult {
+        match self.condition(&param) {
+            Ok(true) => LineResult::Success,
+            Ok(false) => LineResult::Error(format!("assertion failed: {}", param)),
+            Err(e) => LineResult::Error(e)
+        }
+    }
+
+    //':assert_close A B [tolerance]' or ':assert_close a, b, tolerance' if the terms have spaces
+    fn assert_close_cmd(&mut self, param: String) -> LineResult {
+        let words = param.split_whitespace().map(|x| x.to_owned()).collect::<Vec<String>>();
+        let values = if words.len() == 2 || words.len() == 3 {
+            //the tolerance can be written like '1e-9'
+            words.iter().enumerate().map(|(i, x)| match x.parse::<f64>() {
+                Ok(v) if i == 2 => Ok(Value::Float(v)),
+                _ => self.eval_value(x)
+            }).collect::<Result<Vec<Value>, String>>()
+        }
+        else {
+            match self.eval_value(&format!("[{}]", param)) {
+                Ok(Value::List(items)) => Ok(items),
+                Ok(_) => Ok(Vec::new()),
+                Err(e) => Err(e)
+            }
+        };
+        let mut values = match values {
+            Ok(ref v) if v.len() == 2 || v.len() == 3 => v.clone(),
+            Ok(_) => return LineResult::Error(format!("expected ':assert_close [term] [term] [tolerance]'")),
+            Err(e) => return LineResult::Error(e)
+        };
+        if values.len() == 2 {
+            values.push(Value::Float(DEFAULT_TOLERANCE));
+        }
+        if let Some(v) = values.iter().find(|x| !x.is_number()) {
+            return LineResult::Error(format!("':assert_close' needs numbers, found {}", v.type_str()))
+        }
+        let (a, b, tolerance) = (&values[0], &values[1], values[2].to_float());
+        if (a.to_float() - b.to_float()).abs() <= tolerance {
+            LineResult::Success
+        }
+        else {
+            LineResult::Error(format!("assertion failed: {} is not within {:e} of {}", a.literal(), tolerance, b.literal()))
+        }
+    }
+
+    //the variables, functions and settings as a session script, see session.rs
+    fn save_cmd(&mut self, param: String) -> LineResult {
+        let path = expand_home(&param);
+        //written next to the file and renamed over it, so a failed write keeps the old session
+        let temp = format!("{}.tmp", path);
+        let result = File::create(&temp)
+            .and_then(|mut file| file.write_all(session::to_text(self).as_bytes()).and_then(|()| file.sync_all()))
+            .and_then(|()| fs::rename(&temp, &path));
+        match result {
+            Ok(()) => LineResult::Success,
+            Err(e) => {
+                let _ = fs::remove_file(&temp);
+                LineResult::Error(format!("file write error: {}: {}", path, e))
+            }
+        }
+    }
+
+    //replaces the whole state by the session in the file
+    fn restore_cmd(&mut self, param: String) -> LineResult {
+        let path = expand_home(&param);
+        let text = match read_file(path.clone()) {
+            Ok(v) => v,
+            Err(e) => return LineResult::Error(format!("file read error: {}: {}", path, e))
+        };
+        let counter = match session::header(&text) {
+            Ok(v) => v,
+            Err(e) => return LineResult::Error(e)
+        };
+        //the session is read into a new engine, so a file with an error does not change anything
+        let mut restored = Engine::new();
+        restored.counter = counter;
+        restored.loading = self.loading.clone();
+        if let LineResult::Error(e) = restored.run_script(&path, &text) {
+            return LineResult::Error(e)
+        }
+        for name in self.vars.keys().chain(restored.vars.keys()).cloned().collect::<Vec<String>>() {
+            self.touch_var(&name);
+        }
+        for name in self.functions.keys().chain(restored.functions.keys()).cloned().collect::<Vec<String>>() {
+            self.touch_fn(&name);
+        }
+        for name in self.formulas.keys().chain(restored.formulas.keys()).cloned().collect::<Vec<String>>() {
+            self.touch_formula(&name);
+        }
+        self.vars = restored.vars;
+        self.functions = restored.functions;
+        self.formulas = restored.formulas;
+        self.numeric = restored.numeric;
+        self.decimal_places = restored.decimal_places;
+        self.precision = restored.precision;
+        self.clock = restored.clock;
+        self.search_path = restored.search_path;
+        self.imported = restored.imported;
+        self.autosave = restored.autosave;
+        self.counter = restored.counter;
+        self.output.extend(restored.output);
+        LineResult::Success
+    }
+
+    //':path add [dir]' or ':path show'
+    fn path_cmd(&mut self, param: String) -> LineResult {
+        let index = param.find(char::is_whitespace).unwrap_or(param.len());
+        match (&param[..index], param[index..].trim()) {
+            ("add", dir) if !dir.is_empty() => {
+                self.add_search_dir(dir.to_owned());
+                LineResult::Success
+            },
+            ("show", "") => {
+                let lines = self.search_path.clone();
+                self.output.extend(lines);
+                LineResult::Success
+            },
+            _ => LineResult::Error(format!("expected ':path add [dir]' or ':path show'"))
+        }
+    }
+
+    //prints a list of lists with aligned columns
+    fn table_cmd(&mut self, param: String) -> LineResult {
+        let rows = match self.eval_stmt(param) {
+            StatementResult::Value(Value::List(rows)) => rows,
+            StatementResult::Value(v) => return LineResult::Error(format!("expected a list, found {}", v.type_str())),
+            StatementResult::Error(e) => return LineResult::Error(e)
+        };
+        let cells = rows.into_iter().map(|row| match row {
+            Value::List(items) => items.iter().map(|x| x.literal()).collect::<Vec<String>>(),
+            other => vec![other.literal()]
+        }).collect::<Vec<Vec<String>>>();
+        let mut widths: Vec<usize> = Vec::new();
+        for row in &cells {
+            for (index, cell) in row.iter().enumerate() {
+                if index >= widths.len() {
+                    widths.push(0);
+                }
+                widths[index] = widths[index].max(cell.chars().count());
+            }
+        }
+        for row in cells {
+            let line = row.iter().enumerate()
+                .map(|(index, cell)| format!("{:>width$}", cell, width = widths[index]))
+                .collect::<Vec<String>>()
+                .join("  ");
+            self.output.push(line);
+        }
+        LineResult::Success
+    }
+
+    fn simplify_cmd(&mut self, param: String) -> LineResult {
+        let code = match tokenize::tokenize(param, self.numeric, &self.functions).and_then(interpreter::rearrange) {
+            Ok(v) => v,
+            Err(e) => return LineResult::Error(e)
+        };
+        match Expr::from_code(&code, self) {
+            Ok(v) => {
+                let simplified = format!("{}", v.simplify(self));
+                self.output.push(simplified);
+                LineResult::Success
+            },
+            Err(e) => LineResult::Error(e)
+        }
+    }
+
+    //':diff f X [name]' defines name (default: f') as the derivative of f with respect to X
+    fn diff_cmd(&mut self, param: String) -> LineResult {
+        let params = param.split_whitespace().collect::<Vec<&str>>();
+        if params.len() < 2 || params.len() > 3 {
+            return LineResult::Error(format!("diff usage: ':diff [function] [var] [name]'"))
+        }
+        let (func_name, var) = (params[0].to_owned(), params[1]);
+        let new_name = match params.get(2) {
+            Some(v) => v.to_string(),
+            None => format!("{}'", func_name)
+        };
+        if new_name.starts_with(char::is_uppercase) {
+            return LineResult::Error(format!("function names should start with a lowercase character"))
+        }
+        let (args, body) = match self.functions.get(&func_name) {
+            Some(v) => (v.args().clone(), Expr::from_code(&v.code(), self)),
+            None => return LineResult::Error(format!("function {} not found", func_name))
+        };
+        if !args.iter().any(|x| x == var) {
+            return LineResult::Error(format!("'{}' is not an argument of {}", var, func_name))
+        }
+        let derivative = match body.and_then(|x| x.derive(var, self)) {
+            Ok(v) => v.simplify(self),
+            Err(e) => return LineResult::Error(e)
+        };
+        //the numbers of the text have their suffix, so it reads the same in float mode
+        self.add_func(new_name, Function::from_code(args, derivative.to_code(), format!("{}", derivative), NumericMode::Float));
+        LineResult::Success
+    }
+
+    //':solve X^2 + 3*X = 10 for X', the current value of X is used as a starting point
+    fn solve_cmd(&mut self, param: String) -> LineResult {
+        let split_index = match param.rfind(" for ") {
+            Some(v) => v,
+            None => return LineResult::Error(format!("solve usage: ':solve [expr] = [expr] for [var]'"))
+        };
+        let (equation, var) = param.split_at(split_index);
+        let var = var[" for ".len()..].trim().to_owned();
+        if !var.starts_with(char::is_uppercase) {
+            return LineResult::Error(format!("variable names should start with an uppercase character"))
+        }
+        let equals_index = match find_equals(equation) {
+            Ok(v) => v,
+            Err(e) => return LineResult::Error(e)
+        };
+        let (lhs, rhs) = equation.split_at(equals_index);
+        let line = format!("({}) - ({})", lhs, &rhs[1..]);
+        let code = match tokenize::tokenize(line, self.numeric, &self.functions).and_then(interpreter::rearrange) {
+            Ok(v) => v,
+            Err(e) => return LineResult::Error(e)
+        };
+        let guess = match self.get_val(&var) {
+            Some(v) if v.is_number() => v.to_float(),
+            _ => 0.0
+        };
+        let f = |x: f64| {
+            let mut local_vars = HashMap::new();
+            local_vars.insert(var.clone(), Value::Float(x));
+            let val = interpreter::interpret(code.clone(), self, &local_vars)?;
+            if !val.is_number() {
+                return Err(format!("equation has to be numeric, found {}", val.type_str()))
+            }
+            Ok(val.to_float())
+        };
+        match solver::find_root(&f, guess) {
+            Ok(v) => LineResult::Value(Value::Float(v)),
+            Err(e) => LineResult::Error(e)
+        }
+    }
+
     fn set_cmd(&mut self, param: String) -> LineResult {
         let split_index = match param.find(char::is_whitespace) {
             Some(v) => v,
             None => return LineResult::Error(format!("set usage: ':set [var] [expr]'"))
         };
         let (arg1, arg2) = param.split_at(split_index);
-        if arg1.starts_with(char::is_lowercase) {
+        if !is_var_name(arg1) {
             return LineResult::Error(format!("variable names should start with an uppercase character"))
         }
         let val = match self.eval_stmt(arg2.to_owned()) {
@@ -156,9 +1203,173 @@ impl Engine {
             },
             StatementResult::Value(v) => v
         };
+        self.touch_formula(arg1);
+        self.formulas.remove(arg1);
         self.set_val(arg1.to_owned(), val);
         LineResult::Success
     }
+
+    pub fn formulas(&self) -> &HashMap<String, Formula> {
+        &self.formulas
+    }
+
+    //':let Area = W*H', Area is computed again whenever W or H change
+    fn let_cmd(&mut self, param: String) -> LineResult {
+        let index = match param.find('=') {
+            Some(v) => v,
+            None => return LineResult::Error(format!("let usage: ':let [var] = [expr]'"))
+        };
+        let (name, text) = (param[..index].trim().to_owned(), param[index + 1..].trim().to_owned());
+        if !is_var_name(&name) {
+            return LineResult::Error(format!("variable names should start with an uppercase character"))
+        }
+        let code = match tokenize::tokenize(text.clone(), self.numeric, &self.functions).and_then(interpreter::rearrange) {
+            Ok(v) => v,
+            Err(e) => return LineResult::Error(e)
+        };
+        let mut deps = Vec::new();
+        function::variables(&code, &mut deps);
+        deps.sort();
+        deps.dedup();
+        let formula = Formula { text: text, deps: deps, mode: self.numeric };
+        if let Some(cycle) = self.find_cycle(&name, &formula.deps, &mut vec![name.clone()]) {
+            return LineResult::Error(format!("cycle: {}", cycle.join(" -> ")))
+        }
+        self.touch_formula(&name);
+        let old = self.formulas.insert(name.clone(), formula);
+        match self.eval_formula(&name) {
+            StatementResult::Value(v) => {
+                self.set_val(name, v);
+                LineResult::Success
+            },
+            StatementResult::Error(e) => {
+                match old {
+                    Some(f) => self.formulas.insert(name, f),
+                    None => self.formulas.remove(&name)
+                };
+                LineResult::Error(e)
+            }
+        }
+    }
+
+    //the path from deps back to name through the formulas, if there is one
+    fn find_cycle(&self, name: &str, deps: &[String], path: &mut Vec<String>) -> Option<Vec<String>> {
+        for dep in deps {
+            path.push(dep.clone());
+            if dep == name {
+                return Some(path.clone())
+            }
+            let inner = self.formulas.get(dep).map(|x| x.deps.clone()).unwrap_or(Vec::new());
+            if !path[..path.len() - 1].contains(dep) {
+                if let Some(cycle) = self.find_cycle(name, &inner, path) {
+                    return Some(cycle)
+                }
+            }
+            path.pop();
+        }
+        None
+    }
+
+    //computes the formulas, each after the formulas it uses
+    fn update_formulas(&mut self) {
+        if self.formulas.is_empty() {
+            return
+        }
+        let mut names = self.formulas.keys().cloned().collect::<Vec<String>>();
+        names.sort();
+        let mut order = Vec::new();
+        for name in names {
+            self.formula_order(&name, &mut order);
+        }
+        for name in order {
+            self.touch_var(&name);
+            match self.eval_formula(&name) {
+                StatementResult::Value(v) => self.vars.insert(name, v),
+                //shows 'variable not found' instead of an old value
+                StatementResult::Error(_) => self.vars.remove(&name)
+            };
+        }
+    }
+
+    fn eval_formula(&mut self, name: &str) -> StatementResult {
+        let (text, mode) = (self.formulas[name].text.clone(), self.formulas[name].mode);
+        self.eval_text(text, mode)
+    }
+
+    fn formula_order(&self, name: &String, order: &mut Vec<String>) {
+        if order.contains(name) {
+            return
+        }
+        if let Some(formula) = self.formulas.get(name) {
+            for dep in &formula.deps {
+                if dep != name {
+                    self.formula_order(dep, order);
+                }
+            }
+            order.push(name.clone());
+        }
+    }
+
+    //':deps Area' shows the variables Area uses, their variables and so on, and the formulas using Area
+    fn deps_cmd(&mut self, param: String) -> LineResult {
+        if !self.vars.contains_key(&param) && !self.formulas.contains_key(&param) {
+            return LineResult::Error(format!("var {} not found", param))
+        }
+        let mut lines = Vec::new();
+        self.deps_tree(&param, 0, &mut Vec::new(), &mut lines);
+        let mut users = self.formulas.iter().filter(|x| x.1.deps.contains(&param)).map(|x| x.0.clone()).collect::<Vec<String>>();
+        if !users.is_empty() {
+            users.sort();
+            lines.push(format!("used by: {}", users.join(", ")));
+        }
+        self.output.extend(lines);
+        LineResult::Success
+    }
+
+    fn deps_tree(&self, name: &String, depth: usize, path: &mut Vec<String>, lines: &mut Vec<String>) {
+        let indent = "    ".repeat(depth);
+        let value = self.vars.get(name).map_or(format!("(none)"), |v| v.literal());
+        match self.formulas.get(name) {
+            Some(formula) if !path.contains(name) => {
+                lines.push(format!("{}{} = {} = {}", indent, name, formula.text, value));
+                path.push(name.clone());
+                for dep in &formula.deps {
+                    self.deps_tree(dep, depth + 1, path, lines);
+                }
+                path.pop();
+            },
+            _ => lines.push(format!("{}{} = {}", indent, name, value))
+        }
+    }
+}
+
+//'X', '$0', or 'geo.R' with the namespace of an import
+fn is_var_name(name: &str) -> bool {
+    let last = name.rsplit('.').next().unwrap_or(name);
+    (last.starts_with(char::is_uppercase) || last.starts_with('$')) && !name.contains(char::is_whitespace)
+}
+
+//the index of the only '=' outside of brackets
+fn find_equals(equation: &str) -> Result<usize, String> {
+    let mut depth = 0;
+    let mut result = None;
+    for (index, ch) in equation.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '=' if depth == 0 => {
+                if result.is_some() {
+                    return Err(format!("more than one '=' in equation"))
+                }
+                result = Some(index);
+            },
+            _ => {}
+        }
+    }
+    match result {
+        Some(v) => Ok(v),
+        None => Err(format!("no '=' found"))
+    }
 }
 
 fn read_file(path: String) -> Result<String, io::Error> {
@@ -168,6 +1379,154 @@ fn read_file(path: String) -> Result<String, io::Error> {
     Ok(result)
 }
 
+//the lines of a script with the number of their first line, a line ending with '\\' continues in the next line
+fn continued_lines(script: &str) -> Vec<(usize, String)> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut first = 1;
+    for (index, line) in script.lines().enumerate() {
+        if current.is_empty() {
+            first = index + 1;
+        }
+        if line.trim_end().ends_with('\\') {
+            let line = line.trim_end();
+            current.push_str(&line[..line.len() - 1]);
+            current.push(' ');
+        }
+        else {
+            current.push_str(line);
+            result.push((first, mem::replace(&mut current, String::new())));
+        }
+    }
+    if !current.is_empty() {
+        result.push((first, current));
+    }
+    result
+}
+
+//the body after ':fn name X =', either indented deeper than the ':fn' (optionally closed by 'end')
+//or up to the 'end' that is not the end of an 'if' inside the body
+fn block_body(lines: &[(usize, String)], index: &mut usize, header_indentation: usize) -> Option<String> {
+    let indented = |x: &String| x.trim().is_empty() || indentation(x) > header_indentation;
+    let mut body = Vec::new();
+    if lines.get(*index).map_or(false, |x| !x.1.trim().is_empty() && indented(&x.1)) {
+        while *index < lines.len() && indented(&lines[*index].1) {
+            body.push(lines[*index].1.trim());
+            *index += 1;
+        }
+        if lines.get(*index).map_or(false, |x| x.1.trim() == "end") {
+            *index += 1;
+        }
+        return Some(body.join("\n"))
+    }
+    let mut depth = 0;
+    while *index < lines.len() {
+        let line = lines[*index].1.trim();
+        *index += 1;
+        if line == "end" {
+            if depth == 0 {
+                return Some(body.join("\n"))
+            }
+            depth -= 1;
+        }
+        else if line.starts_with("if ") {
+            depth += 1;
+        }
+        body.push(line);
+    }
+    None
+}
+
+//the old value of name, if the step has none yet
+fn touch<T: Clone>(changes: &mut Changes<T>, map: &HashMap<String, T>, name: &str) {
+    if !changes.contains_key(name) {
+        changes.insert(name.to_owned(), (map.get(name).cloned(), None));
+    }
+}
+
+//the new values of the names, without the ones that did not change
+fn finish<T: Clone + PartialEq>(changes: &mut Changes<T>, map: &HashMap<String, T>) {
+    for (name, change) in changes.iter_mut() {
+        change.1 = map.get(name).cloned();
+    }
+    changes.retain(|_, change| change.0 != change.1);
+}
+
+//'    X: 2 -> 5' or '    (none) -> f X = X^2' for every change of the step
+fn step_changes(step: &Step) -> Vec<String> {
+    let show_var = |x: &Option<Value>| x.as_ref().map_or(format!("(none)"), |v| v.literal());
+    let show_fn = |name: &String, x: &Option<Function>| x.as_ref().map_or(format!("(none)"), |f| format!("{} {} = {}", name, f.args().join(" "), f.text()));
+    let mut result = step.vars.iter().map(|(name, x)| format!("    {}: {} -> {}", name, show_var(&x.0), show_var(&x.1))).collect::<Vec<String>>();
+    result.extend(step.functions.iter().map(|(name, x)| format!("    {} -> {}", show_fn(name, &x.0), show_fn(name, &x.1))));
+    let show_formula = |name: &String, x: &Option<Formula>| x.as_ref().map_or(format!("(none)"), |f| format!("{} = {}", name, f.text));
+    result.extend(step.formulas.iter().map(|(name, x)| format!("    {} -> {}", show_formula(name, &x.0), show_formula(name, &x.1))));
+    result
+}
+
+//'~' and '~/...' in the home directory
+fn expand_home(path: &str) -> String {
+    match env::var("HOME") {
+        Ok(ref home) if path == "~" || path.starts_with("~/") => format!("{}{}", home, &path[1..]),
+        _ => path.to_owned()
+    }
+}
+
+fn same_file(path: &str, key: &str) -> bool {
+    match fs::canonicalize(path) {
+        Ok(v) => v.to_string_lossy() == key,
+        Err(_) => false
+    }
+}
+
+//the column of the first name in quotes in the error, or of the start of the line
+fn error_column(error: &str, line: &str) -> usize {
+    let start = indentation(line);
+    let quoted = error.split('\'').nth(1).filter(|x| !x.is_empty());
+    let index = match quoted.and_then(|x| line[start..].find(x)) {
+        Some(index) => start + index,
+        None => start
+    };
+    line[..index].chars().count() + 1
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn block_keyword(line: &str) -> Option<&'static str> {
+    [":for", ":while", ":if"].iter().cloned().find(|x| line.starts_with(x) && line[x.len()..].starts_with(char::is_whitespace))
+}
+
+//the lines up to the matching ':end', split at ':else'
+fn script_block(lines: &[(usize, String)], index: &mut usize) -> Option<(Vec<(usize, String)>, Option<Vec<(usize, String)>>)> {
+    let mut body = Vec::new();
+    let mut otherwise: Option<Vec<(usize, String)>> = None;
+    let mut depth = 0;
+    while *index < lines.len() {
+        let line = lines[*index].clone();
+        *index += 1;
+        let trimmed = line.1.trim();
+        if depth == 0 && trimmed == ":end" {
+            return Some((body, otherwise))
+        }
+        if depth == 0 && trimmed == ":else" && otherwise.is_none() {
+            otherwise = Some(Vec::new());
+            continue;
+        }
+        if trimmed == ":end" {
+            depth -= 1;
+        }
+        else if block_keyword(trimmed).is_some() {
+            depth += 1;
+        }
+        match otherwise {
+            Some(ref mut lines) => lines.push(line),
+            None => body.push(line)
+        }
+    }
+    None
+}
+
 pub enum StatementResult {
     Error(String),
     Value(Value)
@@ -187,3 +1546,190 @@ pub enum LineResult {
     Error(String),
     Success
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::{run, temp_dir, temp_file};
+
+    fn script(engine: &mut Engine, text: &str) -> String {
+        match engine.run_script("test.tc", text) {
+            LineResult::Error(e) => format!("error: {}", e),
+            _ => engine.take_output().join("\n")
+        }
+    }
+
+    #[test]
+    fn blocks() {
+        let mut engine = Engine::new();
+        let text = ":for I in 1..3\n    :print str(I^2)\n:end\n:for I in 3..1\n    :print \"never\"\n:end\n\
+            :var X 2\n:while abs(X^2 - 2) > 0.0000001\n    :var X X - (X^2 - 2)/(2*X)\n:end\n\
+            :if X > 1\n    :print \"found\"\n:else\n    :print \"not found\"\n:end\n";
+        assert_eq!(script(&mut engine, text), "1\n4\n9\nfound");
+        assert_eq!(script(&mut engine, ":for I in 9223372036854775806..9223372036854775807\n    :print str(I)\n:end\n"),
+            "9223372036854775806\n9223372036854775807");
+        assert_eq!(run(&mut engine, ":for I in 1..3"), "error: ':for' can only be used in scripts");
+    }
+
+    #[test]
+    fn block_errors() {
+        let mut engine = Engine::new();
+        assert_eq!(script(&mut engine, ":for I in 1..1000000000000\n:end\n"),
+            "error: error in test.tc:1:1: ':for I in 1..1000000000000' would run more than 1000000 iterations\n    1 | :for I in 1..1000000000000");
+        assert_eq!(script(&mut engine, ":for I in 1..2\n:var X 1\n"),
+            "error: error in test.tc:1:1: ':for I in 1..2' without ':end'\n    1 | :for I in 1..2");
+        assert_eq!(script(&mut engine, ":end\n"),
+            "error: error in test.tc:1:1: ':end' without ':for', ':while' or ':if'\n    1 | :end");
+    }
+
+    #[test]
+    fn assertions() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, ":assert 1 = 2"), "error: assertion failed: 1 = 2");
+        assert_eq!(run(&mut engine, ":assert_close sqrt(2) * sqrt(2), 2, 0.001"), "");
+        assert_eq!(run(&mut engine, ":assert_close 1"), "error: expected ':assert_close [term] [term] [tolerance]'");
+        //in test mode the script goes on after a failed assertion
+        engine.start_tests();
+        let text = ":assert 1\n:assert 1 = 2\n:assert_close 1 2\n:assert_close sqrt(2)^2 2\n";
+        assert_eq!(script(&mut engine, text), "");
+        let report = engine.take_tests().unwrap();
+        assert_eq!(report.passed, 2);
+        assert_eq!(report.failed, vec![
+            format!("error in test.tc:2:1: assertion failed: 1 = 2\n    2 | :assert 1 = 2"),
+            format!("error in test.tc:3:1: assertion failed: 1 is not within 1e-9 of 2\n    3 | :assert_close 1 2")
+        ]);
+    }
+
+    #[test]
+    fn script_errors() {
+        let lib = temp_file("errors-lib.tc", ":fn f X = g(X+1)\n:fn g X = X*Q\n\n  :var B f(1)\n");
+        let main = temp_file("errors-main.tc", &format!(":var A 1\n:load {}\n", lib));
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, &format!(":load {}", main)), format!("error: error in {}:4:3: variable 'Q' not found\n    \
+            4 | :var B f(1)\n    in g(X = 2)\n    in f(X = 1)\n    loaded at {}:2", lib, main));
+        //the column of the name in the error
+        assert_eq!(script(&mut engine, ":var C 1 + Z"), "error: error in test.tc:1:12: variable 'Z' not found\n    1 | :var C 1 + Z");
+        fs::remove_file(lib).unwrap();
+        fs::remove_file(main).unwrap();
+    }
+
+    #[test]
+    fn relative_paths() {
+        let dir = temp_dir("relative");
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("main.tc"), ":load lib.tc\n:import helper\n").unwrap();
+        fs::write(dir.join("sub").join("lib.tc"), ":var T 7\n").unwrap();
+        fs::write(dir.join("sub").join("helper.tc"), ":var H 8\n").unwrap();
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, &format!(":load {}", dir.join("sub").join("main.tc").display())), "");
+        assert_eq!(run(&mut engine, "T + helper.H"), "15 [int]");
+        assert_eq!(expand_home("~/a.tc"), format!("{}/a.tc", env::var("HOME").unwrap()));
+        assert_eq!(expand_home("a~/b.tc"), "a~/b.tc");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycles() {
+        let dir = temp_dir("cycles");
+        let (a, b) = (dir.join("a.tc"), dir.join("b.tc"));
+        fs::write(&a, ":load b.tc\n").unwrap();
+        fs::write(&b, ":var X 1\n:load a.tc\n").unwrap();
+        fs::write(dir.join("self.tc"), ":import self\n").unwrap();
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, &format!(":load {}", a.display())),
+            format!("error: error in {1}:2:1: include cycle: {0} -> {1} -> {0}\n    2 | :load a.tc\n    loaded at {0}:1",
+                a.display(), b.display()));
+        let own = dir.join("self.tc");
+        assert_eq!(run(&mut engine, &format!(":import \"{}\" as own", own.display())),
+            format!("error: error in {0}:1:1: include cycle: {0} -> {0}\n    1 | :import self", own.display()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, ":undo"), "error: nothing to undo");
+        for line in &[":var X 2", ":var X 3", ":fn f X = X*2", ":let Y = X + 1", ":rvar X"] {
+            run(&mut engine, line);
+        }
+        //one step undoes the formula and the variables it computed
+        assert_eq!(run(&mut engine, ":undo"), "undone: :rvar X");
+        assert_eq!(run(&mut engine, "X + Y"), "7 [int]");
+        assert_eq!(run(&mut engine, ":undo"), "undone: :let Y = X + 1");
+        assert_eq!(run(&mut engine, "Y"), "error: variable 'Y' not found");
+        assert_eq!(run(&mut engine, ":redo"), "redone: :let Y = X + 1");
+        assert_eq!(run(&mut engine, "Y"), "4 [int]");
+        for _ in 0..4 {
+            run(&mut engine, ":undo");
+        }
+        assert_eq!(run(&mut engine, ":undo"), "error: nothing to undo");
+        assert_eq!(run(&mut engine, ":redo"), "redone: :var X 2");
+        assert_eq!(run(&mut engine, "X"), "2 [int]");
+        //a new change drops the steps that were undone
+        run(&mut engine, ":var X 4");
+        assert_eq!(run(&mut engine, ":redo"), "error: nothing to redo");
+    }
+
+    #[test]
+    fn undo_history() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, ":history"), "no changes");
+        for line in &[":var X 2", ":var X 2", ":print \"unchanged\"", ":let Y = X*2", ":var X 3", ":fn f X = X", ":undo"] {
+            run(&mut engine, line);
+        }
+        assert_eq!(run(&mut engine, ":history"), "1: :var X 2\n    X: (none) -> 2\n\
+            2: :let Y = X*2\n    Y: (none) -> 4\n    (none) -> Y = X*2\n\
+            3: :var X 3\n    X: 2 -> 3\n    Y: 4 -> 6\n\
+            undone:\n:fn f X = X\n    (none) -> f X = X");
+        //the names of an imported script and a restored session
+        let module = temp_file("undo-module.tc", ":var R 2\n");
+        let session = temp_file("undo-session.tc", "");
+        run(&mut engine, &format!(":save {}", session));
+        run(&mut engine, &format!(":import \"{}\" as geo", module));
+        run(&mut engine, ":var Z 1");
+        run(&mut engine, &format!(":restore {}", session));
+        assert_eq!(run(&mut engine, "Z"), "error: variable 'Z' not found");
+        assert_eq!(run(&mut engine, ":undo"), format!("undone: :restore {}", session));
+        assert_eq!(run(&mut engine, "Z + geo.R"), "3 [int]");
+        run(&mut engine, ":undo");
+        run(&mut engine, ":undo");
+        assert_eq!(run(&mut engine, "geo.R"), "error: variable 'geo.R' not found");
+        assert_eq!(run(&mut engine, "X"), "3 [int]");
+        fs::remove_file(module).unwrap();
+        fs::remove_file(session).unwrap();
+    }
+
+    #[test]
+    fn formulas() {
+        let mut engine = Engine::new();
+        run(&mut engine, ":var W 2");
+        run(&mut engine, ":let A = W*3");
+        run(&mut engine, ":let C = A + W");
+        assert_eq!(run(&mut engine, ":deps C"), "C = A + W = 8\n    A = W*3 = 6\n        W = 2\n    W = 2");
+        assert_eq!(run(&mut engine, ":let W = C"), "error: cycle: W -> C -> A -> W");
+        assert_eq!(run(&mut engine, ":let D = A + Q"), "error: variable 'Q' not found");
+        //a formula without its variables has no value until they are back
+        run(&mut engine, ":rvar W");
+        assert_eq!(run(&mut engine, "C"), "error: variable 'C' not found");
+        run(&mut engine, ":var W 1");
+        assert_eq!(run(&mut engine, "C"), "4 [int]");
+        //':var' replaces the formula
+        run(&mut engine, ":var A 10");
+        run(&mut engine, ":var W 5");
+        assert_eq!(run(&mut engine, "A + C"), "25 [int]");
+    }
+
+    #[test]
+    fn imported_formulas() {
+        let module = temp_file("formulas.tc", ":var R 2\n:let D = R*2\n");
+        let mut engine = Engine::new();
+        run(&mut engine, &format!(":import \"{}\" as geo", module));
+        //the formula uses the variable of the module, not R of the session
+        run(&mut engine, ":var R 5");
+        assert_eq!(run(&mut engine, "D"), "error: variable 'D' not found");
+        assert_eq!(run(&mut engine, "geo.D"), "4 [int]");
+        run(&mut engine, ":var geo.R 10");
+        assert_eq!(run(&mut engine, "geo.D"), "20 [int]");
+        fs::remove_file(module).unwrap();
+    }
+}
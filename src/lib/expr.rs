@@ -0,0 +1,695 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, self};
+
+use tokenize::{self, Token, Let, If};
+use value::Value;
+use bigfloat::BigFloat;
+use engine::Engine;
+use stdlib;
+
+//user functions are inlined when differentiating, this stops recursive ones
+const MAX_INLINE_DEPTH: usize = 32;
+const ATOM_PRECEDENCE: usize = 100;
+
+//a function body as a tree, operators are calls of their stdlib functions (add, mul, ...)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Value(Value),
+    Variable(String),
+    Call(String, Vec<Expr>),
+    List(Vec<Expr>),
+    //let A = 1, B = A + 1 in A*B
+    Let(Vec<(String, Expr)>, Box<Expr>)
+}
+
+impl Expr {
+    pub fn from_code(code: &Vec<Token>, engine: &Engine) -> Result<Expr, String> {
+        let mut stack = Vec::new();
+        let mut list_starts = Vec::new();
+        for elem in code {
+            match *elem {
+                Token::Value(ref v) => stack.push(Expr::Value(v.clone())),
+                Token::ListOpen => list_starts.push(stack.len()),
+                Token::ListClose => {
+                    let start = match list_starts.pop() {
+                        Some(v) => v,
+                        None => return Err(format!("mismatched brackets"))
+                    };
+                    let items = stack.split_off(start);
+                    stack.push(Expr::List(items));
+                },
+                Token::Variable(ref name) => stack.push(Expr::Variable(name.clone())),
+                Token::Function(ref name) => {
+                    let count = match engine.functions().get(name) {
+                        Some(func) => func.arg_count(),
+                        None => match stdlib::arity(name) {
+                            Some(v) => v,
+                            None => return Err(format!("unknown function: {}", name))
+                        }
+                    };
+                    push_call(name.clone(), count, &mut stack)?;
+                },
+                Token::Call(ref name, count) => push_call(name.clone(), count, &mut stack)?,
+                Token::Apply(_) => return Err(format!("a call of a function literal cannot be simplified")),
+                Token::Let(ref l) => {
+                    let mut bindings = Vec::new();
+                    for &(ref name, ref code) in &l.bindings {
+                        bindings.push((name.clone(), Expr::from_code(code, engine)?));
+                    }
+                    stack.push(Expr::Let(bindings, Box::new(Expr::from_code(&l.body, engine)?)));
+                },
+                Token::If(ref i) => {
+                    let args = vec![Expr::from_code(&i.condition, engine)?, Expr::from_code(&i.then, engine)?, Expr::from_code(&i.otherwise, engine)?];
+                    stack.push(Expr::Call(String::from("if"), args));
+                },
+                _ => {
+                    if tokenize::OPS.contains(elem) {
+                        let count = if *elem == Token::UnarySub { 1 } else { 2 };
+                        push_call(tokenize::function_of_token(elem.clone()), count, &mut stack)?;
+                    }
+                }
+            }
+        }
+        if stack.len() != 1 {
+            return Err(format!("malformed expression"))
+        }
+        Ok(stack.pop().unwrap())
+    }
+
+    pub fn to_code(&self) -> Vec<Token> {
+        let mut result = Vec::new();
+        self.push_code(&mut result);
+        result
+    }
+
+    fn push_code(&self, code: &mut Vec<Token>) {
+        match *self {
+            Expr::Value(ref v) => code.push(Token::Value(v.clone())),
+            Expr::Variable(ref name) => code.push(Token::Variable(name.clone())),
+            Expr::Call(ref name, ref args) if name == "if" && args.len() == 3 => {
+                code.push(Token::If(If {
+                    condition: args[0].to_code(),
+                    then: args[1].to_code(),
+                    otherwise: args[2].to_code(),
+                    text: format!("{}", self)
+                }));
+            },
+            Expr::Call(ref name, ref args) => {
+                for arg in args {
+                    arg.push_code(code);
+                }
+                match tokenize::token_of_function(name) {
+                    Some(op) => code.push(op),
+                    None if name.starts_with(char::is_uppercase) || name.starts_with('$') => code.push(Token::Call(name.clone(), args.len())),
+                    None => code.push(Token::Function(name.clone()))
+                }
+            },
+            Expr::List(ref items) => {
+                code.push(Token::ListOpen);
+                for item in items {
+                    item.push_code(code);
+                }
+                code.push(Token::ListClose);
+            },
+            Expr::Let(ref bindings, ref body) => {
+                code.push(Token::Let(Let {
+                    bindings: bindings.iter().map(|&(ref name, ref value)| (name.clone(), value.to_code())).collect(),
+                    body: body.to_code(),
+                    text: format!("{}", self)
+                }));
+            }
+        }
+    }
+
+    //the body of a let expression with the values put in
+    pub fn expand(&self) -> Expr {
+        match *self {
+            Expr::Let(ref bindings, ref body) => {
+                //the last value first, it can use the names of the ones before
+                let mut result = body.expand();
+                for &(ref name, ref value) in bindings.iter().rev() {
+                    let mut vars = HashMap::new();
+                    vars.insert(name.clone(), value.expand());
+                    result = result.substitute(&vars);
+                }
+                result
+            },
+            Expr::Call(ref name, ref args) => Expr::Call(name.clone(), args.iter().map(|x| x.expand()).collect()),
+            Expr::List(ref items) => Expr::List(items.iter().map(|x| x.expand()).collect()),
+            _ => self.clone()
+        }
+    }
+
+    pub fn depends_on(&self, var: &str) -> bool {
+        match *self {
+            Expr::Value(_) => false,
+            Expr::Variable(ref name) => name == var,
+            Expr::Call(_, ref args) | Expr::List(ref args) => args.iter().any(|x| x.depends_on(var)),
+            Expr::Let(..) => self.expand().depends_on(var)
+        }
+    }
+
+    //replaces variables by expressions, e.g. the arguments of an inlined function
+    pub fn substitute(&self, vars: &HashMap<String, Expr>) -> Expr {
+        match *self {
+            Expr::Variable(ref name) => match vars.get(name) {
+                Some(v) => v.clone(),
+                None => self.clone()
+            },
+            Expr::Call(ref name, ref args) => {
+                Expr::Call(name.clone(), args.iter().map(|x| x.substitute(vars)).collect())
+            },
+            Expr::List(ref items) => Expr::List(items.iter().map(|x| x.substitute(vars)).collect()),
+            Expr::Let(..) => self.expand().substitute(vars),
+            _ => self.clone()
+        }
+    }
+
+    //derivative with respect to var
+    pub fn derive(&self, var: &str, engine: &Engine) -> Result<Expr, String> {
+        self.derive_depth(var, engine, 0)
+    }
+
+    fn derive_depth(&self, var: &str, engine: &Engine, depth: usize) -> Result<Expr, String> {
+        if !self.depends_on(var) {
+            return Ok(int(0))
+        }
+        let (name, args) = match *self {
+            Expr::Variable(_) => return Ok(int(1)),
+            Expr::Call(ref name, ref args) => (name.as_str(), args),
+            Expr::List(ref items) => {
+                let mut result = Vec::new();
+                for item in items {
+                    result.push(item.derive_depth(var, engine, depth)?);
+                }
+                return Ok(Expr::List(result))
+            },
+            Expr::Let(..) => return self.expand().derive_depth(var, engine, depth),
+            Expr::Value(_) => return Ok(int(0))
+        };
+        if engine.functions().contains_key(name) {
+            if depth >= MAX_INLINE_DEPTH {
+                return Err(format!("function nesting too deep in '{}'", name))
+            }
+            return inline(name, args, engine)?.derive_depth(var, engine, depth + 1)
+        }
+        let d = |index: usize| args[index].derive_depth(var, engine, depth);
+        let a = || args[0].clone();
+        let b = || args[1].clone();
+        Ok(match name {
+            "add" => call("add", vec![d(0)?, d(1)?]),
+            "sub" => call("sub", vec![d(0)?, d(1)?]),
+            "neg" => call("neg", vec![d(0)?]),
+            "mul" => call("add", vec![
+                call("mul", vec![d(0)?, b()]),
+                call("mul", vec![a(), d(1)?])
+            ]),
+            "div" => call("div", vec![
+                call("sub", vec![call("mul", vec![d(0)?, b()]), call("mul", vec![a(), d(1)?])]),
+                call("pow", vec![b(), int(2)])
+            ]),
+            "pow" => {
+                if !args[1].depends_on(var) {
+                    call("mul", vec![
+                        call("mul", vec![b(), call("pow", vec![a(), call("sub", vec![b(), int(1)])])]),
+                        d(0)?
+                    ])
+                }
+                else {
+                    //(a^b)' = a^b * (b' * ln(a) + b * a' / a)
+                    call("mul", vec![
+                        self.clone(),
+                        call("add", vec![
+                            call("mul", vec![d(1)?, call("ln", vec![a()])]),
+                            call("div", vec![call("mul", vec![b(), d(0)?]), a()])
+                        ])
+                    ])
+                }
+            },
+            "sqrt" => call("div", vec![d(0)?, call("mul", vec![int(2), self.clone()])]),
+            "exp" => call("mul", vec![self.clone(), d(0)?]),
+            "ln" => call("div", vec![d(0)?, a()]),
+            "sin" => call("mul", vec![call("cos", vec![a()]), d(0)?]),
+            "cos" => call("neg", vec![call("mul", vec![call("sin", vec![a()]), d(0)?])]),
+            "tan" => call("div", vec![d(0)?, call("pow", vec![call("cos", vec![a()]), int(2)])]),
+            "sinh" => call("mul", vec![call("cosh", vec![a()]), d(0)?]),
+            "cosh" => call("mul", vec![call("sinh", vec![a()]), d(0)?]),
+            "tanh" => call("div", vec![d(0)?, call("pow", vec![call("cosh", vec![a()]), int(2)])]),
+            "abs" => call("div", vec![call("mul", vec![a(), d(0)?]), self.clone()]),
+            "floor" | "ceil" | "eq" | "lt" | "gt" => int(0),
+            "pm" => d(0)?,
+            "if" => call("if", vec![a(), d(1)?, d(2)?]),
+            _ => return Err(format!("cannot differentiate '{}' symbolically", name))
+        })
+    }
+
+    //constant folding, removal of neutral elements and combining of like terms
+    pub fn simplify(&self, engine: &Engine) -> Expr {
+        match *self {
+            Expr::Call(ref name, ref args) => {
+                let args = args.iter().map(|x| x.simplify(engine)).collect::<Vec<Expr>>();
+                simplify_call(name, args, engine)
+            },
+            Expr::List(ref items) => Expr::List(items.iter().map(|x| x.simplify(engine)).collect()),
+            Expr::Let(ref bindings, ref body) => {
+                let bindings = bindings.iter().map(|&(ref name, ref value)| (name.clone(), value.simplify(engine))).collect();
+                Expr::Let(bindings, Box::new(body.simplify(engine)))
+            },
+            _ => self.clone()
+        }
+    }
+
+    //values, variables and function calls bind tighter than any operator
+    fn precedence(&self) -> usize {
+        match *self {
+            Expr::Value(ref v) if v.literal().starts_with('-') => Token::UnarySub.precedence(),
+            Expr::Call(ref name, _) => match tokenize::token_of_function(name) {
+                Some(op) => op.precedence(),
+                None => ATOM_PRECEDENCE
+            },
+            Expr::Let(..) => 0,
+            _ => ATOM_PRECEDENCE
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Expr::Value(ref v) => write!(fmt, "{}", v.literal()),
+            Expr::Variable(ref name) => write!(fmt, "{}", name),
+            Expr::Call(ref name, ref args) => {
+                let token = tokenize::token_of_function(name);
+                match (token, args.len()) {
+                    (Some(op), 2) => {
+                        //brackets only where the tokenizer would group differently without them
+                        let left = args[0].precedence() < op.precedence() ||
+                            (args[0].precedence() == op.precedence() && !op.left_assoc());
+                        let right = args[1].precedence() < op.precedence() ||
+                            (args[1].precedence() == op.precedence() && op.left_assoc());
+                        write!(fmt, "{}{}{}", Bracketed(&args[0], left), operator(name), Bracketed(&args[1], right))
+                    },
+                    (Some(op), 1) => {
+                        write!(fmt, "{}{}", operator(name), Bracketed(&args[0], args[0].precedence() < op.precedence()))
+                    },
+                    _ => {
+                        write!(fmt, "{}(", name)?;
+                        for (index, arg) in args.iter().enumerate() {
+                            if index > 0 {
+                                write!(fmt, ", ")?;
+                            }
+                            write!(fmt, "{}", arg)?;
+                        }
+                        write!(fmt, ")")
+                    }
+                }
+            },
+            Expr::List(ref items) => {
+                write!(fmt, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(fmt, ", ")?;
+                    }
+                    write!(fmt, "{}", item)?;
+                }
+                write!(fmt, "]")
+            },
+            Expr::Let(ref bindings, ref body) => {
+                write!(fmt, "let ")?;
+                for (index, &(ref name, ref value)) in bindings.iter().enumerate() {
+                    if index > 0 {
+                        write!(fmt, ", ")?;
+                    }
+                    //a let in a value would end at the wrong 'in'
+                    write!(fmt, "{} = {}", name, Bracketed(value, value.precedence() == 0))?;
+                }
+                write!(fmt, " in {}", body)
+            }
+        }
+    }
+}
+
+struct Bracketed<'a>(&'a Expr, bool);
+
+impl<'a> Display for Bracketed<'a> {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        if self.1 {
+            write!(fmt, "({})", self.0)
+        }
+        else {
+            write!(fmt, "{}", self.0)
+        }
+    }
+}
+
+fn operator(name: &str) -> &'static str {
+    match name {
+        "add" => " + ",
+        "sub" => " - ",
+        "mul" => "*",
+        "div" => "/",
+        "pow" => "^",
+        "neg" => "-",
+        "eq" => " = ",
+        "lt" => " < ",
+        "gt" => " > ",
+        "pm" => " ± ",
+        _ => ""
+    }
+}
+
+fn push_call(name: String, count: usize, stack: &mut Vec<Expr>) -> Result<(), String> {
+    if stack.len() < count {
+        return Err(format!("not enough arguments for function '{}'", name))
+    }
+    let index = stack.len() - count;
+    let args = stack.split_off(index);
+    stack.push(Expr::Call(name, args));
+    Ok(())
+}
+
+//the body of a user function with its arguments replaced by args
+pub fn inline(name: &str, args: &Vec<Expr>, engine: &Engine) -> Result<Expr, String> {
+    let function = engine.functions().get(name).unwrap();
+    let body = Expr::from_code(&function.code(), engine)?;
+    let mut vars = HashMap::new();
+    for (arg_name, arg) in function.args().iter().zip(args.iter()) {
+        vars.insert(arg_name.clone(), arg.clone());
+    }
+    Ok(body.substitute(&vars))
+}
+
+fn simplify_call(name: &str, args: Vec<Expr>, engine: &Engine) -> Expr {
+    let is_user_fn = engine.functions().contains_key(name);
+    if !is_user_fn && !args.is_empty() && args.iter().all(is_number) {
+        let mut stack = args.iter().map(|x| match *x {
+            Expr::Value(ref v) => v.clone(),
+            _ => unreachable!()
+        }).collect::<Vec<Value>>();
+        if let Ok(v) = stdlib::match_fn(name.to_owned(), &mut stack, engine) {
+            if stack.is_empty() {
+                return Expr::Value(v)
+            }
+        }
+    }
+    match name {
+        //'a' + 'b' is not 'b' + 'a'
+        "add" | "sub" if !args.iter().any(is_text) => combine_sum(&call(name, args), engine),
+        "mul" => combine_product(&call(name, args), engine),
+        "div" if is_one(&args[1]) => args[0].clone(),
+        "div" if is_zero(&args[0]) => int(0),
+        "pow" if is_one(&args[1]) => args[0].clone(),
+        "pow" if is_zero(&args[1]) => int(1),
+        "neg" => match args[0] {
+            Expr::Call(ref inner, ref inner_args) if inner == "neg" => inner_args[0].clone(),
+            Expr::Call(ref inner, _) if inner == "add" || inner == "sub" => combine_sum(&call(name, args.clone()), engine),
+            _ => call(name, args)
+        },
+        "if" => match args[0] {
+            Expr::Value(Value::Integer(0)) => args[2].clone(),
+            Expr::Value(Value::Integer(_)) => args[1].clone(),
+            _ => call(name, args)
+        },
+        _ => call(name, args)
+    }
+}
+
+//rewrites a sum as c1*term1 + c2*term2 + ... + constant, with every term only once
+fn combine_sum(expr: &Expr, engine: &Engine) -> Expr {
+    let mut terms = Vec::new();
+    let mut constant = Value::Integer(0);
+    collect_terms(expr, false, &mut terms, &mut constant, engine);
+    terms.push((constant, None));
+    terms.retain(|x| !is_zero(&Expr::Value(x.0.clone())));
+    //start with a positive term if there is one: 2 - X instead of -X + 2
+    if let Some(index) = terms.iter().position(|x| x.0.to_float() > 0.0) {
+        let first = terms.remove(index);
+        terms.insert(0, first);
+    }
+    let mut result: Option<Expr> = None;
+    for (coeff, term) in terms {
+        let negative = coeff.to_float() < 0.0;
+        if result.is_none() {
+            //a leading negative coefficient stays in the product: -2*X
+            result = Some(match term {
+                Some(t) => if is_one(&Expr::Value(coeff.clone())) {
+                    t
+                }
+                else if coeff.to_float() == -1.0 {
+                    call("neg", vec![t])
+                }
+                else {
+                    call("mul", vec![Expr::Value(coeff), t])
+                },
+                None => Expr::Value(coeff)
+            });
+            continue;
+        }
+        let magnitude = if negative {
+            arith("neg", vec![coeff.clone()], engine).unwrap_or(coeff)
+        }
+        else {
+            coeff
+        };
+        let summand = match term {
+            Some(t) => if is_one(&Expr::Value(magnitude.clone())) { t } else { call("mul", vec![Expr::Value(magnitude), t]) },
+            None => Expr::Value(magnitude)
+        };
+        result = Some(call(if negative { "sub" } else { "add" }, vec![result.unwrap(), summand]));
+    }
+    match result {
+        Some(v) => v,
+        None => int(0)
+    }
+}
+
+fn collect_terms(expr: &Expr, negate: bool, terms: &mut Vec<(Value, Option<Expr>)>, constant: &mut Value, engine: &Engine) {
+    match *expr {
+        Expr::Call(ref name, ref args) if name == "add" || name == "sub" => {
+            collect_terms(&args[0], negate, terms, constant, engine);
+            collect_terms(&args[1], negate != (name == "sub"), terms, constant, engine);
+        },
+        Expr::Call(ref name, ref args) if name == "neg" => {
+            collect_terms(&args[0], !negate, terms, constant, engine);
+        },
+        Expr::Value(ref v) if v.is_number() => {
+            let op = if negate { "sub" } else { "add" };
+            match arith(op, vec![constant.clone(), v.clone()], engine) {
+                Some(sum) => *constant = sum,
+                None => terms.push((Value::Integer(if negate { -1 } else { 1 }), Some(expr.clone())))
+            }
+        },
+        _ => {
+            let (mut coeff, term) = split_coeff(expr);
+            if negate {
+                coeff = arith("neg", vec![coeff.clone()], engine).unwrap_or(coeff);
+            }
+            for elem in terms.iter_mut() {
+                if elem.1.as_ref() == Some(&term) {
+                    if let Some(sum) = arith("add", vec![elem.0.clone(), coeff.clone()], engine) {
+                        elem.0 = sum;
+                        return;
+                    }
+                }
+            }
+            terms.push((coeff, Some(term)));
+        }
+    }
+}
+
+//2*X -> (2, X)
+fn split_coeff(expr: &Expr) -> (Value, Expr) {
+    if let Expr::Call(ref name, ref args) = *expr {
+        if name == "mul" {
+            match (&args[0], &args[1]) {
+                (&Expr::Value(ref c), term) if c.is_number() => return (c.clone(), term.clone()),
+                (term, &Expr::Value(ref c)) if c.is_number() => return (c.clone(), term.clone()),
+                _ => {}
+            }
+        }
+    }
+    (Value::Integer(1), expr.clone())
+}
+
+//rewrites a product as constant * base1^exp1 * base2^exp2 * ..., with every base only once
+fn combine_product(expr: &Expr, engine: &Engine) -> Expr {
+    let mut factors = Vec::new();
+    let mut constant = Value::Integer(1);
+    collect_factors(expr, &mut factors, &mut constant, engine);
+    if is_zero(&Expr::Value(constant.clone())) {
+        return Expr::Value(constant)
+    }
+    let mut result: Option<Expr> = None;
+    for (base, exponent) in factors {
+        let factor = if is_one(&Expr::Value(exponent.clone())) {
+            base
+        }
+        else if is_zero(&Expr::Value(exponent.clone())) {
+            continue;
+        }
+        else {
+            call("pow", vec![base, Expr::Value(exponent)])
+        };
+        result = Some(match result {
+            None => factor,
+            Some(r) => call("mul", vec![r, factor])
+        });
+    }
+    let result = match result {
+        Some(v) => v,
+        None => return Expr::Value(constant)
+    };
+    if is_one(&Expr::Value(constant.clone())) {
+        result
+    }
+    else if constant.to_float() == -1.0 {
+        call("neg", vec![result])
+    }
+    else {
+        call("mul", vec![Expr::Value(constant), result])
+    }
+}
+
+fn collect_factors(expr: &Expr, factors: &mut Vec<(Expr, Value)>, constant: &mut Value, engine: &Engine) {
+    match *expr {
+        Expr::Call(ref name, ref args) if name == "mul" => {
+            collect_factors(&args[0], factors, constant, engine);
+            collect_factors(&args[1], factors, constant, engine);
+            return;
+        },
+        Expr::Call(ref name, ref args) if name == "neg" => {
+            if let Some(v) = arith("neg", vec![constant.clone()], engine) {
+                *constant = v;
+                collect_factors(&args[0], factors, constant, engine);
+                return;
+            }
+        },
+        Expr::Value(ref v) if v.is_number() => {
+            if let Some(product) = arith("mul", vec![constant.clone(), v.clone()], engine) {
+                *constant = product;
+                return;
+            }
+        },
+        _ => {}
+    }
+    let (base, exponent) = split_exponent(expr);
+    for elem in factors.iter_mut() {
+        if elem.0 == base {
+            if let Some(sum) = arith("add", vec![elem.1.clone(), exponent.clone()], engine) {
+                elem.1 = sum;
+                return;
+            }
+        }
+    }
+    factors.push((base, exponent));
+}
+
+//X^2 -> (X, 2), (X^2)^3 -> (X, 6)
+fn split_exponent(expr: &Expr) -> (Expr, Value) {
+    if let Expr::Call(ref name, ref args) = *expr {
+        if name == "pow" {
+            if let Expr::Value(Value::Integer(outer)) = args[1] {
+                if let (base, Value::Integer(inner)) = split_exponent(&args[0]) {
+                    return (base, Value::Integer(inner * outer))
+                }
+            }
+            if let Expr::Value(ref v) = args[1] {
+                if v.is_number() {
+                    return (args[0].clone(), v.clone())
+                }
+            }
+        }
+    }
+    (expr.clone(), Value::Integer(1))
+}
+
+//evaluates a stdlib function on constants
+fn arith(name: &str, args: Vec<Value>, engine: &Engine) -> Option<Value> {
+    let mut stack = args;
+    stdlib::match_fn(name.to_owned(), &mut stack, engine).ok()
+}
+
+fn is_number(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Value(ref v) => v.is_number(),
+        _ => false
+    }
+}
+
+fn is_text(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Value(Value::Str(_)) => true,
+        _ => false
+    }
+}
+
+fn is_zero(expr: &Expr) -> bool {
+    is_number(expr) && match *expr {
+        Expr::Value(Value::BigFloat(ref v)) => v.is_zero(),
+        Expr::Value(ref v) => v.to_float() == 0.0,
+        _ => false
+    }
+}
+
+fn is_one(expr: &Expr) -> bool {
+    is_number(expr) && match *expr {
+        Expr::Value(Value::BigFloat(ref v)) => v == &BigFloat::from_int(1),
+        Expr::Value(ref v) => v.to_float() == 1.0,
+        _ => false
+    }
+}
+
+fn call(name: &str, args: Vec<Expr>) -> Expr {
+    Expr::Call(name.to_owned(), args)
+}
+
+fn int(v: i64) -> Expr {
+    Expr::Value(Value::Integer(v))
+}
+
+#[cfg(test)]
+mod tests {
+    use engine::Engine;
+    use testing::run;
+
+    #[test]
+    fn diff() {
+        let mut engine = Engine::new();
+        run(&mut engine, ":fn f X = X^3 + 2*X");
+        run(&mut engine, ":diff f X");
+        assert_eq!(engine.fn_body(&"f'".to_owned()).unwrap(), "3*X^2 + 2");
+        assert_eq!(run(&mut engine, "f'(2)"), "14 [int]");
+        run(&mut engine, ":fn g X = sin(X)*X");
+        run(&mut engine, ":diff g X");
+        assert_eq!(engine.fn_body(&"g'".to_owned()).unwrap(), "cos(X)*X + sin(X)");
+        run(&mut engine, ":fn h X = exp(2*X)");
+        run(&mut engine, ":diff h X dh");
+        assert_eq!(engine.fn_body(&"dh".to_owned()).unwrap(), "2*exp(2*X)");
+        assert!(run(&mut engine, ":diff h Y").starts_with("error"));
+    }
+
+    #[test]
+    fn simplify() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, ":simplify 2*X + X*3 - 1"), "5*X - 1");
+        assert_eq!(run(&mut engine, ":simplify X*X*2"), "2*X^2");
+        assert_eq!(run(&mut engine, ":simplify X - X"), "0");
+        assert_eq!(run(&mut engine, ":simplify 0*X + 1*Y"), "Y");
+        assert_eq!(run(&mut engine, ":simplify (X+1)^2 - (X+1)^2"), "0");
+        assert_eq!(run(&mut engine, ":simplify 2 + 3*4"), "14");
+        assert_eq!(run(&mut engine, ":simplify (X - Y) - (Z - 1)"), "X - Y - Z + 1");
+    }
+
+    //like terms are only folded for display, the functions run the body as it was typed in
+    #[test]
+    fn bodies_are_not_folded() {
+        let mut engine = Engine::new();
+        run(&mut engine, ":fn dup S = S + S");
+        assert_eq!(run(&mut engine, "dup(\"ab\")"), "\"abab\" [string]");
+        assert_eq!(engine.fn_body(&"dup".to_owned()).unwrap(), "2*S");
+        run(&mut engine, ":fn z X = X - X");
+        assert!(run(&mut engine, "z(\"a\")").starts_with("error: invalid types in function 'sub'"));
+        assert_eq!(run(&mut engine, "z(2026-10-18)"), "0s [duration]");
+        assert_eq!(run(&mut engine, "z(3)"), "0 [int]");
+    }
+}
@@ -0,0 +1,367 @@
+use value::{Value, NumericMode};
+use engine::Engine;
+use decimal::Decimal;
+use calculus;
+use solver;
+use stdlib;
+
+//money paid is negative, money received is positive, payments are due at the end of each period
+//with decimal arguments (or in decimal mode) and a whole number of periods, amounts are decimals
+
+//fv(rate, nper, pmt, pv): the value after nper periods
+pub fn fv(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let values = stdlib::pop_args("fv", 4, valstack)?;
+    if let Some(args) = decimals(engine, &values, Some(1)) {
+        let places = engine.decimal_places();
+        let (rate, nper, pmt, pv) = (args[0], args[1].to_i64(), args[2], args[3]);
+        return decimal_result((|| {
+            pv.mul(growth_decimal(rate, nper, places)?, places)?
+                .add(pmt.mul(annuity_decimal(rate, nper, places)?, places)?)
+                .map(Decimal::neg)
+        })())
+    }
+    let args = floats("fv", &values)?;
+    let (rate, nper, pmt, pv) = (args[0], args[1], args[2], args[3]);
+    Ok(Value::Float(-(pv * growth(rate, nper) + pmt * annuity(rate, nper))))
+}
+
+//pv(rate, nper, pmt, fv): the value today
+pub fn pv(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let values = stdlib::pop_args("pv", 4, valstack)?;
+    if let Some(args) = decimals(engine, &values, Some(1)) {
+        let places = engine.decimal_places();
+        let (rate, nper, pmt, fv) = (args[0], args[1].to_i64(), args[2], args[3]);
+        return decimal_result((|| {
+            fv.add(pmt.mul(annuity_decimal(rate, nper, places)?, places)?)?
+                .div(growth_decimal(rate, nper, places)?, places)
+                .map(Decimal::neg)
+        })())
+    }
+    let args = floats("pv", &values)?;
+    let (rate, nper, pmt, fv) = (args[0], args[1], args[2], args[3]);
+    Ok(Value::Float(-(fv + pmt * annuity(rate, nper)) / growth(rate, nper)))
+}
+
+//pmt(rate, nper, pv, fv): the payment per period
+pub fn pmt(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let values = stdlib::pop_args("pmt", 4, valstack)?;
+    let args = floats("pmt", &values)?;
+    if args[1] == 0.0 {
+        return Err(format!("pmt: nper has to be non-zero"))
+    }
+    if let Some(args) = decimals(engine, &values, Some(1)) {
+        let places = engine.decimal_places();
+        return decimal_result(payment_decimal(args[0], args[1].to_i64(), args[2], args[3], places))
+    }
+    let (rate, nper, pv, fv) = (args[0], args[1], args[2], args[3]);
+    Ok(Value::Float(payment(rate, nper, pv, fv)))
+}
+
+//nper(rate, pmt, pv, fv): the number of periods
+pub fn nper(valstack: &mut Vec<Value>, _engine: &Engine) -> Result<Value, String> {
+    let args = floats("nper", &stdlib::pop_args("nper", 4, valstack)?)?;
+    let (rate, pmt, pv, fv) = (args[0], args[1], args[2], args[3]);
+    let result = if rate == 0.0 {
+        -(pv + fv) / pmt
+    }
+    else {
+        ((pmt - fv * rate) / (pmt + pv * rate)).ln() / (1.0 + rate).ln()
+    };
+    if !result.is_finite() {
+        return Err(format!("nper: the payments never reach the future value"))
+    }
+    Ok(Value::Float(result))
+}
+
+//rate(nper, pmt, pv, fv): the interest rate per period
+pub fn rate(valstack: &mut Vec<Value>, _engine: &Engine) -> Result<Value, String> {
+    let args = floats("rate", &stdlib::pop_args("rate", 4, valstack)?)?;
+    let (nper, pmt, pv, fv) = (args[0], args[1], args[2], args[3]);
+    check_signs("rate", &[pv, pmt, fv])?;
+    let f = |rate: f64| Ok(pv * growth(rate, nper) + pmt * annuity(rate, nper) + fv);
+    checked_rate("rate", solver::find_root(&f, 0.1)?)
+}
+
+//npv(rate, cashflows): the first cashflow is today, the next one after a period, ...
+pub fn npv(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    stdlib::check_n(2, "npv", valstack)?;
+    let flow_list = valstack.pop().unwrap();
+    let rate = valstack.pop().unwrap();
+    let flows = number_list("npv", &flow_list)?;
+    if let Value::List(ref items) = flow_list {
+        let mut values = items.clone();
+        values.push(rate.clone());
+        if let Some(mut args) = decimals(engine, &values, None) {
+            let places = engine.decimal_places();
+            let rate = args.pop().unwrap();
+            return decimal_result(args.iter().enumerate().fold(Some(Decimal::from_int(0)), |sum, (period, flow)| {
+                sum?.add(flow.div(growth_decimal(rate, period as i64, places)?, places)?)
+            }))
+        }
+    }
+    let rate = calculus::expect_number("npv", &rate)?;
+    Ok(Value::Float(net_value(rate, &flows)))
+}
+
+//irr(cashflows): the rate at which npv is 0
+pub fn irr(valstack: &mut Vec<Value>, _engine: &Engine) -> Result<Value, String> {
+    stdlib::check_n(1, "irr", valstack)?;
+    let flows = number_list("irr", &valstack.pop().unwrap())?;
+    check_signs("irr", &flows)?;
+    let f = |rate: f64| Ok(net_value(rate, &flows));
+    checked_rate("irr", solver::find_root(&f, 0.1)?)
+}
+
+//xirr(cashflows, days): irr for cashflows on irregular days (counted from any start day)
+pub fn xirr(valstack: &mut Vec<Value>, _engine: &Engine) -> Result<Value, String> {
+    stdlib::check_n(2, "xirr", valstack)?;
+    let days = number_list("xirr", &valstack.pop().unwrap())?;
+    let flows = number_list("xirr", &valstack.pop().unwrap())?;
+    if days.len() != flows.len() || days.is_empty() {
+        return Err(format!("xirr: cashflows and days have different lengths"))
+    }
+    check_signs("xirr", &flows)?;
+    let f = |rate: f64| {
+        Ok(flows.iter().zip(days.iter()).map(|(flow, day)| {
+            flow / (1.0 + rate).powf((day - days[0]) / 365.0)
+        }).sum::<f64>())
+    };
+    checked_rate("xirr", solver::find_root(&f, 0.1)?)
+}
+
+//without money paid and money received no rate makes the value 0
+fn check_signs(fnname: &str, values: &[f64]) -> Result<(), String> {
+    if values.iter().any(|x| *x > 0.0) && values.iter().any(|x| *x < 0.0) {
+        Ok(())
+    } else {
+        Err(format!("{}: the cashflows need a positive and a negative value", fnname))
+    }
+}
+
+//at -100% or below the growth (1 + rate)^n is 0 or changes its sign
+fn checked_rate(fnname: &str, rate: f64) -> Result<Value, String> {
+    if rate > -1.0 {
+        Ok(Value::Float(rate))
+    } else {
+        Err(format!("{}: no rate above -100% found", fnname))
+    }
+}
+
+//amort(rate, nper, pv): one [period, payment, interest, principal, balance] row per period
+pub fn amort(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let values = stdlib::pop_args("amort", 3, valstack)?;
+    let args = floats("amort", &values)?;
+    let (rate, nper, pv) = (args[0], args[1], args[2]);
+    if nper < 1.0 || nper.fract() != 0.0 {
+        return Err(format!("amort: nper has to be a positive whole number"))
+    }
+    if let Some(args) = decimals(engine, &values, Some(1)) {
+        return amort_decimal(args[0], args[1].to_i64(), args[2], engine.decimal_places())
+    }
+    let payment = -payment(rate, nper, pv, 0.0);
+    let mut balance = pv;
+    let mut rows = Vec::new();
+    for period in 1..nper as i64 + 1 {
+        let interest = balance * rate;
+        let principal = payment - interest;
+        balance -= principal;
+        rows.push(Value::List(vec![
+            Value::Integer(period),
+            Value::Float(payment),
+            Value::Float(interest),
+            Value::Float(principal),
+            Value::Float(balance)
+        ]));
+    }
+    Ok(Value::List(rows))
+}
+
+//compound(principal, rate, periods per year, years): rate is per year
+pub fn compound(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let values = stdlib::pop_args("compound", 4, valstack)?;
+    let args = floats("compound", &values)?;
+    let (principal, rate, periods, years) = (args[0], args[1], args[2], args[3]);
+    if periods <= 0.0 {
+        return Err(format!("compound: periods per year have to be positive"))
+    }
+    if let Some(args) = decimals(engine, &values, None) {
+        let places = engine.decimal_places();
+        let count = args[2].mul(args[3], places);
+        if let Some(count) = count.filter(|x| x.floor() == *x) {
+            return decimal_result((|| {
+                let growth = growth_decimal(args[1].div(args[2], places)?, count.to_i64(), places)?;
+                args[0].mul(growth, places)
+            })())
+        }
+    }
+    Ok(Value::Float(principal * (1.0 + rate / periods).powf(periods * years)))
+}
+
+//pctchange(old, new): the change from old to new in percent
+pub fn pctchange(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let values = stdlib::pop_args("pctchange", 2, valstack)?;
+    let args = floats("pctchange", &values)?;
+    if args[0] == 0.0 {
+        return Err(format!("divide by zero"))
+    }
+    if let Some(args) = decimals(engine, &values, None) {
+        let places = engine.decimal_places();
+        return decimal_result(args[1].sub(args[0]).and_then(|x| x.mul(Decimal::from_int(100), places))
+            .and_then(|x| x.div(args[0], places)))
+    }
+    Ok(Value::Float((args[1] - args[0]) / args[0] * 100.0))
+}
+
+//addpct(x, p): x plus p percent
+pub fn addpct(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let values = stdlib::pop_args("addpct", 2, valstack)?;
+    if let Some(args) = decimals(engine, &values, None) {
+        let places = engine.decimal_places();
+        let share = args[0].mul(args[1], places).and_then(|x| x.div(Decimal::from_int(100), places));
+        return decimal_result(share.and_then(|x| args[0].add(x)))
+    }
+    let args = floats("addpct", &values)?;
+    Ok(Value::Float(args[0] * (1.0 + args[1] / 100.0)))
+}
+
+//subpct(x, p): x minus p percent
+pub fn subpct(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let values = stdlib::pop_args("subpct", 2, valstack)?;
+    if let Some(args) = decimals(engine, &values, None) {
+        let places = engine.decimal_places();
+        let share = args[0].mul(args[1], places).and_then(|x| x.div(Decimal::from_int(100), places));
+        return decimal_result(share.and_then(|x| args[0].sub(x)))
+    }
+    let args = floats("subpct", &values)?;
+    Ok(Value::Float(args[0] * (1.0 - args[1] / 100.0)))
+}
+
+//UTILS
+
+fn payment(rate: f64, nper: f64, pv: f64, fv: f64) -> f64 {
+    -(pv * growth(rate, nper) + fv) / annuity(rate, nper)
+}
+
+fn growth(rate: f64, nper: f64) -> f64 {
+    (1.0 + rate).powf(nper)
+}
+
+//the future value of paying 1 per period
+fn annuity(rate: f64, nper: f64) -> f64 {
+    if rate == 0.0 {
+        nper
+    }
+    else {
+        (growth(rate, nper) - 1.0) / rate
+    }
+}
+
+fn net_value(rate: f64, flows: &Vec<f64>) -> f64 {
+    flows.iter().enumerate().map(|(period, flow)| flow / growth(rate, period as f64)).sum()
+}
+
+fn payment_decimal(rate: Decimal, nper: i64, pv: Decimal, fv: Decimal, places: u32) -> Option<Decimal> {
+    pv.mul(growth_decimal(rate, nper, places)?, places)?.add(fv)?
+        .div(annuity_decimal(rate, nper, places)?, places)
+        .map(Decimal::neg)
+}
+
+fn growth_decimal(rate: Decimal, nper: i64, places: u32) -> Option<Decimal> {
+    Decimal::from_int(1).add(rate)?.powi(nper, places)
+}
+
+fn annuity_decimal(rate: Decimal, nper: i64, places: u32) -> Option<Decimal> {
+    if rate.is_zero() {
+        Some(Decimal::from_int(nper))
+    }
+    else {
+        growth_decimal(rate, nper, places)?.sub(Decimal::from_int(1))?.div(rate, places)
+    }
+}
+
+fn amort_decimal(rate: Decimal, nper: i64, pv: Decimal, places: u32) -> Result<Value, String> {
+    let overflow = || format!("decimal overflow");
+    let payment = payment_decimal(rate, nper, pv, Decimal::from_int(0), places).ok_or_else(overflow)?.neg();
+    let mut balance = pv;
+    let mut rows = Vec::new();
+    for period in 1..nper + 1 {
+        let interest = balance.mul(rate, places).ok_or_else(overflow)?;
+        let principal = payment.sub(interest).ok_or_else(overflow)?;
+        balance = balance.sub(principal).ok_or_else(overflow)?;
+        rows.push(Value::List(vec![
+            Value::Integer(period),
+            Value::Decimal(payment.normalize()),
+            Value::Decimal(interest.normalize()),
+            Value::Decimal(principal.normalize()),
+            Value::Decimal(balance.normalize())
+        ]));
+    }
+    Ok(Value::List(rows))
+}
+
+//amounts without the trailing zeros of intermediate results
+fn decimal_result(result: Option<Decimal>) -> Result<Value, String> {
+    match result {
+        Some(v) => Ok(Value::Decimal(v.normalize())),
+        None => Err(format!("decimal overflow"))
+    }
+}
+
+fn floats(fnname: &str, args: &Vec<Value>) -> Result<Vec<f64>, String> {
+    args.iter().map(|x| calculus::expect_number(fnname, x)).collect()
+}
+
+//the arguments as decimals, if one of them is a decimal (or in decimal mode), the others
+//are integers or decimals and the argument at whole is a whole number
+fn decimals(engine: &Engine, args: &Vec<Value>, whole: Option<usize>) -> Option<Vec<Decimal>> {
+    if engine.numeric() != NumericMode::Decimal && !args.iter().any(|x| x.is_decimal()) {
+        return None
+    }
+    let result = args.iter().map(|x| x.to_decimal()).collect::<Option<Vec<Decimal>>>()?;
+    match whole {
+        Some(index) if result[index].floor() != result[index] => None,
+        _ => Some(result)
+    }
+}
+
+fn number_list(fnname: &str, val: &Value) -> Result<Vec<f64>, String> {
+    match *val {
+        Value::List(ref items) => items.iter().map(|x| calculus::expect_number(fnname, x)).collect(),
+        _ => Err(format!("invalid types in function '{}': {}", fnname, val.type_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::run;
+
+    fn close(engine: &mut Engine, line: &str, expected: f64) -> bool {
+        let result = run(engine, line);
+        match result.trim_end_matches(" [float]").parse::<f64>() {
+            Ok(v) => (v - expected).abs() < 1e-9,
+            Err(_) => panic!("{} gave {}", line, result)
+        }
+    }
+
+    #[test]
+    fn rates() {
+        let mut engine = Engine::new();
+        assert!(close(&mut engine, "pmt(0.05/12, 360, 200000, 0)", -1073.6432460242797));
+        assert!(close(&mut engine, "rate(360, -1073.6432460242797, 200000, 0)", 0.05 / 12.0));
+        assert!(close(&mut engine, "irr([-100, 110])", 0.1));
+        assert!(close(&mut engine, "xirr([-100, 110], [0, 365])", 0.1));
+        assert!(close(&mut engine, "rate(10, 0, -100, 50)", 0.5f64.powf(0.1) - 1.0));
+    }
+
+    #[test]
+    fn no_rate() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "rate(10, 0, 0, 0)"), "error: rate: the cashflows need a positive and a negative value");
+        assert_eq!(run(&mut engine, "rate(10, -10, -100, 0)"), "error: rate: the cashflows need a positive and a negative value");
+        assert_eq!(run(&mut engine, "irr([1, 2])"), "error: irr: the cashflows need a positive and a negative value");
+        assert_eq!(run(&mut engine, "irr([0, 0])"), "error: irr: the cashflows need a positive and a negative value");
+        assert_eq!(run(&mut engine, "xirr([-1, -2], [0, 10])"), "error: xirr: the cashflows need a positive and a negative value");
+        assert_eq!(checked_rate("irr", -1.00000000000076), Err(format!("irr: no rate above -100% found")));
+    }
+}
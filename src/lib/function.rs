@@ -1,22 +1,43 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, self};
+
 use tokenize::Token;
 use tokenize;
+use value::{Value, NumericMode};
 use interpreter;
+use engine::Engine;
+use module;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Function {
     args: Vec<String>,
-    code: Vec<Token>
+    code: Vec<Token>,
+    //the body as it was typed in, for ':save'
+    text: String,
+    //the numeric mode the numbers of text were read in
+    mode: NumericMode
 }
 
 impl Function {
-    pub fn new(args: Vec<String>, line: String) -> Result<Function, String> {
-        let code = interpreter::rearrange(tokenize::tokenize(line))?;
+    pub fn new(args: Vec<String>, line: String, mode: NumericMode, functions: &HashMap<String, Function>) -> Result<Function, String> {
+        let code = interpreter::rearrange(tokenize::tokenize(line.clone(), mode, functions)?)?;
         Ok(Function {
             args: args,
-            code: code
+            code: code,
+            text: line.trim().to_owned(),
+            mode: mode
         })
     }
 
+    pub fn from_code(args: Vec<String>, code: Vec<Token>, text: String, mode: NumericMode) -> Function {
+        Function {
+            args: args,
+            code: code,
+            text: text,
+            mode: mode
+        }
+    }
+
     pub fn args(&self) -> &Vec<String> {
         &self.args
     }
@@ -28,4 +49,170 @@ impl Function {
     pub fn code(&self) -> Vec<Token> {
         self.code.clone()
     }
+
+    pub fn text(&self) -> &String {
+        &self.text
+    }
+
+    pub fn mode(&self) -> NumericMode {
+        self.mode
+    }
+}
+
+//a function value, e.g. 'X -> X^2', with the variables it uses from where it was created
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    args: Vec<String>,
+    code: Vec<Token>,
+    text: String,
+    captured: HashMap<String, Value>
+}
+
+impl Closure {
+    pub fn new(args: Vec<String>, code: Vec<Token>, text: String) -> Closure {
+        Closure {
+            args: args,
+            code: code,
+            text: text,
+            captured: HashMap::new()
+        }
+    }
+
+    pub fn args(&self) -> &Vec<String> {
+        &self.args
+    }
+
+    pub fn code(&self) -> Vec<Token> {
+        self.code.clone()
+    }
+
+    pub fn captured(&self) -> &HashMap<String, Value> {
+        &self.captured
+    }
+
+    //the variables of the body that are not arguments, with their current values
+    pub fn capture(&self, local_vars: &HashMap<String, Value>, engine: &Engine) -> Closure {
+        let mut result = self.clone();
+        let mut names = Vec::new();
+        variables(&self.code, &mut names);
+        for name in names {
+            if self.args.contains(&name) || result.captured.contains_key(&name) {
+                continue;
+            }
+            if let Some(v) = local_vars.get(&name).or_else(|| engine.get_val(&name)) {
+                result.captured.insert(name.clone(), v.clone());
+            }
+        }
+        result
+    }
+
+    //with the names of an imported module, see module::rename_code()
+    pub fn renamed(&self, names: &HashMap<String, String>, locals: &[String]) -> Closure {
+        let mut inner = locals.to_vec();
+        inner.extend(self.args.iter().cloned());
+        let captured = self.captured.iter().map(|(name, val)| {
+            let new_name = if inner.contains(name) { name.clone() } else { names.get(name).unwrap_or(name).clone() };
+            (new_name, module::rename_value(val, names, &[]))
+        }).collect();
+        Closure {
+            args: self.args.clone(),
+            code: module::rename_code(&self.code, names, &inner),
+            text: module::rename_text(&self.text, names, &inner),
+            captured: captured
+        }
+    }
+
+    //the captured variables and the arguments
+    pub fn locals(&self, args: Vec<Value>) -> HashMap<String, Value> {
+        let mut result = self.captured.clone();
+        for (name, val) in self.args.iter().zip(args.into_iter()) {
+            result.insert(name.clone(), val);
+        }
+        result
+    }
+}
+
+//the names of the variables in code, including those in let expressions
+pub fn variables(code: &[Token], names: &mut Vec<String>) {
+    for elem in code {
+        match *elem {
+            Token::Variable(ref name) | Token::Call(ref name, _) => names.push(name.clone()),
+            Token::Let(ref l) => {
+                for &(_, ref value) in &l.bindings {
+                    variables(value, names);
+                }
+                variables(&l.body, names);
+            },
+            Token::If(ref i) => {
+                variables(&i.condition, names);
+                variables(&i.then, names);
+                variables(&i.otherwise, names);
+            },
+            _ => {}
+        }
+    }
+}
+
+//the names of the functions passed as values, e.g. f in 'deriv(f, X)'
+pub fn function_refs(code: &[Token], names: &mut Vec<String>) {
+    for elem in code {
+        match *elem {
+            Token::Value(Value::FnRef(ref name)) => names.push(name.clone()),
+            Token::Value(Value::Function(ref closure)) => function_refs(&closure.code, names),
+            Token::Let(ref l) => {
+                for &(_, ref value) in &l.bindings {
+                    function_refs(value, names);
+                }
+                function_refs(&l.body, names);
+            },
+            Token::If(ref i) => {
+                function_refs(&i.condition, names);
+                function_refs(&i.then, names);
+                function_refs(&i.otherwise, names);
+            },
+            _ => {}
+        }
+    }
+}
+
+impl Display for Closure {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        if self.args.len() == 1 {
+            write!(fmt, "{} -> {}", self.args[0], self.text)
+        }
+        else {
+            write!(fmt, "({}) -> {}", self.args.join(", "), self.text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use engine::Engine;
+    use testing::run;
+
+    #[test]
+    fn literals() {
+        let mut engine = Engine::new();
+        run(&mut engine, ":var A 2");
+        assert_eq!(run(&mut engine, "(X -> X*A)(3)"), "6 [int]");
+        run(&mut engine, ":var A 5");
+        run(&mut engine, ":fn adder N = X -> X + N");
+        assert_eq!(run(&mut engine, "adder(10)(5)"), "15 [int]");
+        assert_eq!(run(&mut engine, "((X, Y) -> X + Y)(1, 2)"), "3 [int]");
+        assert_eq!(run(&mut engine, "2 * (X -> X + 1)(2) + 1"), "7 [int]");
+        assert_eq!(run(&mut engine, "map(X -> (Y -> Y + X)(1), [1, 2])"), "[2, 3] [list]");
+        run(&mut engine, ":fn g X = (Y -> Y*X)(2)");
+        assert_eq!(run(&mut engine, "g(5)"), "10 [int]");
+        assert_eq!(engine.fn_body(&"g".to_owned()).unwrap(), "(Y -> Y*X)(2)");
+    }
+
+    #[test]
+    fn errors() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "((X, Y) -> X + Y)(1)"), "error: function (X, Y) -> X + Y expects 2 arguments, found 1");
+        assert_eq!(run(&mut engine, "(3)(4)"), "error: expected a function, found integer");
+        assert_eq!(run(&mut engine, "3 4"), "error: unused arguments: 3, 4");
+        assert_eq!(run(&mut engine, "X -> X"), "X -> X [function]");
+    }
 }
@@ -6,57 +6,93 @@ use value::Value;
 use stdlib;
 use engine::Engine;
 
+//calls shown below an error, a deep recursion would show thousands
+const MAX_TRACE: usize = 20;
+
+//nested calls of user functions and function literals, an error instead of a stack overflow
+//(termcalc runs with a stack that is big enough for this many, also in a debug build)
+pub const MAX_CALL_DEPTH: usize = 1000;
+
 pub fn interpret(code: Vec<Token>, engine: &Engine, local_vars: &HashMap<String, Value>) -> Result<Value, String> {
     let mut valstack = Vec::new();
+    //stack heights at the start of the list literals that are not closed yet
+    let mut list_starts = Vec::new();
     for elem in code {
         match elem {
+            //a function literal gets the variables it uses
+            Token::Value(Value::Function(closure)) => valstack.push(Value::Function(closure.capture(local_vars, engine))),
             Token::Value(v) => valstack.push(v),
+            //the values are visible in the following values and in the body, and hide outer variables
+            Token::Let(l) => {
+                let mut scope = local_vars.clone();
+                for (name, code) in l.bindings {
+                    let val = interpret(code, engine, &scope)?;
+                    scope.insert(name, val);
+                }
+                valstack.push(interpret(l.body, engine, &scope)?);
+            },
+            Token::If(i) => {
+                let condition = interpret(i.condition, engine, local_vars)?;
+                let branch = match condition {
+                    Value::Integer(0) => i.otherwise,
+                    Value::Integer(_) => i.then,
+                    _ => return Err(format!("invalid types in function 'if': {} (the condition has to be an integer)", condition.type_str()))
+                };
+                valstack.push(interpret(branch, engine, local_vars)?);
+            },
+            Token::ListOpen => list_starts.push(valstack.len()),
+            Token::ListClose => {
+                let start = match list_starts.pop() {
+                    Some(v) => v,
+                    None => return Err(String::from("mismatched brackets"))
+                };
+                let items = valstack.split_off(start);
+                valstack.push(Value::List(items));
+            },
             Token::Variable(var) => {
                 valstack.push(match local_vars.get(&var) {
                     Some(a) => a.clone(),
                     None => {
                         match engine.get_val(&var) {
                             Some(b) => b.clone(),
-                            None => return Err(format!("variable '{}' not found", var))
+                            None => match engine.constant(&var) {
+                                Some(c) => c,
+                                None => return Err(format!("variable '{}' not found", var))
+                            }
                         }
                     }
                 })
             },
             Token::Function(name) => {
-                if !engine.functions().contains_key(&name) { //stdlib functions
-                    let val = exec_fn(name, &mut valstack)?;
-                    valstack.push(val);
+                let val = call_fn(&name, &mut valstack, engine)?;
+                valstack.push(val);
+            },
+            Token::Call(name, count) => {
+                let func = match local_vars.get(&name).or_else(|| engine.get_val(&name)) {
+                    Some(v) => v.clone(),
+                    None => return Err(format!("variable '{}' not found", name))
+                };
+                if valstack.len() < count {
+                    return Err(format!("not enough arguments for function '{}'", name))
                 }
-                else {
-                    let function = engine.functions().get(&name).unwrap();
-                    if valstack.len() < function.arg_count() {
-                        return Err(format!("not enough arguments for function '{}'", name))
-                    }
-                    //fetch arguments + argument names in HashMap
-                    let mut local_stack = Vec::new();
-                    for _ in 0..function.arg_count() {
-                        local_stack.push(valstack.pop().unwrap())
-                    }
-                    local_stack = local_stack.into_iter().rev().collect::<Vec<Value>>();
-                    let args_zip = local_stack.into_iter().zip(
-                        function.args().into_iter().map(|x| x.clone())
-                    );
-                    let mut local_vars = HashMap::new();
-                    for arg_pair in args_zip {
-                        local_vars.insert(arg_pair.1, arg_pair.0);
-                    }
-
-                    match interpret(function.code(), engine, &local_vars) {
-                        Ok(v) => valstack.push(v),
-                        Err(e) => {
-                            return Err(format!("error in function '{}': \n{}", name, e))
-                        }
-                    };
+                let index = valstack.len() - count;
+                let args = valstack.split_off(index);
+                let val = apply(&func, args, engine)?;
+                valstack.push(val);
+            },
+            Token::Apply(count) => {
+                if valstack.len() < count + 1 {
+                    return Err(format!("not enough arguments for the function call"))
                 }
+                let index = valstack.len() - count;
+                let args = valstack.split_off(index);
+                let func = valstack.pop().unwrap();
+                let val = apply(&func, args, engine)?;
+                valstack.push(val);
             },
             _ => {
                 if tokenize::OPS.contains(&elem) {
-                    let val = exec_fn(tokenize::function_of_token(elem), &mut valstack)?;
+                    let val = exec_fn(tokenize::function_of_token(elem), &mut valstack, engine)?;
                     valstack.push(val);
                 }
             }
@@ -70,13 +106,95 @@ pub fn interpret(code: Vec<Token>, engine: &Engine, local_vars: &HashMap<String,
             Err(format!("no arguments"))
         }
         else {
-            Err(format!("unused arguments: {:?}", valstack))
+            Err(format!("unused arguments: {}", valstack.iter().map(|x| x.literal()).collect::<Vec<String>>().join(", ")))
+        }
+    }
+}
+
+pub fn exec_fn(name: String, stack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    stdlib::match_fn(name, stack, engine)
+}
+
+//calls a user or stdlib function with the arguments on top of the stack
+pub fn call_fn(name: &String, valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    if !engine.functions().contains_key(name) { //stdlib functions
+        return exec_fn(name.clone(), valstack, engine)
+    }
+    let function = engine.functions().get(name).unwrap();
+    if valstack.len() < function.arg_count() {
+        return Err(format!("not enough arguments for function '{}'", name))
+    }
+    //fetch arguments + argument names in HashMap
+    let mut local_stack = Vec::new();
+    for _ in 0..function.arg_count() {
+        local_stack.push(valstack.pop().unwrap())
+    }
+    local_stack = local_stack.into_iter().rev().collect::<Vec<Value>>();
+    let args_zip = local_stack.into_iter().zip(
+        function.args().into_iter().map(|x| x.clone())
+    );
+    let mut local_vars = HashMap::new();
+    for arg_pair in args_zip {
+        local_vars.insert(arg_pair.1, arg_pair.0);
+    }
+
+    match nested(engine, || interpret(function.code(), engine, &local_vars)) {
+        Ok(v) => Ok(v),
+        Err(e) => {
+            let args = function.args().iter().map(|x| format!("{} = {}", x, local_vars[x].literal())).collect::<Vec<String>>();
+            Err(trace(e, format!("{}({})", name, args.join(", "))))
         }
     }
 }
 
-pub fn exec_fn(name: String, stack: &mut Vec<Value>) -> Result<Value, String> {
-    stdlib::match_fn(name, stack)
+//f() one call deeper
+fn nested<F>(engine: &Engine, f: F) -> Result<Value, String> where F: FnOnce() -> Result<Value, String> {
+    let depth = engine.call_depth().get();
+    if depth >= MAX_CALL_DEPTH {
+        return Err(format!("recursion too deep (more than {} nested calls)", MAX_CALL_DEPTH))
+    }
+    engine.call_depth().set(depth + 1);
+    let result = f();
+    engine.call_depth().set(depth);
+    result
+}
+
+//'in f(X = 1)' below the error, the innermost call first
+fn trace(error: String, call: String) -> String {
+    let depth = error.lines().filter(|x| x.starts_with("    in ")).count();
+    if error.ends_with("    ...") {
+        error
+    }
+    else if depth == MAX_TRACE {
+        format!("{}\n    ...", error)
+    }
+    else {
+        format!("{}\n    in {}", error, call)
+    }
+}
+
+//calls a function value (e.g. the 'f' in deriv(f, X)) with the given arguments
+pub fn apply(func: &Value, args: Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let name = match *func {
+        Value::FnRef(ref name) => name,
+        Value::Function(ref closure) => {
+            if closure.args().len() != args.len() {
+                return Err(format!("function {} expects {} arguments, found {}", closure, closure.args().len(), args.len()))
+            }
+            let call = format!("({})({})", closure, args.iter().map(|x| x.literal()).collect::<Vec<String>>().join(", "));
+            return match nested(engine, || interpret(closure.code(), engine, &closure.locals(args))) {
+                Ok(v) => Ok(v),
+                Err(e) => Err(trace(e, call))
+            }
+        },
+        _ => return Err(format!("expected a function, found {}", func.type_str()))
+    };
+    let mut stack = args;
+    let val = call_fn(name, &mut stack, engine)?;
+    if !stack.is_empty() {
+        return Err(format!("too many arguments for function '{}'", name))
+    }
+    Ok(val)
 }
 
 //shunting yard
@@ -85,7 +203,7 @@ pub fn rearrange(token: Vec<Token>) -> Result<Vec<Token>, String> {
     let mut stack = Vec::new();
     for elem in token {
         match elem {
-            Token::Value(_) => {
+            Token::Value(_) | Token::Let(_) | Token::If(_) => {
                 result.push(elem);
             },
             Token::Variable(_) => {
@@ -94,13 +212,35 @@ pub fn rearrange(token: Vec<Token>) -> Result<Vec<Token>, String> {
             Token::BrOpen => {
                 stack.push(elem);
             },
-            Token::Function(_) => {
+            Token::Function(_) | Token::Call(_, _) | Token::Apply(_) => {
+                stack.push(elem);
+            },
+            Token::Keyword(_) => {
+                return Err(String::from("malformed let expression, expected 'let A = ..., B = ... in ...'"))
+            },
+            Token::Arrow => {
+                return Err(String::from("malformed function literal, expected 'X -> ...' or '(X, Y) -> ...'"))
+            },
+            Token::ListOpen => {
+                //the interpreter needs to know where the list starts
+                result.push(elem.clone());
                 stack.push(elem);
             },
+            Token::ListClose => {
+                while !stack.is_empty() &&
+                        *stack.last().unwrap() != Token::ListOpen &&
+                        *stack.last().unwrap() != Token::BrOpen {
+                    result.push(stack.pop().unwrap());
+                }
+                match stack.pop() {
+                    Some(Token::ListOpen) => result.push(elem),
+                    _ => return Err(String::from("mismatched brackets"))
+                }
+            },
             Token::Seperator => {
                 let mut success = false;
                 while !stack.is_empty() {
-                    if *stack.last().unwrap() == Token::BrOpen {
+                    if *stack.last().unwrap() == Token::BrOpen || *stack.last().unwrap() == Token::ListOpen {
                         success = true;
                         break;
                     }
@@ -112,27 +252,29 @@ pub fn rearrange(token: Vec<Token>) -> Result<Vec<Token>, String> {
             }
             Token::BrClose => {
                 while !stack.is_empty() &&
-                        *stack.last().unwrap() != Token::BrOpen {
+                        *stack.last().unwrap() != Token::BrOpen &&
+                        *stack.last().unwrap() != Token::ListOpen {
                     result.push(stack.pop().unwrap());
                 }
                 match stack.pop() {
-                    Some(_) => {},
-                    None => return Err(String::from("mismatched brackets"))
+                    Some(Token::BrOpen) => {},
+                    _ => return Err(String::from("mismatched brackets"))
                 }
                 if !stack.is_empty() {
                     let func = stack.pop().unwrap();
                     match func {
-                        Token::Function(_) => result.push(func),
+                        Token::Function(_) | Token::Call(_, _) | Token::Apply(_) => result.push(func),
                         _ => {stack.push(func)}
                     }
                 }
             }
             _ => {
                 if tokenize::OPS.contains(&elem) {
-                    while !stack.is_empty() &&
+                    //a unary minus has no left operand, the operators before it still wait for their right one
+                    while elem != Token::UnarySub && !stack.is_empty() &&
                             tokenize::OPS.contains(stack.last().unwrap()) &&
-                            stack.last().unwrap().precedence() > elem.precedence() &&
-                            stack.last().unwrap().left_assoc() {
+                            (stack.last().unwrap().precedence() > elem.precedence() ||
+                                (stack.last().unwrap().precedence() == elem.precedence() && elem.left_assoc())) {
                         result.push(stack.pop().unwrap());
                     }
                     stack.push(elem);
@@ -142,7 +284,67 @@ pub fn rearrange(token: Vec<Token>) -> Result<Vec<Token>, String> {
 
     }
     while !stack.is_empty() {
-        result.push(stack.pop().unwrap());
+        let elem = stack.pop().unwrap();
+        if elem == Token::BrOpen || elem == Token::ListOpen {
+            return Err(String::from("mismatched brackets"))
+        }
+        result.push(elem);
     }
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::thread;
+
+    use engine::Engine;
+    use testing::{run, temp_file};
+
+    #[test]
+    fn precedence() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "2 - 3 - 4"), "-5 [int]");
+        assert_eq!(run(&mut engine, "8/2/2"), "2 [float]");
+        assert_eq!(run(&mut engine, "2^3^2"), "512 [int]");
+        assert_eq!(run(&mut engine, "(5) - (2)"), "3 [int]");
+        assert_eq!(run(&mut engine, "(3)-1"), "2 [int]");
+        assert_eq!(run(&mut engine, "--2"), "2 [int]");
+        assert_eq!(run(&mut engine, "2*-3"), "-6 [int]");
+        assert_eq!(run(&mut engine, "2^-1"), "0.5 [float]");
+        assert_eq!(run(&mut engine, "1 = -1"), "0 [int]");
+        assert_eq!(run(&mut engine, "1 > -1"), "1 [int]");
+    }
+
+    #[test]
+    fn lazy_if() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "if (1 > 0, 1, 1/0)"), "1 [int]");
+        assert_eq!(run(&mut engine, "if (1 < 0, 1, 1/0)"), "error: divide by zero");
+        assert!(run(&mut engine, "if (\"a\", 1, 2)").starts_with("error: invalid types in function 'if'"));
+    }
+
+    #[test]
+    fn deep_recursion() {
+        //more stack than a test thread has, like termcalc itself
+        let child = thread::Builder::new().stack_size(64 * 1024 * 1024).spawn(|| {
+            let mut engine = Engine::new();
+            run(&mut engine, ":fn sumall X = if (X = 0, 1, X+sumall(X-1))");
+            assert_eq!(run(&mut engine, "sumall(10)"), "56 [int]");
+            assert!(run(&mut engine, "sumall(5000)").starts_with("error: recursion too deep"));
+            run(&mut engine, ":fn loop X = loop(X)+1");
+            assert!(run(&mut engine, "loop(1)").starts_with("error: recursion too deep"));
+            assert_eq!(engine.call_depth().get(), 0);
+        }).unwrap();
+        child.join().unwrap();
+    }
+
+    #[test]
+    fn continued_lines() {
+        let script = temp_file("continued.tc", ":fn hyp A B = \\\n    sqrt(A^2 + B^2)\n:var C hyp(3, \\\n4)\n");
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, &format!(":load {}", script)), "");
+        assert_eq!(run(&mut engine, "C"), "5 [float]");
+        fs::remove_file(script).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+use std::f64::consts::PI;
+use std::fmt::{Display, Formatter, self};
+
+//all numbers between lo and hi, e.g. a measured value 9.81 ± 0.02
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    lo: f64,
+    hi: f64
+}
+
+impl Interval {
+    //x ± dx
+    pub fn new(x: f64, dx: f64) -> Interval {
+        Interval { lo: x - dx.abs(), hi: x + dx.abs() }
+    }
+
+    pub fn exact(x: f64) -> Interval {
+        Interval { lo: x, hi: x }
+    }
+
+    pub fn bounds(a: f64, b: f64) -> Interval {
+        Interval { lo: a.min(b), hi: a.max(b) }
+    }
+
+    pub fn lo(&self) -> f64 {
+        self.lo
+    }
+
+    pub fn hi(&self) -> f64 {
+        self.hi
+    }
+
+    pub fn mid(&self) -> f64 {
+        (self.lo + self.hi) / 2.0
+    }
+
+    pub fn radius(&self) -> f64 {
+        (self.hi - self.lo) / 2.0
+    }
+
+    fn contains(&self, x: f64) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    //offset + k * period for any k
+    fn contains_periodic(&self, offset: f64, period: f64) -> bool {
+        let k = ((self.lo - offset) / period).ceil();
+        offset + k * period <= self.hi
+    }
+
+    pub fn add(&self, other: &Interval) -> Interval {
+        Interval { lo: self.lo + other.lo, hi: self.hi + other.hi }
+    }
+
+    pub fn sub(&self, other: &Interval) -> Interval {
+        Interval { lo: self.lo - other.hi, hi: self.hi - other.lo }
+    }
+
+    pub fn mul(&self, other: &Interval) -> Interval {
+        let products = [self.lo * other.lo, self.lo * other.hi, self.hi * other.lo, self.hi * other.hi];
+        Interval {
+            lo: products.iter().cloned().fold(::std::f64::INFINITY, f64::min),
+            hi: products.iter().cloned().fold(::std::f64::NEG_INFINITY, f64::max)
+        }
+    }
+
+    pub fn div(&self, other: &Interval) -> Result<Interval, String> {
+        if other.contains(0.0) {
+            return Err(format!("divide by an interval containing zero"))
+        }
+        Ok(self.mul(&Interval::bounds(1.0 / other.lo, 1.0 / other.hi)))
+    }
+
+    pub fn neg(&self) -> Interval {
+        Interval { lo: -self.hi, hi: -self.lo }
+    }
+
+    pub fn abs(&self) -> Interval {
+        if self.contains(0.0) {
+            Interval { lo: 0.0, hi: self.hi.max(-self.lo) }
+        }
+        else {
+            Interval::bounds(self.lo.abs(), self.hi.abs())
+        }
+    }
+
+    //applies a function that does not decrease
+    fn monotonic(&self, f: fn(f64) -> f64) -> Interval {
+        Interval { lo: f(self.lo), hi: f(self.hi) }
+    }
+
+    pub fn powi(&self, exponent: i64) -> Result<Interval, String> {
+        if exponent < 0 {
+            return Interval::exact(1.0).div(&self.powi(-exponent)?)
+        }
+        let n = exponent as i32;
+        let (lo, hi) = (self.lo.powi(n), self.hi.powi(n));
+        if n % 2 == 1 || !self.contains(0.0) {
+            Ok(Interval::bounds(lo, hi))
+        }
+        else {
+            Ok(Interval { lo: 0.0, hi: lo.max(hi) })
+        }
+    }
+
+    //x^y = exp(y * ln(x))
+    pub fn pow(&self, exponent: &Interval) -> Result<Interval, String> {
+        if exponent.lo == exponent.hi && exponent.lo.fract() == 0.0 {
+            return self.powi(exponent.lo as i64)
+        }
+        Ok(exponent.mul(&self.apply("ln")?).monotonic(f64::exp))
+    }
+
+    pub fn round(&self, places: u32) -> Interval {
+        let factor = 10f64.powi(places as i32);
+        Interval { lo: (self.lo * factor).round() / factor, hi: (self.hi * factor).round() / factor }
+    }
+
+    //one of the functions sqrt, exp, ln, floor, ceil, sin, cos, tan, sinh, cosh and tanh
+    pub fn apply(&self, fnname: &str) -> Result<Interval, String> {
+        match fnname {
+            "sqrt" if self.lo < 0.0 => Err(format!("square root of a negative number")),
+            "sqrt" => Ok(self.monotonic(f64::sqrt)),
+            "ln" if self.lo <= 0.0 => Err(format!("logarithm of a non-positive number")),
+            "ln" => Ok(self.monotonic(f64::ln)),
+            "exp" => Ok(self.monotonic(f64::exp)),
+            "floor" => Ok(self.monotonic(f64::floor)),
+            "ceil" => Ok(self.monotonic(f64::ceil)),
+            "sinh" => Ok(self.monotonic(f64::sinh)),
+            "tanh" => Ok(self.monotonic(f64::tanh)),
+            "cosh" if self.contains(0.0) => Ok(Interval { lo: 1.0, hi: self.lo.cosh().max(self.hi.cosh()) }),
+            "cosh" => Ok(Interval::bounds(self.lo.cosh(), self.hi.cosh())),
+            "sin" => Ok(self.periodic(f64::sin, PI / 2.0, -PI / 2.0)),
+            "cos" => Ok(self.periodic(f64::cos, 0.0, PI)),
+            "tan" if self.contains_periodic(PI / 2.0, PI) => Err(format!("tan: the interval contains a pole")),
+            "tan" => Ok(self.monotonic(f64::tan)),
+            _ => Err(format!("function '{}' does not take intervals", fnname))
+        }
+    }
+
+    //sin or cos, with the maxima at max + 2k pi and the minima at min + 2k pi
+    fn periodic(&self, f: fn(f64) -> f64, max: f64, min: f64) -> Interval {
+        let ends = Interval::bounds(f(self.lo), f(self.hi));
+        Interval {
+            lo: if self.contains_periodic(min, 2.0 * PI) { -1.0 } else { ends.lo },
+            hi: if self.contains_periodic(max, 2.0 * PI) { 1.0 } else { ends.hi }
+        }
+    }
+
+    //Some(true) if every number of self is less than every number of other, None if they overlap
+    pub fn less(&self, other: &Interval) -> Option<bool> {
+        if self.hi < other.lo {
+            Some(true)
+        }
+        else if self.lo >= other.hi {
+            Some(false)
+        }
+        else {
+            None
+        }
+    }
+
+    //None if they overlap, but are not the same number
+    pub fn equal(&self, other: &Interval) -> Option<bool> {
+        if self.lo == self.hi && self == other {
+            Some(true)
+        }
+        else if self.hi < other.lo || other.hi < self.lo {
+            Some(false)
+        }
+        else {
+            None
+        }
+    }
+}
+
+//x ± dx with two significant digits of dx, x rounded to the same place
+impl Display for Interval {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        let radius = self.radius();
+        if radius == 0.0 || !radius.is_finite() {
+            return write!(fmt, "{} ± {}", self.mid(), radius)
+        }
+        let mut places = (1 - radius.log10().floor() as i32).max(0) as usize;
+        while places > 0 && format!("{:.*}", places, radius).ends_with('0') {
+            places -= 1;
+        }
+        write!(fmt, "{:.*} ± {:.*}", places, self.mid(), places, radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use engine::Engine;
+    use testing::run;
+
+    #[test]
+    fn uncertainties() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "9.81 ± 0.02"), "9.81 ± 0.02 [interval]");
+        assert_eq!(run(&mut engine, "(1 ± 0.1) + (2 ± 0.2)"), "3.0 ± 0.3 [interval]");
+        assert_eq!(run(&mut engine, "-1 ± 1"), "-1 ± 1 [interval]");
+        assert_eq!(run(&mut engine, "-(1 ± 0.5)"), "-1.0 ± 0.5 [interval]");
+        assert_eq!(run(&mut engine, "interval(1, 3)"), "2 ± 1 [interval]");
+        assert_eq!(run(&mut engine, "(1 ± 1) > 5"), "0 [int]");
+    }
+
+    #[test]
+    fn errors() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "1 ± -1"), "error: pm: the uncertainty must not be negative, found -1 [int]");
+        assert_eq!(run(&mut engine, "pm(1, 0 - 0.5)"), "error: pm: the uncertainty must not be negative, found -0.5 [float]");
+        assert_eq!(run(&mut engine, "(1 ± 1) > 1.5"), "error: undecidable comparison in function 'gt': the intervals overlap");
+        assert_eq!(run(&mut engine, "1/(0 ± 1)"), "error: divide by an interval containing zero");
+    }
+}
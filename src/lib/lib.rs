@@ -1,6 +1,22 @@
+#[cfg(test)]
+mod testing;
 pub mod engine;
 mod value;
+mod decimal;
+mod bigfloat;
+mod interval;
+mod datetime;
 pub mod tokenize;
 pub mod interpreter;
 mod stdlib;
 mod function;
+mod calculus;
+mod solver;
+mod expr;
+mod poly;
+mod finance;
+mod strings;
+mod lists;
+mod block;
+mod module;
+mod session;
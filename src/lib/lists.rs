@@ -0,0 +1,221 @@
+use std::cmp::Ordering;
+
+use value::Value;
+use engine::Engine;
+use interpreter;
+use stdlib;
+
+//range(), linspace(), sum() and product() stop there instead of filling the memory or running for hours
+pub const MAX_ITEMS: i64 = 1000000;
+
+//range(a, b, step): a, a + step, a + 2*step, ... up to b (including b)
+pub fn range(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let args = stdlib::pop_args("range", 3, valstack)?;
+    let (from, to, step) = (&args[0], &args[1], &args[2]);
+    //the direction of the steps, ascending if from + step > from
+    let next = arith("add", from.clone(), step.clone(), engine)?;
+    let ascending = is_true(&arith("gt", next.clone(), from.clone(), engine)?);
+    let descending = is_true(&arith("lt", next, from.clone(), engine)?);
+    let past_end = if ascending { "gt" } else { "lt" };
+    if !ascending && !descending {
+        return Err(format!("range: the step must not be zero"))
+    }
+    let mut result = Vec::new();
+    for i in 0..MAX_ITEMS + 1 {
+        //from + i*step, adding up the steps would add up the rounding errors
+        let item = arith("add", from.clone(), arith("mul", step.clone(), Value::Integer(i), engine)?, engine)?;
+        if is_true(&arith(past_end, item.clone(), to.clone(), engine)?) {
+            return Ok(Value::List(result))
+        }
+        result.push(item);
+    }
+    Err(format!("range: more than {} items", MAX_ITEMS))
+}
+
+//linspace(a, b, n): n numbers from a to b with equal distances
+pub fn linspace(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let args = stdlib::pop_args("linspace", 3, valstack)?;
+    let count = match args[2] {
+        Value::Integer(v) if v >= 1 && v <= MAX_ITEMS => v,
+        ref v => return Err(format!("linspace: the count has to be an integer between 1 and {}, found {}", MAX_ITEMS, v))
+    };
+    if count == 1 {
+        return Ok(Value::List(vec![args[0].clone()]))
+    }
+    let width = arith("sub", args[1].clone(), args[0].clone(), engine)?;
+    let mut result = Vec::new();
+    for i in 0..count {
+        let offset = arith("div", arith("mul", width.clone(), Value::Integer(i), engine)?, Value::Integer(count - 1), engine)?;
+        result.push(arith("add", args[0].clone(), offset, engine)?);
+    }
+    Ok(Value::List(result))
+}
+
+//map(f, xs): f applied to every item
+pub fn map(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let args = stdlib::pop_args("map", 2, valstack)?;
+    let items = expect_list("map", &args[1])?;
+    let result = items.into_iter().map(|x| interpreter::apply(&args[0], vec![x], engine)).collect::<Result<Vec<Value>, String>>()?;
+    Ok(Value::List(result))
+}
+
+//filter(pred, xs): the items for which pred is not 0
+pub fn filter(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let args = stdlib::pop_args("filter", 2, valstack)?;
+    let mut result = Vec::new();
+    for item in expect_list("filter", &args[1])? {
+        let keep = interpreter::apply(&args[0], vec![item.clone()], engine)?;
+        if !keep.is_number() {
+            return Err(format!("filter: the predicate returned {}, expected a number", keep.type_str()))
+        }
+        if is_true(&keep) {
+            result.push(item);
+        }
+    }
+    Ok(Value::List(result))
+}
+
+//reduce(f, xs, init): f(...f(f(init, x1), x2)..., xn)
+pub fn reduce(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let args = stdlib::pop_args("reduce", 3, valstack)?;
+    let mut acc = args[2].clone();
+    for item in expect_list("reduce", &args[1])? {
+        acc = interpreter::apply(&args[0], vec![acc, item], engine)?;
+    }
+    Ok(acc)
+}
+
+//zip(xs, ys): [[x1, y1], [x2, y2], ...], as long as the shorter list
+pub fn zip(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    let args = stdlib::pop_args("zip", 2, valstack)?;
+    let (xs, ys) = (expect_list("zip", &args[0])?, expect_list("zip", &args[1])?);
+    Ok(Value::List(xs.into_iter().zip(ys.into_iter()).map(|(x, y)| Value::List(vec![x, y])).collect()))
+}
+
+//sort(xs): ascending, items that are equal keep their order
+pub fn sort(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let args = stdlib::pop_args("sort", 1, valstack)?;
+    let mut items = expect_list("sort", &args[0])?;
+    let mut error = None;
+    items.sort_by(|a, b| match order(a, b, engine) {
+        Ok(v) => v,
+        Err(e) => {
+            error = Some(e);
+            Ordering::Equal
+        }
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(Value::List(items))
+    }
+}
+
+pub fn reverse(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    let args = stdlib::pop_args("reverse", 1, valstack)?;
+    let items = expect_list("reverse", &args[0])?;
+    Ok(Value::List(items.into_iter().rev().collect()))
+}
+
+//len(xs): the number of items of a list or characters of a string
+pub fn len(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    let args = stdlib::pop_args("len", 1, valstack)?;
+    match args[0] {
+        Value::List(ref items) => Ok(Value::Integer(items.len() as i64)),
+        Value::Str(ref s) => Ok(Value::Integer(s.chars().count() as i64)),
+        ref v => Err(format!("invalid types in function 'len': {}", v.type_str()))
+    }
+}
+
+//take(xs, n): the first n items
+pub fn take(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    let (items, count) = list_and_count("take", valstack)?;
+    Ok(Value::List(items.into_iter().take(count).collect()))
+}
+
+//drop(xs, n): all but the first n items
+pub fn drop(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    let (items, count) = list_and_count("drop", valstack)?;
+    Ok(Value::List(items.into_iter().skip(count).collect()))
+}
+
+//cumsum(xs): [x1, x1 + x2, x1 + x2 + x3, ...]
+pub fn cumsum(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    let args = stdlib::pop_args("cumsum", 1, valstack)?;
+    let mut result: Vec<Value> = Vec::new();
+    for item in expect_list("cumsum", &args[0])? {
+        let sum = match result.last() {
+            Some(last) => arith("add", last.clone(), item, engine)?,
+            None => item
+        };
+        result.push(sum);
+    }
+    Ok(Value::List(result))
+}
+
+//UTILS
+
+fn expect_list(fnname: &str, val: &Value) -> Result<Vec<Value>, String> {
+    match *val {
+        Value::List(ref items) => Ok(items.clone()),
+        _ => Err(format!("invalid types in function '{}': {}", fnname, val.type_str()))
+    }
+}
+
+fn list_and_count(fnname: &str, valstack: &mut Vec<Value>) -> Result<(Vec<Value>, usize), String> {
+    let args = stdlib::pop_args(fnname, 2, valstack)?;
+    match args[1] {
+        Value::Integer(v) if v >= 0 => Ok((expect_list(fnname, &args[0])?, v as usize)),
+        ref v => Err(format!("invalid types in function '{}': {} {} (expected a list and a count)", fnname, args[0].type_str(), v.type_str()))
+    }
+}
+
+fn arith(name: &str, a: Value, b: Value, engine: &Engine) -> Result<Value, String> {
+    let mut stack = vec![a, b];
+    stdlib::match_fn(name.to_owned(), &mut stack, engine)
+}
+
+fn order(a: &Value, b: &Value, engine: &Engine) -> Result<Ordering, String> {
+    if is_true(&arith("lt", a.clone(), b.clone(), engine)?) {
+        Ok(Ordering::Less)
+    }
+    else if is_true(&arith("gt", a.clone(), b.clone(), engine)?) {
+        Ok(Ordering::Greater)
+    }
+    else {
+        Ok(Ordering::Equal)
+    }
+}
+
+fn is_true(val: &Value) -> bool {
+    val.is_number() && val.to_float() != 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use engine::Engine;
+    use testing::run;
+
+    #[test]
+    fn lists() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "range(1, 2, 0.5)"), "[1.0, 1.5, 2.0] [list]");
+        assert_eq!(run(&mut engine, "range(3, 1, -1)"), "[3, 2, 1] [list]");
+        assert_eq!(run(&mut engine, "range(2026-10-18, 2026-10-20, 1 day)"), "[2026-10-18, 2026-10-19, 2026-10-20] [list]");
+        assert_eq!(run(&mut engine, "linspace(0, 1, 3)"), "[0.0, 0.5, 1.0] [list]");
+        assert_eq!(run(&mut engine, "filter(X -> X > 1, map(X -> X + 1, [0, 1, 2]))"), "[2, 3] [list]");
+        assert_eq!(run(&mut engine, "reduce((A, B) -> A + B, [1, 2, 3], 0)"), "6 [int]");
+        assert_eq!(run(&mut engine, "sort([3, 1, 2])"), "[1, 2, 3] [list]");
+        assert_eq!(run(&mut engine, "drop([1, 2, 3], 2)"), "[3] [list]");
+        assert_eq!(run(&mut engine, "cumsum([1, 2, 3])"), "[1, 3, 6] [list]");
+    }
+
+    #[test]
+    fn errors() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "range(1, 2, 0)"), "error: range: the step must not be zero");
+        assert_eq!(run(&mut engine, "range(1, 10000000, 1)"), "error: range: more than 1000000 items");
+        assert_eq!(run(&mut engine, "linspace(0, 1, 0)"), "error: linspace: the count has to be an integer between 1 and 1000000, found 0 [int]");
+        assert_eq!(run(&mut engine, "map(X -> X, 3)"), "error: invalid types in function 'map': integer");
+        assert_eq!(run(&mut engine, "take([1], -1)"), "error: invalid types in function 'take': list integer (expected a list and a count)");
+    }
+}
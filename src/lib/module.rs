@@ -0,0 +1,133 @@
+//':import' runs a script with its own variables and functions, then adds them with the
+//namespace in front ('area' -> 'geo.area'), so the names in the imported code are renamed too
+
+use std::collections::HashMap;
+
+use tokenize::{Token, Let, If};
+use value::Value;
+
+//the name with the namespace, e.g. geo.area or geo.R
+pub fn qualified(namespace: &str, name: &str) -> String {
+    format!("{}.{}", namespace, name)
+}
+
+//names are the module's names and their new names, locals are the arguments
+//and let names that hide the variables of the module
+pub fn rename_code(code: &[Token], names: &HashMap<String, String>, locals: &[String]) -> Vec<Token> {
+    code.iter().map(|elem| match *elem {
+        Token::Variable(ref name) => Token::Variable(rename(name, names, locals)),
+        Token::Call(ref name, count) => Token::Call(rename(name, names, locals), count),
+        Token::Function(ref name) => Token::Function(rename(name, names, locals)),
+        Token::Value(ref val) => Token::Value(rename_value(val, names, locals)),
+        Token::Let(ref l) => {
+            let mut inner = locals.to_vec();
+            let mut bindings = Vec::new();
+            for &(ref name, ref value) in &l.bindings {
+                bindings.push((name.clone(), rename_code(value, names, &inner)));
+                inner.push(name.clone());
+            }
+            Token::Let(Let {
+                bindings: bindings,
+                body: rename_code(&l.body, names, &inner),
+                text: rename_text(&l.text, names, &inner)
+            })
+        },
+        Token::If(ref i) => Token::If(If {
+            condition: rename_code(&i.condition, names, locals),
+            then: rename_code(&i.then, names, locals),
+            otherwise: rename_code(&i.otherwise, names, locals),
+            text: rename_text(&i.text, names, locals)
+        }),
+        ref other => other.clone()
+    }).collect()
+}
+
+pub fn rename_value(val: &Value, names: &HashMap<String, String>, locals: &[String]) -> Value {
+    match *val {
+        Value::FnRef(ref name) => Value::FnRef(rename(name, names, locals)),
+        Value::Function(ref closure) => Value::Function(closure.renamed(names, locals)),
+        Value::List(ref items) => Value::List(items.iter().map(|x| rename_value(x, names, locals)).collect()),
+        ref other => other.clone()
+    }
+}
+
+//the names in the text of a let, if or function literal, outside of string literals
+pub fn rename_text(text: &str, names: &HashMap<String, String>, locals: &[String]) -> String {
+    let mut result = String::new();
+    let mut word = String::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for ch in text.chars() {
+        if in_string {
+            result.push(ch);
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        }
+        else if ch.is_alphanumeric() || ch == '_' || ch == '$' || ch == '.' || ch == '\'' {
+            word.push(ch);
+        }
+        else {
+            result.push_str(&rename(&word, names, locals));
+            word.clear();
+            result.push(ch);
+            in_string = ch == '"';
+        }
+    }
+    result.push_str(&rename(&word, names, locals));
+    result
+}
+
+fn rename(name: &str, names: &HashMap<String, String>, locals: &[String]) -> String {
+    match names.get(name) {
+        Some(new) if !locals.iter().any(|x| x == name) => new.clone(),
+        _ => name.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use engine::Engine;
+    use testing::{run, temp_dir};
+
+    #[test]
+    fn imports() {
+        let dir = temp_dir("imports");
+        fs::write(dir.join("geometry.tc"), ":var R 2\n:fn square X = X^2\n:fn area X = 3*square(X)\n").unwrap();
+        fs::write(dir.join("other.tc"), ":var R 3\n").unwrap();
+        let mut engine = Engine::new();
+        run(&mut engine, &format!(":path add {}", dir.display()));
+        assert_eq!(run(&mut engine, ":import geometry"), "");
+        assert_eq!(run(&mut engine, "geometry.area(geometry.R)"), "12 [int]");
+        assert_eq!(run(&mut engine, "R"), "error: variable 'R' not found");
+        assert_eq!(run(&mut engine, "area(1)"), "error: unknown function: area");
+        //imported once
+        run(&mut engine, ":var geometry.R 5");
+        assert_eq!(run(&mut engine, ":import geometry"), "");
+        assert_eq!(run(&mut engine, "geometry.R"), "5 [int]");
+        assert_eq!(run(&mut engine, &format!(":import \"{}\" as geo", dir.join("other.tc").display())), "");
+        assert_eq!(run(&mut engine, "geo.R"), "3 [int]");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn import_errors() {
+        let dir = temp_dir("import-errors");
+        fs::write(dir.join("geometry.tc"), ":var R 2\n").unwrap();
+        fs::write(dir.join("other.tc"), ":var R 3\n").unwrap();
+        let mut engine = Engine::new();
+        run(&mut engine, &format!(":path add {}", dir.display()));
+        run(&mut engine, ":import geometry");
+        let other = dir.join("other.tc");
+        assert_eq!(run(&mut engine, &format!(":import \"{}\" as geometry", other.display())),
+            format!("error: the namespace 'geometry' is used by {} already", dir.join("geometry.tc").display()));
+        assert_eq!(run(&mut engine, ":import other as Geo"), "error: invalid namespace 'Geo', it has to start with a lowercase character");
+        assert_eq!(run(&mut engine, ":import missing"), format!("error: module 'missing' not found, the search path is [{}]", dir.display()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
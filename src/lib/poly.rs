@@ -0,0 +1,346 @@
+use value::Value;
+use engine::Engine;
+use expr::{self, Expr};
+use stdlib;
+
+//polynomials are lists of coefficients, highest degree first: [1, 0, -2] is X^2 - 2
+//internally the order is reversed, so that the index is the degree
+
+const ROOTS_MAX_ITERATIONS: usize = 1000;
+const ROOTS_TOLERANCE: f64 = 1e-14;
+
+//polyval(p, x)
+pub fn polyval(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    stdlib::check_n(2, "polyval", valstack)?;
+    let x = valstack.pop().unwrap();
+    let p = expect_poly("polyval", &valstack.pop().unwrap())?;
+    if !x.is_number() {
+        return Err(format!("invalid types in function 'polyval': list {}", x.type_str()))
+    }
+    //horner
+    let mut result = Value::Integer(0);
+    for coeff in p.into_iter().rev() {
+        result = arith("mul", result, x.clone(), engine)?;
+        result = arith("add", result, coeff, engine)?;
+    }
+    Ok(result)
+}
+
+//polymul(p, q)
+pub fn polymul(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    stdlib::check_n(2, "polymul", valstack)?;
+    let q = expect_poly("polymul", &valstack.pop().unwrap())?;
+    let p = expect_poly("polymul", &valstack.pop().unwrap())?;
+    Ok(to_list(mul(&p, &q, engine)?))
+}
+
+//polydiv(p, q): [quotient, remainder]
+pub fn polydiv(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    stdlib::check_n(2, "polydiv", valstack)?;
+    let q = trim(expect_poly("polydiv", &valstack.pop().unwrap())?);
+    let mut rem = trim(expect_poly("polydiv", &valstack.pop().unwrap())?);
+    let lead = q.last().unwrap().clone();
+    if lead.to_float() == 0.0 {
+        return Err(format!("divide by zero"))
+    }
+    if rem.len() < q.len() {
+        return Ok(Value::List(vec![Value::List(vec![Value::Integer(0)]), to_list(rem)]))
+    }
+    let mut quot = vec![Value::Integer(0); rem.len() - q.len() + 1];
+    for shift in (0..quot.len()).rev() {
+        let factor = arith("div", rem[shift + q.len() - 1].clone(), lead.clone(), engine)?;
+        for (index, coeff) in q.iter().enumerate() {
+            let product = arith("mul", factor.clone(), coeff.clone(), engine)?;
+            rem[shift + index] = arith("sub", rem[shift + index].clone(), product, engine)?;
+        }
+        quot[shift] = factor;
+    }
+    rem.truncate(q.len() - 1);
+    Ok(Value::List(vec![to_list(quot), to_list(rem)]))
+}
+
+//polyroots(p): all complex roots as [re, im] pairs (Durand-Kerner)
+pub fn polyroots(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    stdlib::check_n(1, "polyroots", valstack)?;
+    let p = trim(expect_poly("polyroots", &valstack.pop().unwrap())?);
+    let degree = p.len() - 1;
+    let lead = p[degree].to_float();
+    if lead == 0.0 {
+        return Ok(Value::List(Vec::new()))
+    }
+    let monic = p.iter().map(|x| Complex(x.to_float() / lead, 0.0)).collect::<Vec<Complex>>();
+    let eval = |x: Complex| monic.iter().rev().fold(Complex(0.0, 0.0), |acc, c| acc.mul(x).add(*c));
+    let seed = Complex(0.4, 0.9);
+    let mut roots = Vec::new();
+    let mut power = Complex(1.0, 0.0);
+    for _ in 0..degree {
+        roots.push(power);
+        power = power.mul(seed);
+    }
+    for _ in 0..ROOTS_MAX_ITERATIONS {
+        let mut change: f64 = 0.0;
+        for i in 0..degree {
+            let mut denom = Complex(1.0, 0.0);
+            for j in 0..degree {
+                if i != j {
+                    denom = denom.mul(roots[i].sub(roots[j]));
+                }
+            }
+            let step = eval(roots[i]).div(denom);
+            roots[i] = roots[i].sub(step);
+            change = change.max(step.abs() / roots[i].abs().max(1.0));
+        }
+        if change < ROOTS_TOLERANCE {
+            break;
+        }
+    }
+    let result = roots.into_iter().map(|x| {
+        //imaginary parts that are only rounding errors
+        let im = if x.1.abs() < 1e-10 * x.abs().max(1.0) { 0.0 } else { x.1 };
+        Value::List(vec![Value::Float(x.0), Value::Float(im)])
+    }).collect();
+    Ok(Value::List(result))
+}
+
+//polyfit(xs, ys, degree): least squares fit (householder qr)
+pub fn polyfit(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    stdlib::check_n(3, "polyfit", valstack)?;
+    let degree = valstack.pop().unwrap();
+    let ys = expect_poly("polyfit", &valstack.pop().unwrap())?;
+    let xs = expect_poly("polyfit", &valstack.pop().unwrap())?;
+    if !degree.is_int() || degree.to_int() < 0 {
+        return Err(format!("invalid types in function 'polyfit': degree has to be a positive integer"))
+    }
+    let n = degree.to_int() as usize + 1;
+    if xs.len() != ys.len() {
+        return Err(format!("polyfit: xs and ys have different lengths"))
+    }
+    if xs.len() < n {
+        return Err(format!("polyfit: at least {} points are needed for degree {}", n, n - 1))
+    }
+    //vandermonde matrix, one row per point
+    let mut a = xs.iter().map(|x| (0..n).map(|k| x.to_float().powi(k as i32)).collect::<Vec<f64>>()).collect::<Vec<Vec<f64>>>();
+    let mut b = ys.iter().map(|y| y.to_float()).collect::<Vec<f64>>();
+    let m = a.len();
+    for k in 0..n {
+        let norm = (k..m).map(|i| a[i][k] * a[i][k]).sum::<f64>().sqrt();
+        if norm == 0.0 {
+            return Err(format!("polyfit: not enough distinct x values"))
+        }
+        let alpha = if a[k][k] > 0.0 { -norm } else { norm };
+        let mut v = (k..m).map(|i| a[i][k]).collect::<Vec<f64>>();
+        v[0] -= alpha;
+        let vnorm = v.iter().map(|x| x * x).sum::<f64>();
+        for j in k..n {
+            let dot = (k..m).map(|i| v[i - k] * a[i][j]).sum::<f64>();
+            for i in k..m {
+                a[i][j] -= 2.0 * v[i - k] * dot / vnorm;
+            }
+        }
+        let dot = (k..m).map(|i| v[i - k] * b[i]).sum::<f64>();
+        for i in k..m {
+            b[i] -= 2.0 * v[i - k] * dot / vnorm;
+        }
+    }
+    let mut coeffs = vec![0.0; n];
+    for k in (0..n).rev() {
+        let rest = (k + 1..n).map(|j| a[k][j] * coeffs[j]).sum::<f64>();
+        if a[k][k].abs() < 1e-300 {
+            return Err(format!("polyfit: not enough distinct x values"))
+        }
+        coeffs[k] = (b[k] - rest) / a[k][k];
+    }
+    Ok(to_list(coeffs.into_iter().map(Value::Float).collect()))
+}
+
+//polycoeffs(f): the coefficients of a user function that is a polynomial in its argument
+pub fn polycoeffs(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    stdlib::check_n(1, "polycoeffs", valstack)?;
+    let name = match valstack.pop().unwrap() {
+        Value::FnRef(name) => name,
+        other => return Err(format!("invalid types in function 'polycoeffs': {}", other.type_str()))
+    };
+    let function = match engine.functions().get(&name) {
+        Some(v) => v,
+        None => return Err(format!("polycoeffs: {} is not a user function", name))
+    };
+    if function.arg_count() != 1 {
+        return Err(format!("polycoeffs: {} has to take exactly one argument", name))
+    }
+    let var = function.args()[0].clone();
+    let body = Expr::from_code(&function.code(), engine)?.simplify(engine);
+    match to_poly(&body, &var, engine, 0) {
+        Ok(p) => Ok(to_list(trim(p))),
+        Err(e) => Err(format!("{} is not a polynomial in {}: {}", name, var, e))
+    }
+}
+
+//UTILS
+
+fn to_poly(expr: &Expr, var: &str, engine: &Engine, depth: usize) -> Result<Vec<Value>, String> {
+    let (name, args) = match *expr {
+        Expr::Value(ref v) if v.is_number() => return Ok(vec![v.clone()]),
+        Expr::Variable(ref name) if name == var => return Ok(vec![Value::Integer(0), Value::Integer(1)]),
+        Expr::Variable(ref name) => match engine.get_val(name) {
+            Some(v) if v.is_number() => return Ok(vec![v.clone()]),
+            _ => return Err(format!("variable '{}' not found", name))
+        },
+        Expr::Call(ref name, ref args) => (name.as_str(), args),
+        Expr::Let(..) => return to_poly(&expr.expand(), var, engine, depth),
+        _ => return Err(format!("'{}' is not a number", expr))
+    };
+    if engine.functions().contains_key(name) {
+        if depth > 32 {
+            return Err(format!("function nesting too deep in '{}'", name))
+        }
+        return to_poly(&expr::inline(name, args, engine)?, var, engine, depth + 1)
+    }
+    let poly = |index: usize| to_poly(&args[index], var, engine, depth);
+    match name {
+        "add" | "sub" => {
+            let (p, q) = (poly(0)?, poly(1)?);
+            let mut result = vec![Value::Integer(0); p.len().max(q.len())];
+            for (index, elem) in result.iter_mut().enumerate() {
+                let a = p.get(index).cloned().unwrap_or(Value::Integer(0));
+                let b = q.get(index).cloned().unwrap_or(Value::Integer(0));
+                *elem = arith(name, a, b, engine)?;
+            }
+            Ok(result)
+        },
+        "neg" => {
+            poly(0)?.into_iter().map(|x| arith("sub", Value::Integer(0), x, engine)).collect()
+        },
+        "mul" => mul(&poly(0)?, &poly(1)?, engine),
+        "div" => {
+            let divisor = trim(poly(1)?);
+            if divisor.len() > 1 {
+                return Err(format!("division by '{}'", args[1]))
+            }
+            poly(0)?.into_iter().map(|x| arith("div", x, divisor[0].clone(), engine)).collect()
+        },
+        "pow" => {
+            let exponent = trim(poly(1)?);
+            if exponent.len() > 1 || !exponent[0].is_int() || exponent[0].to_int() < 0 {
+                return Err(format!("'{}' is not a positive integer exponent", args[1]))
+            }
+            let base = poly(0)?;
+            let mut result = vec![Value::Integer(1)];
+            for _ in 0..exponent[0].to_int() {
+                result = mul(&result, &base, engine)?;
+            }
+            Ok(result)
+        },
+        _ => {
+            if args.iter().any(|x| x.depends_on(var)) {
+                return Err(format!("'{}' is not a polynomial function", name))
+            }
+            let mut stack = Vec::new();
+            for arg in args {
+                stack.push(eval_const(arg, var, engine, depth)?);
+            }
+            Ok(vec![stdlib::match_fn(name.to_owned(), &mut stack, engine)?])
+        }
+    }
+}
+
+fn eval_const(expr: &Expr, var: &str, engine: &Engine, depth: usize) -> Result<Value, String> {
+    let p = trim(to_poly(expr, var, engine, depth)?);
+    Ok(p[0].clone())
+}
+
+fn mul(p: &Vec<Value>, q: &Vec<Value>, engine: &Engine) -> Result<Vec<Value>, String> {
+    if p.is_empty() || q.is_empty() {
+        return Ok(Vec::new())
+    }
+    let mut result = vec![Value::Integer(0); p.len() + q.len() - 1];
+    for (i, a) in p.iter().enumerate() {
+        for (j, b) in q.iter().enumerate() {
+            let product = arith("mul", a.clone(), b.clone(), engine)?;
+            result[i + j] = arith("add", result[i + j].clone(), product, engine)?;
+        }
+    }
+    Ok(result)
+}
+
+//removes zero coefficients of the highest degrees, keeps at least one coefficient
+fn trim(mut p: Vec<Value>) -> Vec<Value> {
+    while p.len() > 1 && p.last().unwrap().to_float() == 0.0 {
+        p.pop();
+    }
+    if p.is_empty() {
+        p.push(Value::Integer(0));
+    }
+    p
+}
+
+fn expect_poly(fnname: &str, val: &Value) -> Result<Vec<Value>, String> {
+    match *val {
+        Value::List(ref items) if items.iter().all(|x| x.is_number()) => {
+            Ok(items.iter().rev().cloned().collect())
+        },
+        Value::List(_) => Err(format!("invalid types in function '{}': the list has to contain numbers", fnname)),
+        _ => Err(format!("invalid types in function '{}': {}", fnname, val.type_str()))
+    }
+}
+
+fn to_list(p: Vec<Value>) -> Value {
+    Value::List(p.into_iter().rev().collect())
+}
+
+fn arith(name: &str, a: Value, b: Value, engine: &Engine) -> Result<Value, String> {
+    let mut stack = vec![a, b];
+    stdlib::match_fn(name.to_owned(), &mut stack, engine)
+}
+
+#[derive(Clone, Copy)]
+struct Complex(f64, f64);
+
+impl Complex {
+    fn add(self, other: Complex) -> Complex {
+        Complex(self.0 + other.0, self.1 + other.1)
+    }
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex(self.0 - other.0, self.1 - other.1)
+    }
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex(self.0 * other.0 - self.1 * other.1, self.0 * other.1 + self.1 * other.0)
+    }
+
+    fn div(self, other: Complex) -> Complex {
+        let denom = other.0 * other.0 + other.1 * other.1;
+        Complex((self.0 * other.0 + self.1 * other.1) / denom, (self.1 * other.0 - self.0 * other.1) / denom)
+    }
+
+    fn abs(self) -> f64 {
+        self.0.hypot(self.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use engine::Engine;
+    use testing::run;
+
+    #[test]
+    fn polynomials() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "polyval([1, 0, -2], 3)"), "7 [int]");
+        assert_eq!(run(&mut engine, "polymul([1, 1], [1, -1])"), "[1, 0, -1] [list]");
+        assert_eq!(run(&mut engine, "polydiv([1, 0, -1], [1, 1])"), "[[1.0, -1.0], [0.0]] [list]");
+        assert_eq!(run(&mut engine, "polyroots([1, 0, -4])"), "[[2.0, 0.0], [-2.0, 0.0]] [list]");
+        run(&mut engine, ":fn f X = (X+1)^2");
+        assert_eq!(run(&mut engine, "polycoeffs(f)"), "[1, 2, 1] [list]");
+    }
+
+    #[test]
+    fn errors() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "polydiv([1, 2], [0])"), "error: divide by zero");
+        assert_eq!(run(&mut engine, "polyfit([1], [1], 2)"), "error: polyfit: at least 3 points are needed for degree 2");
+        run(&mut engine, ":fn g X = sin(X)");
+        assert_eq!(run(&mut engine, "polycoeffs(g)"), "error: g is not a polynomial in X: 'sin' is not a polynomial function");
+        assert!(run(&mut engine, "[1, 2] + 1").starts_with("error: invalid types in function 'add'"));
+    }
+}
@@ -0,0 +1,209 @@
+//':save' writes the state of the engine as a script that ':restore' runs again:
+//
+//  -- termcalc session 1
+//  -- results 3
+//  :numeric decimal 10
+//  :precision 50
+//  :numeric float
+//  :fn f X = X^2
+//  :numeric decimal 10
+//  :fn g X = X*0.1
+//  :numeric float
+//  :var $0 0.30000000000000004
+//  :let A = $0*2
+//  :numeric decimal 10
+//
+//the numbers of functions and formulas are read in the mode they were typed in, variables
+//are read as floats (decimals and bigfloats have a suffix), the last ':numeric' is the current mode
+
+use std::collections::HashMap;
+
+use value::{Value, NumericMode};
+use datetime::DateTime;
+use engine::Engine;
+use function;
+
+//the version written by this termcalc, ':restore' reads it and the older ones
+pub const VERSION: u32 = 1;
+
+pub fn to_text(engine: &Engine) -> String {
+    let mut lines = vec![format!("-- termcalc session {}", VERSION), format!("-- results {}", engine.result_count())];
+    lines.push(format!(":numeric decimal {}", engine.decimal_places()));
+    lines.push(format!(":precision {}", engine.precision()));
+    lines.push(format!(":numeric float"));
+    let mut mode = NumericMode::Float;
+    //functions passed as values have to exist when the function using them is read
+    let functions = dependency_order(engine.functions(), |func| {
+        let mut names = Vec::new();
+        function::function_refs(&func.code(), &mut names);
+        names
+    });
+    for name in functions {
+        let func = &engine.functions()[name];
+        switch_mode(&mut lines, &mut mode, func.mode(), engine);
+        lines.push(format!(":fn {} {} = {}", name, func.args().join(" "), func.text()));
+    }
+    switch_mode(&mut lines, &mut mode, NumericMode::Float, engine);
+    //the variables of ':let' are computed from the formulas
+    for name in sorted_keys(engine.vars()).into_iter().filter(|x| !engine.formulas().contains_key(*x)) {
+        lines.push(format!(":var {} {}", name, literal(&engine.vars()[name])));
+    }
+    for name in dependency_order(engine.formulas(), |formula| formula.deps.clone()) {
+        let formula = &engine.formulas()[name];
+        switch_mode(&mut lines, &mut mode, formula.mode, engine);
+        lines.push(format!(":let {} = {}", name, formula.text));
+    }
+    lines.push(mode_line(engine.numeric(), engine));
+    if let Some(seconds) = engine.clock() {
+        lines.push(format!(":clock {}", DateTime::Both(seconds)));
+    }
+    for dir in engine.search_path() {
+        lines.push(format!(":path add {}", dir));
+    }
+    if let Some(path) = engine.autosave() {
+        lines.push(format!(":autosave {}", path));
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+fn switch_mode(lines: &mut Vec<String>, mode: &mut NumericMode, new: NumericMode, engine: &Engine) {
+    if *mode != new {
+        lines.push(mode_line(new, engine));
+        *mode = new;
+    }
+}
+
+fn mode_line(mode: NumericMode, engine: &Engine) -> String {
+    match mode {
+        NumericMode::Float => format!(":numeric float"),
+        NumericMode::Decimal => format!(":numeric decimal {}", engine.decimal_places()),
+        NumericMode::BigFloat => format!(":numeric bigfloat")
+    }
+}
+
+//the result counter, after checking the version in the first line
+pub fn header(text: &str) -> Result<usize, String> {
+    let mut lines = text.lines();
+    let version = match lines.next().and_then(|x| x.trim().trim_start_matches("-- termcalc session ").parse::<u32>().ok()) {
+        Some(v) => v,
+        None => return Err(format!("not a termcalc session, it has to start with '-- termcalc session {}'", VERSION))
+    };
+    if version > VERSION {
+        return Err(format!("the session has version {}, this termcalc reads up to version {}", version, VERSION))
+    }
+    match lines.next().and_then(|x| x.trim().trim_start_matches("-- results ").parse::<usize>().ok()) {
+        Some(count) => Ok(count),
+        None => Err(format!("expected '-- results [count]' in the second line of the session"))
+    }
+}
+
+//like Value::literal, but without rounding intervals and with the captured variables of function literals
+fn literal(val: &Value) -> String {
+    match *val {
+        Value::Interval(v) => format!("interval({:?}, {:?})", v.lo(), v.hi()),
+        Value::Function(ref closure) if !closure.captured().is_empty() => {
+            let bindings = sorted_keys(closure.captured()).into_iter()
+                .map(|name| format!("{} = {}", name, literal(&closure.captured()[name])))
+                .collect::<Vec<String>>();
+            format!("(let {} in ({}))", bindings.join(", "), closure)
+        },
+        Value::List(ref items) => format!("[{}]", items.iter().map(literal).collect::<Vec<String>>().join(", ")),
+        _ => val.literal()
+    }
+}
+
+//the names sorted, but each after the names of the map it uses
+fn dependency_order<T, F>(map: &HashMap<String, T>, uses: F) -> Vec<&String> where F: Fn(&T) -> Vec<String> {
+    let mut order = Vec::new();
+    for name in sorted_keys(map) {
+        add_after_uses(map, name, &uses, &mut Vec::new(), &mut order);
+    }
+    order
+}
+
+//path are the names that wait for name, a name using itself stays where it is
+fn add_after_uses<'a, T, F>(map: &'a HashMap<String, T>, name: &'a String, uses: &F, path: &mut Vec<&'a String>, order: &mut Vec<&'a String>)
+    where F: Fn(&T) -> Vec<String> {
+    if order.contains(&name) || path.contains(&name) {
+        return
+    }
+    path.push(name);
+    let mut used = uses(&map[name]);
+    used.sort();
+    for other in used {
+        if let Some((key, _)) = map.get_key_value(&other) {
+            add_after_uses(map, key, uses, path, order);
+        }
+    }
+    path.pop();
+    order.push(name);
+}
+
+fn sorted_keys<T>(map: &HashMap<String, T>) -> Vec<&String> {
+    let mut result = map.keys().collect::<Vec<&String>>();
+    result.sort();
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use testing::{run, temp_dir, temp_file};
+
+    #[test]
+    fn session_round_trip() {
+        let module = temp_file("geometry.tc", ":var R 2\n:var D R*2\n:let A = R*3\n");
+        let session = temp_file("session.tc", "");
+        let mut engine = Engine::new();
+        run(&mut engine, &format!(":import \"{}\" as geo", module));
+        run(&mut engine, ":fn h X = X*0.1");
+        run(&mut engine, ":numeric decimal 10");
+        run(&mut engine, ":fn k X = X*0.1");
+        run(&mut engine, ":fn twice F X = F(F(X))");
+        run(&mut engine, ":fn quad X = twice(h, X)");
+        run(&mut engine, ":let B = geo.A + 1");
+        run(&mut engine, ":let C = B*2");
+        assert_eq!(run(&mut engine, &format!(":save {}", session)), "");
+        let mut restored = Engine::new();
+        assert_eq!(run(&mut restored, &format!(":restore {}", session)), "");
+        assert_eq!(run(&mut restored, "h(3)"), "0.30000000000000004 [float]");
+        assert_eq!(run(&mut restored, "k(3)"), "0.3 [decimal]");
+        assert_eq!(run(&mut restored, "geo.D"), "4 [int]");
+        assert_eq!(run(&mut restored, "C"), "14 [int]");
+        assert_eq!(run(&mut restored, "A"), "error: variable 'A' not found");
+        assert_eq!(to_text(&restored), to_text(&engine));
+        fs::remove_file(module).unwrap();
+        fs::remove_file(session).unwrap();
+    }
+
+    #[test]
+    fn failed_restore_keeps_state() {
+        let session = temp_file("broken.tc", "-- termcalc session 1\n-- results 0\n:var X 1\n:var y 2\n");
+        let mut engine = Engine::new();
+        run(&mut engine, ":var Keep 5");
+        assert!(run(&mut engine, &format!(":restore {}", session)).starts_with("error"));
+        assert_eq!(run(&mut engine, "Keep"), "5 [int]");
+        assert_eq!(run(&mut engine, "X"), "error: variable 'X' not found");
+        fs::remove_file(session).unwrap();
+    }
+
+    #[test]
+    fn save_replaces_the_file() {
+        let dir = temp_dir("save");
+        let path = dir.join("session.tc");
+        fs::write(&path, "old").unwrap();
+        let mut engine = Engine::new();
+        run(&mut engine, ":var X 1");
+        assert_eq!(run(&mut engine, &format!(":save {}", path.display())), "");
+        assert_eq!(fs::read_to_string(&path).unwrap(), to_text(&engine));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        //a directory that does not exist leaves nothing behind
+        let missing = dir.join("missing").join("session.tc");
+        assert!(run(&mut engine, &format!(":save {}", missing.display())).starts_with("error: file write error"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
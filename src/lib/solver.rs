@@ -0,0 +1,250 @@
+use value::Value;
+use engine::Engine;
+use calculus;
+use stdlib;
+
+const MAX_ITERATIONS: usize = 200;
+const TOLERANCE: f64 = 1e-12;
+const RESIDUAL_TOLERANCE: f64 = 1e-8;
+
+//solve(f, guess): a root of f near guess
+pub fn solve(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    stdlib::check_n(2, "solve", valstack)?;
+    let guess = valstack.pop().unwrap();
+    let func = valstack.pop().unwrap();
+    let guess = calculus::expect_number("solve", &guess)?;
+    let f = |x: f64| calculus::eval_at(&func, x, engine);
+    Ok(Value::Float(find_root(&f, guess)?))
+}
+
+//root(f, a, b): a root of f between a and b, f(a) and f(b) need different signs
+pub fn root(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    stdlib::check_n(3, "root", valstack)?;
+    let b = valstack.pop().unwrap();
+    let a = valstack.pop().unwrap();
+    let func = valstack.pop().unwrap();
+    let a = calculus::expect_number("root", &a)?;
+    let b = calculus::expect_number("root", &b)?;
+    let f = |x: f64| calculus::eval_at(&func, x, engine);
+    Ok(Value::Float(brent(&f, a, b)?))
+}
+
+//tries newton, then the secant method, then searches for a sign change around guess
+pub fn find_root<F>(f: &F, guess: f64) -> Result<f64, String> where F: Fn(f64) -> Result<f64, String> {
+    let f_guess = f(guess)?;
+    //newton and secant can stop on a small step far away from a root
+    let residual = RESIDUAL_TOLERANCE * f_guess.abs().max(1.0);
+    if let Some(x) = newton(f, guess)? {
+        if f(x)?.abs() <= residual {
+            return Ok(x)
+        }
+    }
+    if let Some(x) = secant(f, guess)? {
+        if f(x)?.abs() <= residual {
+            return Ok(x)
+        }
+    }
+    let scale = guess.abs().max(1.0);
+    let mut dist = 0.5 * scale;
+    let mut left = (guess, f_guess);
+    let mut right = (guess, f_guess);
+    //further away the rounding of x is bigger than the scale of guess, X + 1 - X is 0
+    while dist < scale / f64::EPSILON {
+        let x = guess - dist;
+        let fx = f(x)?;
+        if changes_sign(left.1, fx) {
+            return checked_root(f, brent(f, x, left.0)?, residual)
+        }
+        left = (x, fx);
+        let x = guess + dist;
+        let fx = f(x)?;
+        if changes_sign(right.1, fx) {
+            return checked_root(f, brent(f, right.0, x)?, residual)
+        }
+        right = (x, fx);
+        dist *= 2.0;
+    }
+    Err(format!("no root found near {}", guess))
+}
+
+//a sign change can also be a pole or a jump, like in 1/X
+fn checked_root<F>(f: &F, x: f64, residual: f64) -> Result<f64, String> where F: Fn(f64) -> Result<f64, String> {
+    let fx = f(x)?;
+    if fx.abs() <= residual {
+        Ok(x)
+    } else {
+        Err(format!("no root found, the sign of the function changes at {} but its value is {}", x, fx))
+    }
+}
+
+pub fn newton<F>(f: &F, guess: f64) -> Result<Option<f64>, String> where F: Fn(f64) -> Result<f64, String> {
+    let mut x = guess;
+    for _ in 0..MAX_ITERATIONS {
+        let fx = f(x)?;
+        if fx == 0.0 {
+            return Ok(Some(x))
+        }
+        let slope = calculus::derivative(f, x)?;
+        if slope == 0.0 || !slope.is_finite() {
+            return Ok(None)
+        }
+        let step = fx / slope;
+        x -= step;
+        if !x.is_finite() {
+            return Ok(None)
+        }
+        if converged(step, x) {
+            return Ok(Some(x))
+        }
+    }
+    Ok(None)
+}
+
+pub fn secant<F>(f: &F, guess: f64) -> Result<Option<f64>, String> where F: Fn(f64) -> Result<f64, String> {
+    let mut x0 = guess;
+    let mut x1 = guess + 1e-3 * guess.abs().max(1.0);
+    let mut f0 = f(x0)?;
+    for _ in 0..MAX_ITERATIONS {
+        let f1 = f(x1)?;
+        if f1 == 0.0 {
+            return Ok(Some(x1))
+        }
+        //a difference of rounding errors gives no slope, like in X - (X + 1)
+        if (f1 - f0).abs() <= TOLERANCE * f1.abs().max(f0.abs()) {
+            return Ok(None)
+        }
+        let step = f1 * (x1 - x0) / (f1 - f0);
+        x0 = x1;
+        f0 = f1;
+        x1 -= step;
+        if !x1.is_finite() {
+            return Ok(None)
+        }
+        if converged(step, x1) {
+            return Ok(Some(x1))
+        }
+    }
+    Ok(None)
+}
+
+pub fn brent<F>(f: &F, a: f64, b: f64) -> Result<f64, String> where F: Fn(f64) -> Result<f64, String> {
+    let (mut a, mut b) = (a, b);
+    let (mut fa, mut fb) = (f(a)?, f(b)?);
+    if fa == 0.0 {
+        return Ok(a)
+    }
+    if fb == 0.0 {
+        return Ok(b)
+    }
+    if fa.signum() == fb.signum() {
+        return Err(format!("f({}) and f({}) have the same sign", a, b))
+    }
+    let (mut c, mut fc) = (a, fa);
+    let mut d = b - a;
+    let mut e = d;
+    for _ in 0..MAX_ITERATIONS {
+        if fb.signum() == fc.signum() {
+            c = a;
+            fc = fa;
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            a = b;
+            b = c;
+            c = a;
+            fa = fb;
+            fb = fc;
+            fc = fa;
+        }
+        let tol = 2.0 * ::std::f64::EPSILON * b.abs() + 0.5 * TOLERANCE;
+        let m = 0.5 * (c - b);
+        if m.abs() <= tol || fb == 0.0 {
+            return Ok(b)
+        }
+        if e.abs() >= tol && fa.abs() > fb.abs() {
+            //inverse quadratic interpolation, or secant if only two points are known
+            let s = fb / fa;
+            let (mut p, mut q);
+            if a == c {
+                p = 2.0 * m * s;
+                q = 1.0 - s;
+            }
+            else {
+                let qa = fa / fc;
+                let r = fb / fc;
+                p = s * (2.0 * m * qa * (qa - r) - (b - a) * (r - 1.0));
+                q = (qa - 1.0) * (r - 1.0) * (s - 1.0);
+            }
+            if p > 0.0 {
+                q = -q;
+            }
+            else {
+                p = -p;
+            }
+            if 2.0 * p < (3.0 * m * q - (tol * q).abs()).min((e * q).abs()) {
+                e = d;
+                d = p / q;
+            }
+            else {
+                d = m;
+                e = m;
+            }
+        }
+        else {
+            d = m;
+            e = m;
+        }
+        a = b;
+        fa = fb;
+        if d.abs() > tol {
+            b += d;
+        }
+        else {
+            b += tol * m.signum();
+        }
+        fb = f(b)?;
+    }
+    Err(format!("root finding did not converge"))
+}
+
+fn changes_sign(a: f64, b: f64) -> bool {
+    a.is_finite() && b.is_finite() && (a == 0.0 || b == 0.0 || a.signum() != b.signum())
+}
+
+fn converged(step: f64, x: f64) -> bool {
+    step.abs() <= TOLERANCE * x.abs().max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn brent_in_bracket() {
+        assert!((brent(&|x: f64| Ok(x.cos()), 0.0, 3.0).unwrap() - PI / 2.0).abs() < 1e-12);
+        assert!((brent(&|x| Ok(x * x - 2.0), 0.0, 2.0).unwrap() - 2f64.sqrt()).abs() < 1e-12);
+        //the root at the end of the bracket
+        assert_eq!(brent(&|x| Ok(x - 1.0), 1.0, 5.0).unwrap(), 1.0);
+        //a flat function with a jump
+        assert!((brent(&|x| Ok(if x < 0.3 { -1.0 } else { 1.0 }), 0.0, 1.0).unwrap() - 0.3).abs() < 1e-11);
+    }
+
+    #[test]
+    fn brent_without_sign_change() {
+        assert!(brent(&|x| Ok(x * x + 1.0), -1.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn roots_near_guess() {
+        assert!((find_root(&|x| Ok(x * x - 2.0), 1.0).unwrap() - 2f64.sqrt()).abs() < 1e-10);
+        assert!((find_root(&|x| Ok(x * x * x - x - 2.0), 0.0).unwrap() - 1.5213797068045676).abs() < 1e-10);
+        //newton does not converge on the cube root from 1
+        assert!(find_root(&|x: f64| Ok(x.cbrt()), 1.0).unwrap().abs() < 1e-10);
+        assert!(find_root(&|x| Ok(x * x + 1.0), 0.0).is_err());
+        //sign changes that are not roots
+        assert!(find_root(&|x| Ok(if x < 0.3 { -1.0 } else { 1.0 }), 0.0).is_err());
+        assert!(find_root(&|x| Ok(x - (x + 1.0)), 0.0).is_err());
+    }
+}
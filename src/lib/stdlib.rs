@@ -1,8 +1,23 @@
-use value::Value;
+use std::cmp::Ordering;
+use value::{Value, NumericMode};
+use decimal::Decimal;
+use bigfloat::BigFloat;
+use interval::Interval;
+use datetime;
+use engine::Engine;
+use calculus;
+use solver;
+use poly;
+use finance;
+use strings;
+use lists;
+
+//significant digits of the bigfloats that decimal results of sqrt, sin, ... are computed with
+const DECIMAL_DIGITS: u32 = 50;
 
 macro_rules! pop {
     ($e:expr) => {
-        $e.pop().unwrap();
+        $e.pop().unwrap()
     };
 }
 
@@ -20,32 +35,111 @@ macro_rules! throw_invalid_type {
     };
 }
 
-pub fn match_fn(name: String, valstack: &mut Vec<Value>) -> Result<Value, String> {
+pub fn match_fn(name: String, valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
     match name.as_str() {
-        "add" => add(valstack),
-        "sub" => sub(valstack),
-        "div" => div(valstack),
-        "mul" => mul(valstack),
+        "add" => add(valstack, engine),
+        "sub" => sub(valstack, engine),
+        "div" => div(valstack, engine),
+        "mul" => mul(valstack, engine),
         "neg" => negate(valstack),
-        "pow" => pow(valstack),
-        "sqrt" => sqrt(valstack),
+        "pow" => pow(valstack, engine),
+        "sqrt" => sqrt(valstack, engine),
+        "exp" => float_fn("exp", valstack, engine, f64::exp, BigFloat::exp),
+        "ln" => ln(valstack, engine),
         "eq" => equals(valstack),
         "lt" => lesserthan(valstack),
         "gt" => greaterthan(valstack),
-        "sin" => sin(valstack),
-        "cos" => cos(valstack),
-        "tan" => tan(valstack),
-        "sinh" => sinh(valstack),
-        "cosh" => cosh(valstack),
-        "tanh" => tanh(valstack),
+        "sin" => float_fn("sin", valstack, engine, f64::sin, BigFloat::sin),
+        "cos" => float_fn("cos", valstack, engine, f64::cos, BigFloat::cos),
+        "tan" => float_fn("tan", valstack, engine, f64::tan, BigFloat::tan),
+        "sinh" => float_fn("sinh", valstack, engine, f64::sinh, BigFloat::sinh),
+        "cosh" => float_fn("cosh", valstack, engine, f64::cosh, BigFloat::cosh),
+        "tanh" => float_fn("tanh", valstack, engine, f64::tanh, BigFloat::tanh),
         "abs" => abs(valstack),
         "floor" => floor(valstack),
         "ceil" => ceil(valstack),
+        "round" => round(valstack),
         "if" => fnif(valstack),
+        "pm" => plusminus(valstack),
+        "interval" => interval(valstack),
+        "deriv" => calculus::deriv(valstack, engine),
+        "integrate" => calculus::integrate(valstack, engine),
+        "integrate_err" => calculus::integrate_err(valstack, engine),
+        "sum" => calculus::sum(valstack, engine),
+        "product" => calculus::product(valstack, engine),
+        "solve" => solver::solve(valstack, engine),
+        "root" => solver::root(valstack, engine),
+        "polyval" => poly::polyval(valstack, engine),
+        "polymul" => poly::polymul(valstack, engine),
+        "polydiv" => poly::polydiv(valstack, engine),
+        "polyroots" => poly::polyroots(valstack),
+        "polyfit" => poly::polyfit(valstack),
+        "polycoeffs" => poly::polycoeffs(valstack, engine),
+        "fv" => finance::fv(valstack, engine),
+        "pv" => finance::pv(valstack, engine),
+        "pmt" => finance::pmt(valstack, engine),
+        "nper" => finance::nper(valstack, engine),
+        "rate" => finance::rate(valstack, engine),
+        "npv" => finance::npv(valstack, engine),
+        "irr" => finance::irr(valstack, engine),
+        "xirr" => finance::xirr(valstack, engine),
+        "amort" => finance::amort(valstack, engine),
+        "compound" => finance::compound(valstack, engine),
+        "pctchange" => finance::pctchange(valstack, engine),
+        "addpct" => finance::addpct(valstack, engine),
+        "subpct" => finance::subpct(valstack, engine),
+        "now" => datetime::now(engine),
+        "today" => datetime::today(engine),
+        "days_between" => datetime::days_between(valstack),
+        "weekday" => datetime::weekday(valstack),
+        "add_business_days" => datetime::add_business_days(valstack),
+        "len" => lists::len(valstack),
+        "str" => strings::str(valstack),
+        "num" => strings::num(valstack, engine),
+        "format" => strings::format(valstack),
+        "range" => lists::range(valstack, engine),
+        "linspace" => lists::linspace(valstack, engine),
+        "map" => lists::map(valstack, engine),
+        "filter" => lists::filter(valstack, engine),
+        "reduce" => lists::reduce(valstack, engine),
+        "zip" => lists::zip(valstack),
+        "sort" => lists::sort(valstack, engine),
+        "reverse" => lists::reverse(valstack),
+        "take" => lists::take(valstack),
+        "drop" => lists::drop(valstack),
+        "cumsum" => lists::cumsum(valstack, engine),
         _ => Err(format!("unknown function: {}", name))
     }
 }
 
+//number of arguments of a stdlib function
+pub fn arity(name: &str) -> Option<usize> {
+    SIGNATURES.iter().find(|x| x.0 == name).map(|x| if x.1.is_empty() { 0 } else { x.1.split(',').count() })
+}
+
+//the stdlib functions with the names of their arguments, for arity() and for completion and hints in the terminal
+pub const SIGNATURES: &[(&str, &str)] = &[
+    ("add", "x, y"), ("sub", "x, y"), ("div", "x, y"), ("mul", "x, y"), ("pow", "x, y"), ("neg", "x"),
+    ("sqrt", "x"), ("exp", "x"), ("ln", "x"),
+    ("sin", "x"), ("cos", "x"), ("tan", "x"), ("sinh", "x"), ("cosh", "x"), ("tanh", "x"),
+    ("abs", "x"), ("floor", "x"), ("ceil", "x"), ("round", "x, places"), ("if", "a, b, c"),
+    ("pm", "x, dx"), ("interval", "a, b"), ("eq", "x, y"), ("lt", "x, y"), ("gt", "x, y"),
+    ("deriv", "f, x"), ("integrate", "f, a, b"), ("integrate_err", "f, a, b"),
+    ("sum", "f, a, b"), ("product", "f, a, b"), ("solve", "f, x"), ("root", "f, a, b"),
+    ("polyval", "p, x"), ("polymul", "p, q"), ("polydiv", "p, q"), ("polyroots", "p"),
+    ("polyfit", "xs, ys, degree"), ("polycoeffs", "f"),
+    ("fv", "rate, nper, pmt, pv"), ("pv", "rate, nper, pmt, fv"), ("pmt", "rate, nper, pv, fv"),
+    ("nper", "rate, pmt, pv, fv"), ("rate", "nper, pmt, pv, fv"), ("npv", "rate, cashflows"),
+    ("irr", "cashflows"), ("xirr", "cashflows, days"), ("amort", "rate, nper, pv"),
+    ("compound", "principal, rate, periods, years"), ("pctchange", "old, new"),
+    ("addpct", "x, p"), ("subpct", "x, p"),
+    ("now", ""), ("today", ""), ("days_between", "a, b"), ("weekday", "date"), ("add_business_days", "date, n"),
+    ("len", "xs"), ("str", "x"), ("num", "s"), ("format", "template, x"),
+    ("range", "a, b, step"), ("linspace", "a, b, n"), ("map", "f, xs"), ("filter", "f, xs"),
+    ("reduce", "f, xs, init"), ("zip", "xs, ys"), ("sort", "xs"), ("reverse", "xs"),
+    ("take", "xs, n"), ("drop", "xs, n"), ("cumsum", "xs")
+];
+
 //FUNCTIONS
 
 fn floor(valstack: &mut Vec<Value>) -> Result<Value, String> {
@@ -54,6 +148,17 @@ fn floor(valstack: &mut Vec<Value>) -> Result<Value, String> {
     if p1.is_int() {
         Ok(p1)
     }
+    else if let Value::Interval(p1val) = p1 {
+        interval_result(p1val.apply("floor"))
+    }
+    else if p1.is_bigfloat() {
+        let p1val = p1.to_bigfloat().unwrap();
+        Ok(Value::BigFloat(p1val.floor()))
+    }
+    else if p1.is_decimal() {
+        let p1val = p1.to_decimal().unwrap();
+        Ok(Value::Decimal(p1val.floor()))
+    }
     else if p1.is_float() {
         let p1val = p1.to_float();
         Ok(Value::Float(p1val.floor()))
@@ -69,100 +174,85 @@ fn ceil(valstack: &mut Vec<Value>) -> Result<Value, String> {
     if p1.is_int() {
         Ok(p1)
     }
-    else if p1.is_float() {
-        let p1val = p1.to_float();
-        Ok(Value::Float(p1val.ceil()))
+    else if let Value::Interval(p1val) = p1 {
+        interval_result(p1val.apply("ceil"))
     }
-    else {
-        throw_invalid_type!("ceil", &p1.type_str())
+    else if p1.is_bigfloat() {
+        let p1val = p1.to_bigfloat().unwrap();
+        Ok(Value::BigFloat(p1val.ceil()))
     }
-}
-
-fn abs(valstack: &mut Vec<Value>) -> Result<Value, String> {
-    check_one("abs", valstack)?;
-    let p1 = pop!(valstack);
-    if p1.is_int() {
-        let p1val = p1.to_int();
-        Ok(Value::Integer(p1val.abs()))
+    else if p1.is_decimal() {
+        let p1val = p1.to_decimal().unwrap();
+        Ok(Value::Decimal(p1val.ceil()))
     }
     else if p1.is_float() {
         let p1val = p1.to_float();
-        Ok(Value::Float(p1val.abs()))
+        Ok(Value::Float(p1val.ceil()))
     }
     else {
-        throw_invalid_type!("abs", &p1.type_str())
+        throw_invalid_type!("ceil", &p1.type_str())
     }
 }
 
-fn sin(valstack: &mut Vec<Value>) -> Result<Value, String> {
-    check_one("sin", valstack)?;
+//round(x, places): half away from zero
+fn round(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    check_two("round", valstack)?;
+    let p2 = pop!(valstack);
     let p1 = pop!(valstack);
-    if p1.is_float_or_int() {
-        let p1val = p1.to_float();
-        Ok(Value::Float(p1val.sin()))
+    if !p2.is_int() || p2.to_int() < 0 || p2.to_int() > 28 {
+        return Err(format!("round: places have to be an integer between 0 and 28"))
     }
-    else {
-        throw_invalid_type!("sin", &p1.type_str())
+    let places = p2.to_int() as u32;
+    if p1.is_int() {
+        Ok(p1)
     }
-}
-
-fn sinh(valstack: &mut Vec<Value>) -> Result<Value, String> {
-    check_one("sinh", valstack)?;
-    let p1 = pop!(valstack);
-    if p1.is_float_or_int() {
-        let p1val = p1.to_float();
-        Ok(Value::Float(p1val.sinh()))
+    else if let Value::Interval(p1val) = p1 {
+        Ok(Value::Interval(p1val.round(places)))
     }
-    else {
-        throw_invalid_type!("sinh", &p1.type_str())
+    else if p1.is_bigfloat() {
+        Ok(Value::BigFloat(p1.to_bigfloat().unwrap().round_places(places)))
     }
-}
-
-fn cos(valstack: &mut Vec<Value>) -> Result<Value, String> {
-    check_one("cos", valstack)?;
-    let p1 = pop!(valstack);
-    if p1.is_float_or_int() {
-        let p1val = p1.to_float();
-        Ok(Value::Float(p1val.cos()))
+    else if p1.is_decimal() {
+        decimal_result(p1.to_decimal().unwrap().round(places))
+    }
+    else if p1.is_float() {
+        let factor = 10f64.powi(places as i32);
+        let scaled = p1.to_float() * factor;
+        //above 2^52 a float has no digits after the point, rounding would only overflow
+        if !scaled.is_finite() || scaled.abs() >= 4503599627370496.0 {
+            return Ok(p1)
+        }
+        Ok(Value::Float(scaled.round() / factor))
     }
     else {
-        throw_invalid_type!("cos", &p1.type_str())
+        throw_invalid_type!("round", &p1.type_str(), &p2.type_str())
     }
 }
 
-fn cosh(valstack: &mut Vec<Value>) -> Result<Value, String> {
-    check_one("cosh", valstack)?;
+fn abs(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    check_one("abs", valstack)?;
     let p1 = pop!(valstack);
-    if p1.is_float_or_int() {
-        let p1val = p1.to_float();
-        Ok(Value::Float(p1val.cosh()))
+    if p1.is_int() {
+        let p1val = p1.to_int();
+        Ok(Value::Integer(p1val.abs()))
     }
-    else {
-        throw_invalid_type!("cosh", &p1.type_str())
+    else if let Value::Interval(p1val) = p1 {
+        Ok(Value::Interval(p1val.abs()))
     }
-}
-
-fn tan(valstack: &mut Vec<Value>) -> Result<Value, String> {
-    check_one("tan", valstack)?;
-    let p1 = pop!(valstack);
-    if p1.is_float_or_int() {
-        let p1val = p1.to_float();
-        Ok(Value::Float(p1val.tan()))
+    else if p1.is_bigfloat() {
+        let p1val = p1.to_bigfloat().unwrap();
+        Ok(Value::BigFloat(p1val.abs()))
     }
-    else {
-        throw_invalid_type!("tan", &p1.type_str())
+    else if p1.is_decimal() {
+        let p1val = p1.to_decimal().unwrap();
+        Ok(Value::Decimal(p1val.abs()))
     }
-}
-
-fn tanh(valstack: &mut Vec<Value>) -> Result<Value, String> {
-    check_one("tanh", valstack)?;
-    let p1 = pop!(valstack);
-    if p1.is_float_or_int() {
+    else if p1.is_float() {
         let p1val = p1.to_float();
-        Ok(Value::Float(p1val.tanh()))
+        Ok(Value::Float(p1val.abs()))
     }
     else {
-        throw_invalid_type!("tanh", &p1.type_str())
+        throw_invalid_type!("abs", &p1.type_str())
     }
 }
 
@@ -173,94 +263,93 @@ fn negate(valstack: &mut Vec<Value>) -> Result<Value, String> {
         let p1val = p1.to_int();
         Ok(Value::Integer(-p1val))
     }
+    else if let Value::Interval(p1val) = p1 {
+        Ok(Value::Interval(p1val.neg()))
+    }
+    else if p1.is_bigfloat() {
+        let p1val = p1.to_bigfloat().unwrap();
+        Ok(Value::BigFloat(p1val.neg()))
+    }
+    else if p1.is_decimal() {
+        let p1val = p1.to_decimal().unwrap();
+        Ok(Value::Decimal(p1val.neg()))
+    }
     else if p1.is_float() {
         let p1val = p1.to_float();
         Ok(Value::Float(-p1val))
     }
+    else if let Some(v) = datetime::negate(&p1) {
+        Ok(v)
+    }
     else {
         throw_invalid_type!("neg", &p1.type_str())
     }
 }
 
-fn sqrt(valstack: &mut Vec<Value>) -> Result<Value, String> {
-    check_one("sqrt", valstack)?;
-    let p1 = pop!(valstack);
-    if p1.is_float_or_int() {
-        let p1val = p1.to_float();
-        if p1val < 0.0 {
-            return Err(format!("square root of a negative number"))
-        }
-        Ok(Value::Float(p1val.sqrt()))
+fn sqrt(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    if valstack.last().map(|x| x.is_number() && x.to_float() < 0.0).unwrap_or(false) {
+        return Err(format!("square root of a negative number"))
     }
-    else {
-        throw_invalid_type!("sqrt", &p1.type_str())
+    float_fn("sqrt", valstack, engine, f64::sqrt, BigFloat::sqrt)
+}
+
+fn ln(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    if valstack.last().map(|x| x.is_float_or_int() && x.to_float() <= 0.0).unwrap_or(false) {
+        return Err(format!("logarithm of a non-positive number"))
     }
+    float_fn("ln", valstack, engine, f64::ln, BigFloat::ln)
 }
 
 fn equals(valstack: &mut Vec<Value>) -> Result<Value, String> {
-    check_two("equals", valstack)?;
-    let p2 = pop!(valstack);
-    let p1 = pop!(valstack);
-    if p1.is_int() && p2.is_int() {
-        let p1val = p1.to_int();
-        let p2val = p2.to_int();
-        if p1val == p2val {
-            Ok(Value::Integer(1))
-        }
-        else {
-            Ok(Value::Integer(0))
-        }
+    if let Some(result) = compare_intervals("equals", valstack, |a, b| a.equal(b))? {
+        return Ok(result)
     }
-    else if p1.is_float() && p2.is_float() {
-        let p1val = p1.to_float();
-        let p2val = p2.to_float();
-        if p1val == p2val {
-            Ok(Value::Integer(1))
-        }
-        else {
-            Ok(Value::Integer(0))
-        }
+    let ordering = compare("equals", valstack)?;
+    Ok(truth(ordering == Some(Ordering::Equal)))
+}
+
+fn lesserthan(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    if let Some(result) = compare_intervals("lt", valstack, |a, b| a.less(b))? {
+        return Ok(result)
     }
-    else {
-        throw_invalid_type!("equals", &p1.type_str(), &p2.type_str())
+    let ordering = compare("lt", valstack)?;
+    Ok(truth(ordering == Some(Ordering::Less)))
+}
+
+fn greaterthan(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    if let Some(result) = compare_intervals("gt", valstack, |a, b| b.less(a))? {
+        return Ok(result)
     }
+    let ordering = compare("gt", valstack)?;
+    Ok(truth(ordering == Some(Ordering::Greater)))
 }
 
-fn lesserthan(valstack: &mut Vec<Value>) -> Result<Value, String> {
-    check_two("lt", valstack)?;
+//pm(x, dx) alias x ± dx
+fn plusminus(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    check_two("pm", valstack)?;
     let p2 = pop!(valstack);
     let p1 = pop!(valstack);
-    if p1.is_float_or_int() && p2.is_float_or_int() {
-        let p1val = p1.to_float();
-        let p2val = p2.to_float();
-        if p1val < p2val {
-            Ok(Value::Integer(1))
-        }
-        else {
-            Ok(Value::Integer(0))
+    if p1.is_number() && p2.is_number() {
+        if p2.to_float() < 0.0 || p2.to_float().is_nan() {
+            return Err(format!("pm: the uncertainty must not be negative, found {}", p2))
         }
+        Ok(Value::Interval(Interval::new(p1.to_float(), p2.to_float())))
     }
     else {
-        throw_invalid_type!("lt", &p1.type_str(), &p2.type_str())
+        throw_invalid_type!("pm", &p1.type_str(), &p2.type_str())
     }
 }
 
-fn greaterthan(valstack: &mut Vec<Value>) -> Result<Value, String> {
-    check_two("gt", valstack)?;
+//interval(a, b): all numbers between a and b
+fn interval(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    check_two("interval", valstack)?;
     let p2 = pop!(valstack);
     let p1 = pop!(valstack);
-    if p1.is_float_or_int() && p2.is_float_or_int() {
-        let p1val = p1.to_float();
-        let p2val = p2.to_float();
-        if p1val > p2val {
-            Ok(Value::Integer(1))
-        }
-        else {
-            Ok(Value::Integer(0))
-        }
+    if p1.is_number() && p2.is_number() {
+        Ok(Value::Interval(Interval::bounds(p1.to_float(), p2.to_float())))
     }
     else {
-        throw_invalid_type!("gt", &p1.type_str(), &p2.type_str())
+        throw_invalid_type!("interval", &p1.type_str(), &p2.type_str())
     }
 }
 
@@ -282,16 +371,34 @@ fn fnif(valstack: &mut Vec<Value>) -> Result<Value, String> {
     }
 }
 
-fn add(valstack: &mut Vec<Value>) -> Result<Value, String> {
+fn add(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
     check_two("add", valstack)?;
     let p2 = pop!(valstack);
     let p1 = pop!(valstack);
+    if let Some(result) = datetime::arith("add", &p1, &p2) {
+        return result
+    }
+    if let Some(result) = strings::concat(&p1, &p2) {
+        return result
+    }
     if p1.is_int() && p2.is_int() {
         let p1val = p1.to_int();
         let p2val = p2.to_int();
-        Ok(Value::Integer(p1val + p2val))
+        match p1val.checked_add(p2val) {
+            Some(v) => Ok(Value::Integer(v)),
+            None => Err(format!("integer overflow in function 'add'"))
+        }
+    }
+    else if let Some((p1val, p2val)) = both_interval(&p1, &p2) {
+        Ok(Value::Interval(p1val.add(&p2val)))
+    }
+    else if let Some((p1val, p2val)) = both_bigfloat(&p1, &p2) {
+        Ok(Value::BigFloat(p1val.add(&p2val, engine.precision())))
     }
-    else if p1.is_float_or_int() && p2.is_float_or_int(){
+    else if let Some((p1val, p2val)) = both_decimal(&p1, &p2) {
+        decimal_result(p1val.add(p2val))
+    }
+    else if p1.is_number() && p2.is_number() {
         let p1val = p1.to_float();
         let p2val = p2.to_float();
         Ok(Value::Float(p1val + p2val))
@@ -301,16 +408,31 @@ fn add(valstack: &mut Vec<Value>) -> Result<Value, String> {
     }
 }
 
-fn sub(valstack: &mut Vec<Value>) -> Result<Value, String> {
+fn sub(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
     check_two("sub", valstack)?;
     let p2 = pop!(valstack);
     let p1 = pop!(valstack);
+    if let Some(result) = datetime::arith("sub", &p1, &p2) {
+        return result
+    }
     if p1.is_int() && p2.is_int() {
         let p1val = p1.to_int();
         let p2val = p2.to_int();
-        Ok(Value::Integer(p1val - p2val))
+        match p1val.checked_sub(p2val) {
+            Some(v) => Ok(Value::Integer(v)),
+            None => Err(format!("integer overflow in function 'sub'"))
+        }
+    }
+    else if let Some((p1val, p2val)) = both_interval(&p1, &p2) {
+        Ok(Value::Interval(p1val.sub(&p2val)))
+    }
+    else if let Some((p1val, p2val)) = both_bigfloat(&p1, &p2) {
+        Ok(Value::BigFloat(p1val.sub(&p2val, engine.precision())))
+    }
+    else if let Some((p1val, p2val)) = both_decimal(&p1, &p2) {
+        decimal_result(p1val.sub(p2val))
     }
-    else if p1.is_float_or_int() && p2.is_float_or_int(){
+    else if p1.is_number() && p2.is_number() {
         let p1val = p1.to_float();
         let p2val = p2.to_float();
         Ok(Value::Float(p1val - p2val))
@@ -320,16 +442,31 @@ fn sub(valstack: &mut Vec<Value>) -> Result<Value, String> {
     }
 }
 
-fn mul(valstack: &mut Vec<Value>) -> Result<Value, String> {
+fn mul(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
     check_two("mul", valstack)?;
     let p2 = pop!(valstack);
     let p1 = pop!(valstack);
+    if let Some(result) = datetime::arith("mul", &p1, &p2) {
+        return result
+    }
     if p1.is_int() && p2.is_int() {
         let p1val = p1.to_int();
         let p2val = p2.to_int();
-        Ok(Value::Integer(p1val * p2val))
+        match p1val.checked_mul(p2val) {
+            Some(v) => Ok(Value::Integer(v)),
+            None => Err(format!("integer overflow in function 'mul'"))
+        }
+    }
+    else if let Some((p1val, p2val)) = both_interval(&p1, &p2) {
+        Ok(Value::Interval(p1val.mul(&p2val)))
     }
-    else if p1.is_float_or_int() && p2.is_float_or_int(){
+    else if let Some((p1val, p2val)) = both_bigfloat(&p1, &p2) {
+        Ok(Value::BigFloat(p1val.mul(&p2val, engine.precision())))
+    }
+    else if let Some((p1val, p2val)) = both_decimal(&p1, &p2) {
+        decimal_result(p1val.mul(p2val, engine.decimal_places()))
+    }
+    else if p1.is_number() && p2.is_number() {
         let p1val = p1.to_float();
         let p2val = p2.to_float();
         Ok(Value::Float(p1val * p2val))
@@ -339,11 +476,37 @@ fn mul(valstack: &mut Vec<Value>) -> Result<Value, String> {
     }
 }
 
-fn div(valstack: &mut Vec<Value>) -> Result<Value, String> {
+fn div(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
     check_two("div", valstack)?;
     let p2 = pop!(valstack);
     let p1 = pop!(valstack);
-    if p1.is_float_or_int() && p2.is_float_or_int(){
+    if let Some(result) = datetime::arith("div", &p1, &p2) {
+        return result
+    }
+    //in decimal mode 1/3 is a decimal as well, in bigfloat mode a bigfloat
+    let ints = p1.is_int() && p2.is_int();
+    let bigfloat = p1.is_bigfloat() || p2.is_bigfloat() || (engine.numeric() == NumericMode::BigFloat && ints);
+    let decimal = p1.is_decimal() || p2.is_decimal() || (engine.numeric() == NumericMode::Decimal && ints);
+    if let Some((p1val, p2val)) = both_interval(&p1, &p2) {
+        interval_result(p1val.div(&p2val))
+    }
+    else if bigfloat && p1.is_number() && p2.is_number() {
+        let p1val = p1.to_bigfloat().unwrap();
+        let p2val = p2.to_bigfloat().unwrap();
+        match p1val.div(&p2val, engine.precision()) {
+            Some(v) => Ok(Value::BigFloat(v)),
+            None => Err(format!("divide by zero"))
+        }
+    }
+    else if decimal && p1.to_decimal().is_some() && p2.to_decimal().is_some() {
+        let p1val = p1.to_decimal().unwrap();
+        let p2val = p2.to_decimal().unwrap();
+        if p2val.is_zero() {
+            return Err(format!("divide by zero"));
+        }
+        decimal_result(p1val.div(p2val, engine.decimal_places()))
+    }
+    else if p1.is_number() && p2.is_number() {
         let p1val = p1.to_float();
         let p2val = p2.to_float();
         if p2val == 0.0 {
@@ -356,25 +519,81 @@ fn div(valstack: &mut Vec<Value>) -> Result<Value, String> {
     }
 }
 
-fn pow(valstack: &mut Vec<Value>) -> Result<Value, String> {
+fn pow(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
     check_two("pow", valstack)?;
     let p2 = pop!(valstack);
     let p1 = pop!(valstack);
+    let places = engine.decimal_places();
     if p1.is_int() && p2.is_int() {
         let p1val = p1.to_int();
         let p2val = p2.to_int();
-        Ok(Value::Integer(p1val.pow(p2val as u32)))
+        if p2val < 0 && engine.numeric() == NumericMode::Decimal {
+            if p1val == 0 {
+                return Err(format!("divide by zero"));
+            }
+            return decimal_result(Decimal::from_int(p1val).powi(p2val, places))
+        }
+        if p2val < 0 && engine.numeric() == NumericMode::BigFloat {
+            return match BigFloat::from_int(p1val).powi(p2val, engine.precision()) {
+                Some(v) => Ok(Value::BigFloat(v)),
+                None => Err(format!("divide by zero"))
+            }
+        }
+        if p2val < 0 {
+            return Ok(Value::Float((p1val as f64).powi(p2val as i32)))
+        }
+        match p1val.checked_pow(p2val as u32) {
+            Some(v) => Ok(Value::Integer(v)),
+            None if engine.numeric() == NumericMode::BigFloat => {
+                Ok(Value::BigFloat(BigFloat::from_int(p1val).powi(p2val, engine.precision()).unwrap()))
+            },
+            None => Err(format!("integer overflow in function 'pow'"))
+        }
     }
-    else if p1.is_float() && p2.is_float(){
-        let p1val = p1.to_float();
-        let p2val = p2.to_float();
-        Ok(Value::Float(p1val.powf(p2val)))
+    else if let Some((p1val, p2val)) = both_interval(&p1, &p2) {
+        interval_result(p1val.pow(&p2val))
+    }
+    else if let Some((p1val, p2val)) = both_bigfloat(&p1, &p2) {
+        let precision = engine.precision();
+        let result = if p2val.floor() == p2val && p2val.abs().compare(&BigFloat::from_int(1 << 32)) == Ordering::Less {
+            if p1val.is_zero() && p2val.is_negative() {
+                return Err(format!("divide by zero"));
+            }
+            p1val.powi(p2val.to_i64(), precision)
+        }
+        else {
+            p1val.powf(&p2val, precision)
+        };
+        match result {
+            Some(v) => Ok(Value::BigFloat(v)),
+            None => Err(format!("invalid arguments in function 'pow'"))
+        }
+    }
+    else if let Some((p1val, p2val)) = both_decimal(&p1, &p2) {
+        if p2val.floor() == p2val {
+            if p1val.is_zero() && p2val.is_negative() {
+                return Err(format!("divide by zero"));
+            }
+            decimal_result(p1val.powi(p2val.to_i64(), places))
+        }
+        else {
+            //like sqrt, computed with more digits than a decimal has
+            match p1.to_bigfloat().unwrap().powf(&p2.to_bigfloat().unwrap(), DECIMAL_DIGITS) {
+                Some(v) => decimal_result(Decimal::parse(&format!("{}", v.round_places(places)))),
+                None => Err(format!("invalid arguments in function 'pow'"))
+            }
+        }
     }
-    else if p1.is_float() && p2.is_int() {
+    else if p1.is_number() && p2.is_int() {
         let p1val = p1.to_float();
         let p2val = p2.to_int();
         Ok(Value::Float(p1val.powi(p2val as i32)))
     }
+    else if p1.is_number() && p2.is_number() {
+        let p1val = p1.to_float();
+        let p2val = p2.to_float();
+        Ok(Value::Float(p1val.powf(p2val)))
+    }
     else {
         throw_invalid_type!("pow", &p1.type_str(), &p2.type_str())
     }
@@ -382,7 +601,132 @@ fn pow(valstack: &mut Vec<Value>) -> Result<Value, String> {
 
 //UTILS
 
-fn check_n(count: usize, fnname: &str, valstack: &mut Vec<Value>) -> Result<(), String> {
+//floats stay floats, decimals and bigfloats (or integers in those modes) give decimals and bigfloats,
+//intervals give the interval of all results
+fn float_fn(fnname: &str, valstack: &mut Vec<Value>, engine: &Engine, f: fn(f64) -> f64,
+        big: fn(&BigFloat, u32) -> Option<BigFloat>) -> Result<Value, String> {
+    check_one(fnname, valstack)?;
+    let p1 = pop!(valstack);
+    if let Value::Interval(p1val) = p1 {
+        interval_result(p1val.apply(fnname))
+    }
+    else if p1.is_bigfloat() || (engine.numeric() == NumericMode::BigFloat && p1.is_int()) {
+        match big(&p1.to_bigfloat().unwrap(), engine.precision()) {
+            Some(v) => Ok(Value::BigFloat(v)),
+            None => Err(format!("invalid argument in function '{}'", fnname))
+        }
+    }
+    else if p1.is_decimal() || (engine.numeric() == NumericMode::Decimal && p1.is_int()) {
+        //with more digits than a decimal has, so the result is rounded correctly to the decimal places
+        match big(&p1.to_bigfloat().unwrap(), DECIMAL_DIGITS) {
+            Some(v) => decimal_result(Decimal::parse(&format!("{}", v.round_places(engine.decimal_places())))),
+            None => Err(format!("invalid argument in function '{}'", fnname))
+        }
+    }
+    else if p1.is_float_or_int() {
+        Ok(Value::Float(f(p1.to_float())))
+    }
+    else {
+        throw_invalid_type!(fnname, &p1.type_str())
+    }
+}
+
+//both operands as intervals, if one of them is an interval and the other a number
+fn both_interval(p1: &Value, p2: &Value) -> Option<(Interval, Interval)> {
+    if p1.is_interval() || p2.is_interval() {
+        Some((p1.to_interval()?, p2.to_interval()?))
+    }
+    else {
+        None
+    }
+}
+
+fn interval_result(result: Result<Interval, String>) -> Result<Value, String> {
+    result.map(Value::Interval)
+}
+
+//Some(1 or 0) if one of the operands is an interval, an error if the intervals overlap
+fn compare_intervals(fnname: &str, valstack: &mut Vec<Value>, f: fn(&Interval, &Interval) -> Option<bool>) -> Result<Option<Value>, String> {
+    check_two(fnname, valstack)?;
+    let operands = match both_interval(&valstack[valstack.len() - 2], &valstack[valstack.len() - 1]) {
+        Some(v) => v,
+        None => return Ok(None)
+    };
+    valstack.truncate(valstack.len() - 2);
+    match f(&operands.0, &operands.1) {
+        Some(result) => Ok(Some(truth(result))),
+        None => Err(format!("undecidable comparison in function '{}': the intervals overlap", fnname))
+    }
+}
+
+//both operands as bigfloats, if one of them is a bigfloat
+fn both_bigfloat(p1: &Value, p2: &Value) -> Option<(BigFloat, BigFloat)> {
+    if p1.is_bigfloat() || p2.is_bigfloat() {
+        Some((p1.to_bigfloat()?, p2.to_bigfloat()?))
+    }
+    else {
+        None
+    }
+}
+
+//both operands as decimals, if one of them is a decimal and the other is no float
+fn both_decimal(p1: &Value, p2: &Value) -> Option<(Decimal, Decimal)> {
+    if p1.is_decimal() || p2.is_decimal() {
+        Some((p1.to_decimal()?, p2.to_decimal()?))
+    }
+    else {
+        None
+    }
+}
+
+fn decimal_result(result: Option<Decimal>) -> Result<Value, String> {
+    match result {
+        Some(v) => Ok(Value::Decimal(v)),
+        None => Err(format!("decimal overflow"))
+    }
+}
+
+//None if one of the values is NaN
+fn compare(fnname: &str, valstack: &mut Vec<Value>) -> Result<Option<Ordering>, String> {
+    check_two(fnname, valstack)?;
+    let p2 = pop!(valstack);
+    let p1 = pop!(valstack);
+    if let Some(result) = datetime::compare(fnname, &p1, &p2) {
+        return result.map(Some)
+    }
+    if let (&Value::Str(ref a), &Value::Str(ref b)) = (&p1, &p2) {
+        return Ok(Some(a.cmp(b)))
+    }
+    if p1.is_int() && p2.is_int() {
+        Ok(Some(p1.to_int().cmp(&p2.to_int())))
+    }
+    else if let Some((p1val, p2val)) = both_bigfloat(&p1, &p2) {
+        Ok(Some(p1val.compare(&p2val)))
+    }
+    else if let Some((p1val, p2val)) = both_decimal(&p1, &p2) {
+        match p1val.compare(&p2val) {
+            Some(ordering) => Ok(Some(ordering)),
+            None => Err(format!("decimal overflow"))
+        }
+    }
+    else if p1.is_number() && p2.is_number() {
+        Ok(p1.to_float().partial_cmp(&p2.to_float()))
+    }
+    else {
+        throw_invalid_type!(fnname, &p1.type_str(), &p2.type_str())
+    }
+}
+
+fn truth(condition: bool) -> Value {
+    if condition {
+        Value::Integer(1)
+    }
+    else {
+        Value::Integer(0)
+    }
+}
+
+pub fn check_n(count: usize, fnname: &str, valstack: &mut Vec<Value>) -> Result<(), String> {
     if valstack.len() < count {
         Err(format!("expected {} arguments in function '{}'", count, fnname))
     }
@@ -391,6 +735,13 @@ fn check_n(count: usize, fnname: &str, valstack: &mut Vec<Value>) -> Result<(),
     }
 }
 
+//pops count arguments, in argument order
+pub fn pop_args(fnname: &str, count: usize, valstack: &mut Vec<Value>) -> Result<Vec<Value>, String> {
+    check_n(count, fnname, valstack)?;
+    let index = valstack.len() - count;
+    Ok(valstack.split_off(index))
+}
+
 fn check_two(fnname: &str, valstack: &mut Vec<Value>) -> Result<(), String> {
     check_n(2, fnname, valstack)
 }
@@ -0,0 +1,176 @@
+use value::Value;
+use engine::Engine;
+use stdlib;
+
+//str(x): x as it would be typed in, strings stay the same
+pub fn str(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    stdlib::check_n(1, "str", valstack)?;
+    Ok(Value::Str(text(&valstack.pop().unwrap())))
+}
+
+//num(s): the number in s, read like a number literal
+pub fn num(valstack: &mut Vec<Value>, engine: &Engine) -> Result<Value, String> {
+    stdlib::check_n(1, "num", valstack)?;
+    let src = match valstack.pop().unwrap() {
+        Value::Str(s) => s,
+        v => return Err(format!("invalid types in function 'num': {}", v.type_str()))
+    };
+    match Value::parse(src.trim(), engine.numeric()) {
+        Ok(v) if v.is_number() => Ok(v),
+        _ => Err(format!("cannot read '{}' as a number", src))
+    }
+}
+
+//format(template, x): '{}' is replaced by x and '{:.2}' by x with 2 decimal places,
+//a template with several placeholders takes a list, '{{' and '}}' are braces
+pub fn format(valstack: &mut Vec<Value>) -> Result<Value, String> {
+    stdlib::check_n(2, "format", valstack)?;
+    let args = valstack.pop().unwrap();
+    let template = match valstack.pop().unwrap() {
+        Value::Str(s) => s,
+        v => return Err(format!("invalid types in function 'format': {} {}", v.type_str(), args.type_str()))
+    };
+    let specs = placeholders(&template)?;
+    let mut values = match args {
+        Value::List(ref items) if specs.len() != 1 => items.clone(),
+        _ => vec![args.clone()]
+    };
+    if values.len() != specs.len() {
+        return Err(format!("format: {} placeholders, but {} values", specs.len(), values.len()))
+    }
+    values.reverse();
+    let mut result = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                result.push('}');
+            },
+            '{' => {
+                let spec = chars.by_ref().take_while(|&x| x != '}').collect::<String>();
+                result.push_str(&format_value(&spec, &values.pop().unwrap())?);
+            },
+            _ => result.push(ch)
+        }
+    }
+    Ok(Value::Str(result))
+}
+
+//the text of a value without quotes, as printed by ':print'
+pub fn text(val: &Value) -> String {
+    match *val {
+        Value::Str(ref s) => s.clone(),
+        _ => val.literal()
+    }
+}
+
+//'a' + 'b'
+pub fn concat(p1: &Value, p2: &Value) -> Option<Result<Value, String>> {
+    match (p1, p2) {
+        (&Value::Str(ref a), &Value::Str(ref b)) => Some(Ok(Value::Str(format!("{}{}", a, b)))),
+        (&Value::Str(_), _) | (_, &Value::Str(_)) => {
+            Some(Err(format!("invalid types in function 'add': {} {} (use str() to convert)", p1.type_str(), p2.type_str())))
+        },
+        _ => None
+    }
+}
+
+//the specs between the braces, e.g. ['', ':.2']
+fn placeholders(template: &str) -> Result<Vec<String>, String> {
+    let mut result = Vec::new();
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+            },
+            '{' => {
+                let mut spec = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => spec.push(c),
+                        None => return Err(format!("format: unclosed '{{'"))
+                    }
+                }
+                result.push(spec);
+            },
+            '}' => return Err(format!("format: unmatched '}}'")),
+            _ => {}
+        }
+    }
+    Ok(result)
+}
+
+fn format_value(spec: &str, val: &Value) -> Result<String, String> {
+    if spec.is_empty() {
+        return Ok(text(val))
+    }
+    let places = match spec.trim_start_matches(":.").parse::<usize>() {
+        Ok(v) if spec.starts_with(":.") => v,
+        _ => return Err(format!("format: unknown placeholder '{{{}}}'", spec))
+    };
+    match *val {
+        Value::Integer(_) | Value::Float(_) => Ok(format!("{:.*}", places, val.to_float())),
+        Value::Decimal(v) => match v.round(places as u32) {
+            Some(v) => Ok(pad_places(format!("{}", v), places)),
+            None => Err(format!("decimal overflow"))
+        },
+        Value::BigFloat(ref v) => Ok(pad_places(format!("{}", v.round_places(places as u32)), places)),
+        Value::Interval(v) => Ok(format!("{:.*} ± {:.*}", places, v.mid(), places, v.radius())),
+        _ => Err(format!("format: '{{{}}}' needs a number, not a {}", spec, val.type_str()))
+    }
+}
+
+//1.5 with 3 places is 1.500
+fn pad_places(mut number: String, places: usize) -> String {
+    if places == 0 {
+        return number
+    }
+    let current = match number.find('.') {
+        Some(index) => number.len() - index - 1,
+        None => {
+            number.push('.');
+            0
+        }
+    };
+    for _ in current..places {
+        number.push('0');
+    }
+    number
+}
+
+#[cfg(test)]
+mod tests {
+    use engine::Engine;
+    use testing::run;
+
+    #[test]
+    fn strings() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "format(\"{:.2} m\", 3.14159)"), "\"3.14 m\" [string]");
+        assert_eq!(run(&mut engine, "format(\"{} of {}\", [1, 2])"), "\"1 of 2\" [string]");
+        assert_eq!(run(&mut engine, "format(\"{{}} {}\", 1)"), "\"{} 1\" [string]");
+        assert_eq!(run(&mut engine, "len(\"héllo\")"), "5 [int]");
+        assert_eq!(run(&mut engine, "num(\"42\")"), "42 [int]");
+        assert_eq!(run(&mut engine, "\"a\\\"b\\nc\""), "\"a\\\"b\\nc\" [string]");
+        assert_eq!(run(&mut engine, "\"abc\" < \"abd\""), "1 [int]");
+        assert_eq!(run(&mut engine, ":print \"Total: \" + str(2*21)"), "Total: 42");
+    }
+
+    #[test]
+    fn errors() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "format(\"{} {}\", 1)"), "error: format: 2 placeholders, but 1 values");
+        assert_eq!(run(&mut engine, "num(\"abc\")"), "error: cannot read 'abc' as a number");
+        assert_eq!(run(&mut engine, "\"a\" + 1"), "error: invalid types in function 'add': string integer (use str() to convert)");
+    }
+}
@@ -0,0 +1,33 @@
+//helpers shared by the tests of the modules
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use engine::{Engine, LineResult};
+
+//the result of a line as the terminal shows it, errors start with 'error: '
+pub fn run(engine: &mut Engine, line: &str) -> String {
+    match engine.eval_line(line.to_owned()) {
+        LineResult::Value(v) => format!("{}", v),
+        LineResult::Error(e) => format!("error: {}", e),
+        LineResult::Success => engine.take_output().join("\n")
+    }
+}
+
+
+//a file in the temporary directory, the test removes it again
+pub fn temp_file(name: &str, text: &str) -> String {
+    let path = env::temp_dir().join(format!("termcalc-test-{}-{}", process::id(), name));
+    fs::write(&path, text).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+//an empty directory in the temporary directory, the test removes it again
+pub fn temp_dir(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("termcalc-test-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    path
+}
@@ -1,10 +1,14 @@
-use std::str::FromStr;
+use std::collections::HashMap;
 
-use value::Value;
+use value::{Value, NumericMode};
+use datetime::{DateTime, Duration};
+use function::{Closure, Function};
+use interpreter;
+use stdlib;
 
-pub const SPACED_OPS: [char; 11] = ['+', '-', '*', '/', '(', ')', ',', '^', '=', '<', '>'];
-pub const OPS: [Token; 9] = [Token::Add, Token::Sub, Token::Mul, Token::UnarySub,
-                        Token::Div, Token::Pow, Token::Equals, Token::GreaterThan, Token::LesserThan];
+pub const SPACED_OPS: [char; 15] = ['+', '-', '*', '/', '(', ')', ',', '^', '=', '<', '>', '[', ']', '±', '→'];
+pub const OPS: [Token; 10] = [Token::Add, Token::Sub, Token::Mul, Token::UnarySub,
+                        Token::Div, Token::Pow, Token::Equals, Token::GreaterThan, Token::LesserThan, Token::PlusMinus];
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
@@ -15,15 +19,45 @@ pub enum Token {
     Mul,
     Div,
     Pow,
+    PlusMinus,
     BrOpen,
     BrClose,
+    ListOpen,
+    ListClose,
     Seperator,
+    //'->' of a function literal, only until tokenize has built the function
+    Arrow,
     Equals,
     GreaterThan,
     LesserThan,
     Value(Value),
     Variable(String),
-    Function(String)
+    Function(String),
+    //'let' and 'in', only until tokenize has built the let expression
+    Keyword(String),
+    Let(Let),
+    If(If),
+    //a call of the function value in a variable, with the number of arguments: F(1, 2)
+    Call(String, usize),
+    //a call of the function value in the brackets before, with the number of arguments: (X -> X^2)(3)
+    Apply(usize)
+}
+
+//'let A = 1, B = A + 1 in A*B', the code of the values and of the body in postfix order
+#[derive(Debug, PartialEq, Clone)]
+pub struct Let {
+    pub bindings: Vec<(String, Vec<Token>)>,
+    pub body: Vec<Token>,
+    pub text: String
+}
+
+//if(c, a, b), only the code of the branch that is taken is run
+#[derive(Debug, PartialEq, Clone)]
+pub struct If {
+    pub condition: Vec<Token>,
+    pub then: Vec<Token>,
+    pub otherwise: Vec<Token>,
+    pub text: String
 }
 
 impl Token {
@@ -38,12 +72,14 @@ impl Token {
             Token::Equals => 5,
             Token::GreaterThan => 5,
             Token::LesserThan => 5,
+            //9.81 ± 0.02 is a single value
+            Token::PlusMinus => 6,
             _ => 0
         }
     }
 
     pub fn left_assoc(&self) -> bool {
-        *self != Token::Pow
+        *self != Token::Pow && *self != Token::UnarySub
     }
 }
 
@@ -58,17 +94,38 @@ pub fn function_of_token(token: Token) -> String {
         Token::Equals => String::from("eq"),
         Token::GreaterThan => String::from("gt"),
         Token::LesserThan => String::from("lt"),
+        Token::PlusMinus => String::from("pm"),
         _ => panic!()
     }
 }
 
-pub fn tokenize(mut line: String) -> Vec<Token> {
+pub fn token_of_function(name: &str) -> Option<Token> {
+    match name {
+        "add" => Some(Token::Add),
+        "sub" => Some(Token::Sub),
+        "neg" => Some(Token::UnarySub),
+        "mul" => Some(Token::Mul),
+        "div" => Some(Token::Div),
+        "pow" => Some(Token::Pow),
+        "eq" => Some(Token::Equals),
+        "gt" => Some(Token::GreaterThan),
+        "lt" => Some(Token::LesserThan),
+        "pm" => Some(Token::PlusMinus),
+        _ => None
+    }
+}
+
+//functions are the user functions, their names can be passed as values
+pub fn tokenize(line: String, mode: NumericMode, functions: &HashMap<String, Function>) -> Result<Vec<Token>, String> {
     let mut result = Vec::new();
-    line = space_ops(line);
     let mut enclose_next = false; //set () around next token for [fn]!
     let mut last_token = Token::Add;
-    for word in line.split_whitespace() {
-        let token = match_token(word, last_token);
+    for word in words(&line) {
+        let word = word.as_str();
+        if DateTime::is_invalid(word) {
+            return Err(format!("invalid date/time: {}", word))
+        }
+        let token = match_token(word, last_token, mode);
         if enclose_next {
             result.push(Token::BrOpen);
             result.push(token.clone());
@@ -83,15 +140,295 @@ pub fn tokenize(mut line: String) -> Vec<Token> {
             enclose_next = true;
         }
     }
+    Ok(make_compounds(mark_calls(mark_fn_refs(merge_durations(result)?, functions))))
+}
+
+//function literals, let expressions and if() calls, the innermost (last) one first,
+//so that their parts contain no other ones
+fn make_compounds(mut token: Vec<Token>) -> Vec<Token> {
+    let mut limit = token.len();
+    while let Some(index) = (0..limit).rev().find(|&index| is_compound_start(&token[index])) {
+        let success = match token[index] {
+            Token::Arrow => make_closure(&mut token, index),
+            Token::Keyword(_) => make_let(&mut token, index),
+            _ => make_if(&mut token, index)
+        };
+        //the tokens of a malformed one are left for the parser to report
+        limit = if success { token.len().min(index + 1) } else { index };
+    }
+    token
+}
+
+fn is_compound_start(token: &Token) -> bool {
+    match *token {
+        Token::Arrow => true,
+        Token::Keyword(ref word) => word == "let",
+        Token::Function(ref name) => name == "if",
+        _ => false
+    }
+}
+
+//'X -> X^2' and '(X, Y) -> X*Y' become function values, the body reaches to the next ',' or closing bracket
+fn make_closure(token: &mut Vec<Token>, arrow: usize) -> bool {
+    let (start, args) = match closure_args(&token[..arrow]) {
+        Some(v) => v,
+        None => return false
+    };
+    let end = expr_end(token, arrow + 1, false);
+    let code = match compile(&token[arrow + 1..end]) {
+        Some(v) => v,
+        None => return false
+    };
+    let closure = Closure::new(args, code, text(&token[arrow + 1..end]));
+    token.splice(start..end, Some(Token::Value(Value::Function(closure))));
+    true
+}
+
+//'let A = 1, B = A + 1 in A*B', the body reaches to the next ',' or closing bracket like the body of a function literal
+fn make_let(token: &mut Vec<Token>, start: usize) -> bool {
+    let mut bindings = Vec::new();
+    let mut index = start + 1;
+    loop {
+        let name = match (token.get(index), token.get(index + 1)) {
+            (Some(&Token::Variable(ref name)), Some(&Token::Equals)) => name.clone(),
+            _ => return false
+        };
+        let end = expr_end(token, index + 2, true);
+        match compile(&token[index + 2..end]) {
+            Some(code) => bindings.push((name, code)),
+            None => return false
+        }
+        index = end + 1;
+        match token.get(end) {
+            Some(&Token::Seperator) => {},
+            Some(&Token::Keyword(ref word)) if word == "in" => break,
+            _ => return false
+        }
+    }
+    let end = expr_end(token, index, false);
+    let body = match compile(&token[index..end]) {
+        Some(v) => v,
+        None => return false
+    };
+    let text = text(&token[start..end]).trim().to_owned();
+    token.splice(start..end, Some(Token::Let(Let { bindings: bindings, body: body, text: text })));
+    true
+}
+
+//'if(c, a, b)' runs only the branch that is taken
+fn make_if(token: &mut Vec<Token>, start: usize) -> bool {
+    if token.get(start + 1) != Some(&Token::BrOpen) {
+        return false
+    }
+    let mut parts = Vec::new();
+    let mut index = start + 2;
+    loop {
+        let end = expr_end(token, index, false);
+        match compile(&token[index..end]) {
+            Some(code) => parts.push(code),
+            None => return false
+        }
+        index = end + 1;
+        match token.get(end) {
+            Some(&Token::Seperator) => {},
+            Some(&Token::BrClose) => break,
+            _ => return false
+        }
+    }
+    if parts.len() != 3 {
+        return false
+    }
+    let text = text(&token[start..index]);
+    let otherwise = parts.pop().unwrap();
+    let then = parts.pop().unwrap();
+    let condition = parts.pop().unwrap();
+    token.splice(start..index, Some(Token::If(If { condition: condition, then: then, otherwise: otherwise, text: text })));
+    true
+}
+
+//the end of the expression that starts at start, the next ',' (or 'in') or closing bracket
+fn expr_end(token: &[Token], start: usize, stop_at_in: bool) -> usize {
+    let mut depth = 0;
+    let mut end = start;
+    while end < token.len() {
+        match token[end] {
+            Token::BrOpen | Token::ListOpen => depth += 1,
+            Token::BrClose | Token::ListClose | Token::Seperator if depth == 0 => break,
+            Token::Keyword(ref word) if depth == 0 && stop_at_in && word == "in" => break,
+            Token::BrClose | Token::ListClose => depth -= 1,
+            _ => {}
+        }
+        end += 1;
+    }
+    end
+}
+
+//the postfix code of an expression, None if it is empty or malformed
+fn compile(token: &[Token]) -> Option<Vec<Token>> {
+    match interpreter::rearrange(token.to_vec()) {
+        Ok(v) if !v.is_empty() => Some(v),
+        _ => None
+    }
+}
+
+//the start and the names of the arguments in front of an arrow: 'X' or '(X, Y)'
+fn closure_args(token: &[Token]) -> Option<(usize, Vec<String>)> {
+    match token.last() {
+        Some(&Token::Variable(ref name)) => return Some((token.len() - 1, vec![name.clone()])),
+        Some(&Token::BrClose) => {},
+        _ => return None
+    }
+    let open = token.iter().rposition(|x| *x == Token::BrOpen)?;
+    if open > 0 {
+        if let Token::Function(_) = token[open - 1] {
+            return None
+        }
+    }
+    let mut args = Vec::new();
+    for (index, elem) in token[open + 1..token.len() - 1].iter().enumerate() {
+        match *elem {
+            Token::Variable(ref name) if index % 2 == 0 => args.push(name.clone()),
+            Token::Seperator if index % 2 == 1 => {},
+            _ => return None
+        }
+    }
+    Some((open, args))
+}
+
+//a variable followed by '(' is called
+fn mark_calls(token: Vec<Token>) -> Vec<Token> {
+    let mut result = Vec::new();
+    for (index, elem) in token.iter().enumerate() {
+        match *elem {
+            Token::Variable(ref name) if token.get(index + 1) == Some(&Token::BrOpen) => {
+                result.push(Token::Call(name.clone(), arg_count(&token[index + 2..])));
+            },
+            Token::BrClose if token.get(index + 1) == Some(&Token::BrOpen) => {
+                result.push(Token::BrClose);
+                result.push(Token::Apply(arg_count(&token[index + 2..])));
+            },
+            _ => result.push(elem.clone())
+        }
+    }
     result
 }
 
-fn match_token(word: &str, last_token: Token) -> Token {
+//the number of arguments before the closing bracket
+fn arg_count(token: &[Token]) -> usize {
+    if token.first() == Some(&Token::BrClose) {
+        return 0
+    }
+    let mut depth = 0;
+    let mut count = 1;
+    for elem in token {
+        match *elem {
+            Token::BrOpen | Token::ListOpen => depth += 1,
+            Token::BrClose | Token::ListClose if depth == 0 => break,
+            Token::BrClose | Token::ListClose => depth -= 1,
+            Token::Seperator if depth == 0 => count += 1,
+            _ => {}
+        }
+    }
+    count
+}
+
+//the tokens as they would be typed in
+fn text(token: &[Token]) -> String {
+    token.iter().map(|x| match *x {
+        Token::Add => String::from(" + "),
+        Token::Sub => String::from(" - "),
+        Token::UnarySub | Token::SubMonad => String::from("-"),
+        Token::Mul => String::from("*"),
+        Token::Div => String::from("/"),
+        Token::Pow => String::from("^"),
+        Token::PlusMinus => String::from(" ± "),
+        Token::BrOpen => String::from("("),
+        Token::BrClose => String::from(")"),
+        Token::ListOpen => String::from("["),
+        Token::ListClose => String::from("]"),
+        Token::Seperator => String::from(", "),
+        Token::Arrow => String::from(" -> "),
+        Token::Equals => String::from(" = "),
+        Token::GreaterThan => String::from(" > "),
+        Token::LesserThan => String::from(" < "),
+        Token::Keyword(ref word) => format!(" {} ", word),
+        Token::Let(ref l) => l.text.clone(),
+        Token::If(ref i) => i.text.clone(),
+        Token::Value(ref v) => v.literal(),
+        Token::Variable(ref name) | Token::Function(ref name) | Token::Call(ref name, _) => name.clone(),
+        //between the brackets of the function and of its arguments
+        Token::Apply(_) => String::new()
+    }).collect::<Vec<String>>().concat()
+}
+
+//'90 days' is a single duration, '3h 20min' is the sum of both
+fn merge_durations(token: Vec<Token>) -> Result<Vec<Token>, String> {
+    let mut result = Vec::new();
+    let mut iter = token.into_iter().peekable();
+    while let Some(elem) = iter.next() {
+        let unit = match elem {
+            Token::Function(ref name) if iter.peek() != Some(&Token::BrOpen) => Duration::unit(name),
+            _ => None
+        };
+        let count = match result.last() {
+            Some(&Token::Value(ref v)) if v.is_number() => Some(v.to_float()),
+            _ => None
+        };
+        let elem = match (unit, count) {
+            (Some(unit), Some(count)) => {
+                result.pop();
+                Token::Value(Value::Duration(Duration::of(count, unit)?))
+            },
+            _ => elem
+        };
+        match (result.last_mut(), elem) {
+            (Some(&mut Token::Value(Value::Duration(ref mut a))), Token::Value(Value::Duration(b))) => *a = a.add(b)?,
+            (_, elem) => result.push(elem)
+        }
+    }
+    Ok(result)
+}
+
+//the name of a function in the place of an argument is passed as a value, e.g. deriv(f, 2) or ':var F sin',
+//other names are still called, so 'sqrt 4' works and an unknown name is an error
+fn mark_fn_refs(token: Vec<Token>, functions: &HashMap<String, Function>) -> Vec<Token> {
+    let mut result = Vec::new();
+    let mut iter = token.into_iter().peekable();
+    while let Some(elem) = iter.next() {
+        let elem = match elem {
+            Token::Function(name) => {
+                let before = match result.last() {
+                    None | Some(&Token::BrOpen) | Some(&Token::Seperator) | Some(&Token::ListOpen) |
+                    Some(&Token::Equals) | Some(&Token::Arrow) => true,
+                    _ => false
+                };
+                let after = match iter.peek() {
+                    None | Some(&Token::BrClose) | Some(&Token::Seperator) | Some(&Token::ListClose) |
+                    Some(&Token::Keyword(_)) => true,
+                    _ => false
+                };
+                //functions without arguments, like now, are called
+                let arg_count = functions.get(&name).map(|x| x.arg_count()).or(stdlib::arity(&name));
+                if before && after && arg_count.map_or(false, |x| x > 0) {
+                    Token::Value(Value::FnRef(name))
+                }
+                else {
+                    Token::Function(name)
+                }
+            },
+            other => other
+        };
+        result.push(elem);
+    }
+    result
+}
+
+fn match_token(word: &str, last_token: Token, mode: NumericMode) -> Token {
     match word {
         "+" => Token::Add,
         "-" => {
             match last_token {
-                Token::Value(_) | Token::Function(_) | Token::Variable(_) => Token::Sub,
+                Token::Value(_) | Token::Function(_) | Token::Variable(_) | Token::BrClose | Token::ListClose => Token::Sub,
                 _ => Token::UnarySub
             }
         },
@@ -99,17 +436,25 @@ fn match_token(word: &str, last_token: Token) -> Token {
         "/" => Token::Div,
         "(" => Token::BrOpen,
         ")" => Token::BrClose,
+        "[" => Token::ListOpen,
+        "]" => Token::ListClose,
         "," => Token::Seperator,
         "^" => Token::Pow,
         "=" => Token::Equals,
         "<" => Token::LesserThan,
         ">" => Token::GreaterThan,
+        "±" => Token::PlusMinus,
+        "→" => Token::Arrow,
+        "let" | "in" => Token::Keyword(word.to_owned()),
+        _ if word.starts_with('"') => Token::Value(Value::Str(unquote(word))),
         _ => {
-            match Value::from_str(word) {
+            match Value::parse(word, mode) {
                 Ok(v) => Token::Value(v),
                 Err(_) => {
                     let mut wordstr = word.to_owned();
-                    if word.starts_with(char::is_uppercase) || word.starts_with('$'){
+                    //'geo.R' is the variable R of the module geo
+                    let name = word.rsplit('.').next().unwrap();
+                    if name.starts_with(char::is_uppercase) || word.starts_with('$'){
                         Token::Variable(wordstr)
                     }
                     else {
@@ -124,10 +469,63 @@ fn match_token(word: &str, last_token: Token) -> Token {
     }
 }
 
+//the words of a line, a string literal is a single word
+fn words(line: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut rest = line;
+    while let Some(start) = rest.find('"') {
+        result.extend(split_ops(&rest[..start]));
+        let end = start + string_len(&rest[start..]);
+        result.push(rest[start..end].to_owned());
+        rest = &rest[end..];
+    }
+    result.extend(split_ops(rest));
+    result
+}
+
+fn split_ops(part: &str) -> Vec<String> {
+    space_ops(part.replace("+-", "±").replace("->", "→")).split_whitespace().map(|x| x.to_owned()).collect()
+}
+
+//the length of the string literal at the start of src, to the end if it is not closed
+fn string_len(src: &str) -> usize {
+    let mut escaped = false;
+    for (index, ch) in src.char_indices().skip(1) {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return index + 1,
+            _ => {}
+        }
+    }
+    src.len()
+}
+
+//'"a \"b\""' -> 'a "b"'
+fn unquote(word: &str) -> String {
+    let inner = word[1..].strip_suffix('"').unwrap_or(&word[1..]);
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(c) => result.push(c),
+            None => result.push('\\')
+        }
+    }
+    result
+}
+
 fn space_ops(line: String) -> String {
     let mut result = String::new();
-    for ch in line.chars() {
-        if is_spaced(ch) {
+    let chars = line.chars().collect::<Vec<char>>();
+    for (index, &ch) in chars.iter().enumerate() {
+        if is_spaced(ch) && !in_date(&chars, index) && !in_exponent(&chars, index) {
             result.push(' ');
             result.push(ch);
             result.push(' ');
@@ -142,3 +540,63 @@ fn space_ops(line: String) -> String {
 fn is_spaced(ch: char) -> bool {
     SPACED_OPS.contains(&ch)
 }
+
+//the '-' in '1e-7' is not a minus
+fn in_exponent(chars: &[char], index: usize) -> bool {
+    if chars[index] != '-' || index < 2 || chars[index - 1] != 'e' || !chars.get(index + 1).map_or(false, |c| c.is_digit(10)) {
+        return false
+    }
+    //the part before the 'e' is a number and not the end of a name
+    let start = chars[..index - 1].iter().rposition(|c| !c.is_digit(10) && *c != '.').map_or(0, |x| x + 1);
+    start < index - 1 && chars[start].is_digit(10) && (start == 0 || !(chars[start - 1].is_alphanumeric() || chars[start - 1] == '_'))
+}
+
+//the '-' in '2026-10-18' is not a minus
+fn in_date(chars: &[char], index: usize) -> bool {
+    let pattern = "dddd-dd-dd".chars().collect::<Vec<char>>();
+    let boundary = |x: Option<&char>| x.map_or(true, |c| !c.is_alphanumeric() && *c != '.');
+    [4, 7].iter().filter(|&&offset| offset <= index).any(|&offset| {
+        let start = index - offset;
+        let end = start + pattern.len();
+        end <= chars.len() &&
+            pattern.iter().zip(chars[start..end].iter()).all(|(p, c)| if *p == 'd' { c.is_digit(10) } else { p == c }) &&
+            (start == 0 || boundary(chars.get(start - 1))) &&
+            (boundary(chars.get(end)) || chars[end] == 'T')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use engine::Engine;
+    use testing::run;
+
+    //only known functions in argument places are passed as values
+    #[test]
+    fn function_refs() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "foo"), "error: unknown function: foo");
+        assert_eq!(run(&mut engine, ":var X pii"), "error: unknown function: pii");
+        assert_eq!(run(&mut engine, "sqrt 4"), "2 [float]");
+        assert_eq!(run(&mut engine, "2 * sqrt 9"), "6 [float]");
+        run(&mut engine, ":fn sq X = X^2");
+        assert_eq!(run(&mut engine, "map(sq, [1, 2])"), "[1, 4] [list]");
+        run(&mut engine, ":var F sin");
+        assert_eq!(run(&mut engine, "F(0)"), "0 [float]");
+        assert_eq!(run(&mut engine, "sum(sq, 1, 3)"), "14 [int]");
+    }
+
+    #[test]
+    fn let_expressions() {
+        let mut engine = Engine::new();
+        assert_eq!(run(&mut engine, "let A = 2, B = A + 1 in A*B"), "6 [int]");
+        run(&mut engine, ":var A 10");
+        assert_eq!(run(&mut engine, "let A = 1 in A + 1"), "2 [int]");
+        assert_eq!(run(&mut engine, "A"), "10 [int]");
+        assert_eq!(run(&mut engine, "let X = 2 in let Y = X*3 in X + Y"), "8 [int]");
+        assert_eq!(run(&mut engine, "map(X -> let Y = X*2 in Y + 1, [1, 2])"), "[3, 5] [list]");
+        for line in &["let A = 1 A", "let in 2", "let a = 1 in a"] {
+            assert_eq!(run(&mut engine, line), "error: malformed let expression, expected 'let A = ..., B = ... in ...'");
+        }
+        assert_eq!(run(&mut engine, "let B = C in 1"), "error: variable 'C' not found");
+    }
+}
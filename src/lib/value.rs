@@ -1,12 +1,27 @@
 use std::str::FromStr;
 use std::fmt::{Display, Formatter, self};
 
+use decimal::Decimal;
+use bigfloat::BigFloat;
+use interval::Interval;
+use datetime::{DateTime, Duration};
+use function::Closure;
+
 const NUMBERS: [char; 10] = ['0','1','2','3','4','5','6','7','8','9'];
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Integer(i64),
-    Float(f64)
+    Float(f64),
+    Decimal(Decimal),
+    BigFloat(BigFloat),
+    Interval(Interval),
+    DateTime(DateTime),
+    Duration(Duration),
+    Str(String),
+    FnRef(String),
+    Function(Closure),
+    List(Vec<Value>)
 }
 
 impl Display for Value {
@@ -17,6 +32,33 @@ impl Display for Value {
             },
             Value::Float(v) => {
                 write!(fmt, "{} [float]", v)
+            },
+            Value::Decimal(v) => {
+                write!(fmt, "{} [decimal]", v)
+            },
+            Value::BigFloat(ref v) => {
+                write!(fmt, "{} [bigfloat]", v)
+            },
+            Value::Interval(v) => {
+                write!(fmt, "{} [interval]", v)
+            },
+            Value::DateTime(v) => {
+                write!(fmt, "{} [{}]", v, v.type_str())
+            },
+            Value::Duration(v) => {
+                write!(fmt, "{} [duration]", v)
+            },
+            Value::Str(_) => {
+                write!(fmt, "{} [string]", self.literal())
+            },
+            Value::FnRef(ref name) => {
+                write!(fmt, "{} [function]", name)
+            },
+            Value::Function(ref v) => {
+                write!(fmt, "{} [function]", v)
+            },
+            Value::List(_) => {
+                write!(fmt, "{} [list]", self.literal())
             }
         }
     }
@@ -37,11 +79,37 @@ impl Value {
         }
     }
 
+    pub fn is_decimal(&self) -> bool {
+        match *self {
+            Value::Decimal(_) => true,
+            _ => false
+        }
+    }
+
+    pub fn is_bigfloat(&self) -> bool {
+        match *self {
+            Value::BigFloat(_) => true,
+            _ => false
+        }
+    }
+
+    pub fn is_interval(&self) -> bool {
+        match *self {
+            Value::Interval(_) => true,
+            _ => false
+        }
+    }
+
+    //int, float, decimal or bigfloat
+    pub fn is_number(&self) -> bool {
+        self.is_float_or_int() || self.is_decimal() || self.is_bigfloat()
+    }
+
     pub fn is_float_or_int(&self) -> bool {
         match *self {
             Value::Integer(_) => true,
             Value::Float(_) => true,
-            //_ => false
+            _ => false
         }
     }
 
@@ -49,7 +117,9 @@ impl Value {
         match *self {
             Value::Integer(v) => v as f64,
             Value::Float(v) => v,
-            //_ => panic!("to float failed")
+            Value::Decimal(v) => v.to_f64(),
+            Value::BigFloat(ref v) => v.to_f64(),
+            _ => panic!("to float failed")
         }
     }
 
@@ -57,15 +127,98 @@ impl Value {
         match *self {
             Value::Integer(v) => v,
             Value::Float(v) => v as i64,
-            //_ => panic!("to int failed")
+            Value::Decimal(v) => v.to_i64(),
+            Value::BigFloat(ref v) => v.to_i64(),
+            _ => panic!("to int failed")
+        }
+    }
+
+    //ints are exact decimals, floats are not converted
+    pub fn to_decimal(&self) -> Option<Decimal> {
+        match *self {
+            Value::Integer(v) => Some(Decimal::from_int(v)),
+            Value::Decimal(v) => Some(v),
+            _ => None
+        }
+    }
+
+    //numbers are intervals with a single number
+    pub fn to_interval(&self) -> Option<Interval> {
+        match *self {
+            Value::Interval(v) => Some(v),
+            _ if self.is_number() => Some(Interval::exact(self.to_float())),
+            _ => None
+        }
+    }
+
+    //every number converts, floats with their shortest representation
+    pub fn to_bigfloat(&self) -> Option<BigFloat> {
+        match *self {
+            Value::Integer(v) => Some(BigFloat::from_int(v)),
+            Value::Float(v) => BigFloat::from_f64(v),
+            Value::Decimal(v) => BigFloat::parse(&format!("{}", v)),
+            Value::BigFloat(ref v) => Some(v.clone()),
+            _ => None
+        }
+    }
+
+    //the value as it would be typed in, without the type
+    pub fn literal(&self) -> String {
+        match *self {
+            Value::Integer(v) => format!("{}", v),
+            Value::Float(v) => format!("{:?}", v),
+            Value::Decimal(v) => format!("{}d", v),
+            Value::BigFloat(ref v) => format!("{}p", v),
+            Value::Interval(v) => format!("{}", v),
+            Value::DateTime(v) => format!("{}", v),
+            Value::Duration(v) => format!("{}", v),
+            Value::Str(ref s) => quote(s),
+            Value::FnRef(ref name) => name.clone(),
+            Value::Function(ref v) => format!("({})", v),
+            Value::List(ref items) => {
+                format!("[{}]", items.iter().map(|x| x.literal()).collect::<Vec<String>>().join(", "))
+            }
         }
     }
 
     pub fn type_str(&self) -> String {
         match *self {
             Value::Integer(_) => String::from("integer"),
-            Value::Float(_) => String::from("float")
+            Value::Float(_) => String::from("float"),
+            Value::Decimal(_) => String::from("decimal"),
+            Value::BigFloat(_) => String::from("bigfloat"),
+            Value::Interval(_) => String::from("interval"),
+            Value::DateTime(v) => String::from(v.type_str()),
+            Value::Duration(_) => String::from("duration"),
+            Value::Str(_) => String::from("string"),
+            Value::FnRef(_) | Value::Function(_) => String::from("function"),
+            Value::List(_) => String::from("list")
+        }
+    }
+}
+
+//how number literals without a suffix are read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumericMode {
+    Float,
+    Decimal,
+    BigFloat
+}
+
+impl Value {
+    //like from_str, but '0.1' is a decimal in decimal mode and a bigfloat in bigfloat mode
+    pub fn parse(src: &str, mode: NumericMode) -> Result<Value, ()> {
+        if mode == NumericMode::Decimal && !is_int(src) {
+            if let Some(v) = Decimal::parse(src) {
+                return Ok(Value::Decimal(v))
+            }
         }
+        if mode == NumericMode::BigFloat && !is_int(src) {
+            if let Some(v) = BigFloat::parse(src) {
+                return Ok(Value::BigFloat(v))
+            }
+        }
+        Value::from_str(src)
     }
 }
 
@@ -76,6 +229,24 @@ impl FromStr for Value {
         if is_int(src) {
             Ok(Value::Integer(src.parse::<i64>().unwrap()))
         }
+        else if src.ends_with('d') {
+            match Decimal::parse(&src[..src.len() - 1]) {
+                Some(v) => Ok(Value::Decimal(v)),
+                None => Err(())
+            }
+        }
+        else if src.ends_with('p') {
+            match BigFloat::parse(&src[..src.len() - 1]) {
+                Some(v) => Ok(Value::BigFloat(v)),
+                None => Err(())
+            }
+        }
+        else if let Some(v) = DateTime::parse(src) {
+            Ok(Value::DateTime(v))
+        }
+        else if let Some(v) = Duration::parse(src) {
+            Ok(Value::Duration(v))
+        }
         else {
             match src.parse::<f64>() {
                 Ok(v) => Ok(Value::Float(v)),
@@ -85,6 +256,22 @@ impl FromStr for Value {
     }
 }
 
+//a string literal with the quotes and backslashes escaped
+fn quote(text: &str) -> String {
+    let mut result = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            _ => result.push(ch)
+        }
+    }
+    result.push('"');
+    result
+}
+
 fn is_int(src: &str) -> bool {
     let mut chars = src.chars();
     let minus_check = match chars.next() {
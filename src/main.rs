@@ -1,24 +1,68 @@
 extern crate rustyline;
 extern crate termcalc;
 
+mod completion;
+mod history;
+
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::env;
+use std::process;
+use std::thread;
 
 use termcalc::engine::{Engine, LineResult};
 
+use completion::Completion;
+use history::History;
+
+//entries shown by ':hist'
+const HIST_COUNT: usize = 20;
+
+//the 8MB of the main thread are not enough for interpreter::MAX_CALL_DEPTH calls in a debug build
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
 fn main() {
-    let mut engine = Engine::new();
-    let mut rl = Editor::<()>::new();
-    let mut counter = 0;
+    let child = thread::Builder::new().stack_size(STACK_SIZE).spawn(run).unwrap();
+    if child.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn run() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if args.get(0).map(|x| x.as_str()) == Some("--test") {
+        process::exit(run_tests(&args[1..]));
+    }
+    let mut engine = new_engine();
+    let mut rl = Editor::<Completion>::new();
+    rl.set_helper(Some(Completion::new()));
+    let mut history = History::load();
+    for entry in history.entries() {
+        rl.add_history_entry(&entry.0);
+    }
     loop {
-        let readline = rl.readline("> ");
+        if let Some(helper) = rl.helper_mut() {
+            helper.update(&engine);
+        }
+        let readline = read_continued(&mut rl);
         match readline {
             Ok(line) => {
+                let line = match recall(line, &history) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        println!("    error: {}", e);
+                        continue;
+                    }
+                };
                 if line == ":q" || line == ":quit" {
-                    return;
+                    break
+                }
+                if line == ":hist" || line.starts_with(":hist ") {
+                    print_history(&history, line[":hist".len()..].trim());
+                    continue;
                 }
                 if line.starts_with(":") {
                     if execute_cmd(&line, &engine) {
@@ -26,19 +70,22 @@ fn main() {
                     }
                 }
                 rl.add_history_entry(&line);
-                let result = engine.eval_line(line);
-                match result {
-                    LineResult::Error(s) => {
-                        println!("    error: {}", s)
-                    },
+                let result = engine.eval_line(line.clone());
+                for output in engine.take_output() {
+                    println!("    {}", output);
+                }
+                let result_text = match result {
+                    LineResult::Error(s) => format!("error: {}", s),
                     LineResult::Value(v) => {
-                        let counter_str = format!("${}", counter);
-                        println!("    {}: {}", counter_str, v);
-                        engine.set_val(counter_str, v);
-                        counter += 1;
+                        let text = format!("{}", v);
+                        format!("{}: {}", engine.add_result(v), text)
                     },
-                    LineResult::Success => {}
+                    LineResult::Success => String::new()
+                };
+                if !result_text.is_empty() {
+                    println!("    {}", result_text.replace('\n', "\n    "));
                 }
+                history.add(&line, &result_text);
             },
             Err(ReadlineError::Interrupted) => {
                 println!("[Interrupt]");
@@ -54,6 +101,85 @@ fn main() {
             }
         }
     }
+    if let LineResult::Error(e) = engine.save_on_exit() {
+        println!("    error: {}", e);
+    }
+}
+
+//with the directories in TERMCALC_PATH (separated by ':') as the search path of ':import'
+fn new_engine() -> Engine {
+    let mut engine = Engine::new();
+    if let Ok(dirs) = env::var("TERMCALC_PATH") {
+        for dir in dirs.split(':').filter(|x| !x.is_empty()) {
+            engine.add_search_dir(dir.to_owned());
+        }
+    }
+    engine
+}
+
+//'termcalc --test a.tc b.tc': runs every script with a new engine and counts the assertions
+fn run_tests(paths: &[String]) -> i32 {
+    if paths.is_empty() {
+        println!("usage: termcalc --test [script] [script] [...]");
+        return 2;
+    }
+    let (mut passed, mut failed) = (0, 0);
+    for path in paths {
+        let mut engine = new_engine();
+        engine.start_tests();
+        let result = engine.eval_line(format!(":load {}", path));
+        let report = engine.take_tests().unwrap();
+        println!("{}: {} passed, {} failed", path, report.passed, report.failed.len());
+        for message in report.failed.iter() {
+            println!("    {}", message.replace('\n', "\n    "));
+        }
+        passed += report.passed;
+        failed += report.failed.len();
+        if let LineResult::Error(e) = result {
+            println!("    {}", e.replace('\n', "\n    "));
+            failed += 1;
+        }
+    }
+    println!("{} passed, {} failed", passed, failed);
+    if failed > 0 { 1 } else { 0 }
+}
+
+//a line ending with '\\' continues in the next line
+fn read_continued(rl: &mut Editor<Completion>) -> Result<String, ReadlineError> {
+    let mut line = rl.readline("> ")?;
+    while line.trim_end().ends_with('\\') {
+        let len = line.trim_end().len();
+        line.truncate(len - 1);
+        line.push(' ');
+        line.push_str(&rl.readline("... ")?);
+    }
+    Ok(line)
+}
+
+//'!12' is the input of entry 12 of the history
+fn recall(line: String, history: &History) -> Result<String, String> {
+    if !line.starts_with('!') {
+        return Ok(line)
+    }
+    match line[1..].trim().parse::<usize>().ok().and_then(|n| history.input(n)) {
+        Some(input) => {
+            println!("> {}", input);
+            Ok(input.clone())
+        },
+        None => Err(format!("'{}' is not in the history, see ':hist'", line.trim()))
+    }
+}
+
+//':hist' shows the last entries, ':hist text' the last entries with text in the input or the result
+fn print_history(history: &History, text: &str) {
+    for (n, entry) in history.search(text, HIST_COUNT) {
+        if entry.1.is_empty() {
+            println!("    !{} {}", n, entry.0);
+        }
+        else {
+            println!("    !{} {}    -> {}", n, entry.0, entry.1);
+        }
+    }
 }
 
 fn execute_cmd(cmd: &str, engine: &Engine) -> bool {
@@ -63,7 +189,10 @@ fn execute_cmd(cmd: &str, engine: &Engine) -> bool {
         },
         ":fns" => {
             for elem in engine.functions() {
-                println!("    {} : {:?}", elem.0, elem.1.args());
+                match engine.fn_body(elem.0) {
+                    Ok(body) => println!("    {} {} = {}", elem.0, elem.1.args().join(" "), body),
+                    Err(_) => println!("    {} : {:?}", elem.0, elem.1.args())
+                }
             }
         },
         _ => return false
@@ -76,3 +205,36 @@ fn print_hashmap<T>(hm: &HashMap<String, T>) where T: Display {
         println!("    {} : {}", elem.0, elem.1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    //the exit code of 'termcalc --test' for a script
+    fn test_exit_code(name: &str, script: &str) -> i32 {
+        let path = env::temp_dir().join(format!("termcalc-test-{}-{}", process::id(), name));
+        fs::write(&path, script).unwrap();
+        let code = run_tests(&[path.to_string_lossy().into_owned()]);
+        fs::remove_file(path).unwrap();
+        code
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(test_exit_code("passed.tc", ":assert 1\n:assert_close 0.1+0.2 0.3\n"), 0);
+        assert_eq!(test_exit_code("failed.tc", ":assert 1 = 2\n:assert 1\n"), 1);
+        assert_eq!(test_exit_code("broken.tc", ":var x 1\n"), 1);
+        assert_eq!(run_tests(&[]), 2);
+    }
+
+    #[test]
+    fn recalled_lines() {
+        let mut history = History::from_file(None);
+        history.add("2+3", "$0: 5 [int]");
+        assert_eq!(recall(format!("1+1"), &history), Ok(format!("1+1")));
+        assert_eq!(recall(format!("!1"), &history), Ok(format!("2+3")));
+        assert_eq!(recall(format!("!2"), &history), Err(format!("'!2' is not in the history, see ':hist'")));
+        assert_eq!(recall(format!("!x "), &history), Err(format!("'!x' is not in the history, see ':hist'")));
+    }
+}